@@ -0,0 +1,9 @@
+use serde::Deserialize;
+use validator::Validate;
+
+use crate::auth::types::Role;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct GrantRoleDto {
+  pub role: Role,
+}
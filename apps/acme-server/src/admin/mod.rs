@@ -0,0 +1,115 @@
+use actix_web::{delete, get, http::StatusCode, middleware::from_fn, post, web, HttpResponse};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+  admin::dto::GrantRoleDto,
+  auth::{
+    constants::AuthMessage,
+    guard::{auth_middleware, require_permission, require_role, AuthUser},
+    roles::RoleService,
+    types::{Permission, Role},
+  },
+  common::{error::AppError, extractors::ValidatedJson, functionalities::api_res::api_success},
+  subscribers::{constants::SubscriberMessage, service::SubscriberService},
+  user::{service::UserService, types::User},
+  AppState,
+};
+
+mod dto;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+  cfg.service(
+    web::scope("/admin")
+      .wrap(from_fn(auth_middleware))
+      .service(admin_user_roles)
+      .service(admin_grant_role)
+      .service(admin_revoke_role)
+      .service(admin_send_newsletter),
+  );
+}
+
+/// The user with their roles and permissions.
+#[get(
+  "/users/{user_id}/roles",
+  wrap = "require_permission(Permission::UsersRead)"
+)]
+async fn admin_user_roles(
+  _auth: AuthUser,
+  path: web::Path<Uuid>,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let user = UserService::me(&data, path.into_inner()).await?;
+
+  Ok(api_success::<User, AuthMessage>(
+    StatusCode::OK,
+    user,
+    AuthMessage::AuthRoleListSuccess,
+  ))
+}
+
+#[post("/users/{user_id}/roles", wrap = "require_role(Role::Admin)")]
+async fn admin_grant_role(
+  auth: AuthUser,
+  path: web::Path<Uuid>,
+  credentials: ValidatedJson<GrantRoleDto>,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = path.into_inner();
+
+  UserService::me(&data, user_id).await?;
+  RoleService::grant(&data.db, user_id, credentials.role, Some(auth.id)).await?;
+  let user = UserService::me(&data, user_id).await?;
+
+  Ok(api_success::<User, AuthMessage>(
+    StatusCode::OK,
+    user,
+    AuthMessage::AuthRoleGrantSuccess,
+  ))
+}
+
+#[delete("/users/{user_id}/roles/{role}", wrap = "require_role(Role::Admin)")]
+async fn admin_revoke_role(
+  auth: AuthUser,
+  path: web::Path<(Uuid, Role)>,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let (user_id, role) = path.into_inner();
+
+  // Keeps an admin from locking themselves out of this very endpoint
+  if user_id == auth.id && role == Role::Admin {
+    return Err(AuthMessage::AuthRoleSelfRevoke.into());
+  }
+
+  RoleService::revoke(&data, user_id, role).await?;
+  let user = UserService::me(&data, user_id).await?;
+
+  Ok(api_success::<User, AuthMessage>(
+    StatusCode::OK,
+    user,
+    AuthMessage::AuthRoleRevokeSuccess,
+  ))
+}
+
+#[derive(Serialize)]
+struct NewsletterQueued {
+  queued: usize,
+}
+
+/// Sends the newsletter to every confirmed subscriber through the outbox.
+#[post(
+  "/newsletter/send",
+  wrap = "require_permission(Permission::NewsletterSend)"
+)]
+async fn admin_send_newsletter(
+  _auth: AuthUser,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let queued = SubscriberService::queue_newsletter(&data).await?;
+
+  Ok(api_success::<NewsletterQueued, SubscriberMessage>(
+    StatusCode::OK,
+    NewsletterQueued { queued },
+    SubscriberMessage::SubscriberNewsletterQueueSuccess,
+  ))
+}
@@ -0,0 +1,265 @@
+use actix_web::{http::header, web, HttpRequest};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+  auth::{
+    client_ip,
+    constants::{AuthMessage, NOT_ME_PURPOSE, NOT_ME_TTL_SECS},
+    sessions::SessionRegistry,
+    tokens::TokenService,
+  },
+  common::{
+    functionalities::{geoip, signing},
+    i18n::Locale,
+  },
+  email::{emails::NewSignInEmail, outbox::EmailOutbox},
+  otp_code::{self, types::OtpPurpose},
+  user::types::User,
+  AppState,
+};
+
+/// Remembers the devices each user signs in from and emails them when a new one shows up.
+///
+/// A device is the user agent plus the approximate location, falling back to the IP when the
+/// GeoIP database knows nothing about it.
+pub struct SignInAlertService;
+
+impl SignInAlertService {
+  /// Records the device behind a completed sign-in; `sid` is the session it opened, if any.
+  ///
+  /// Never fails the sign-in, problems are only logged.
+  pub async fn record(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    user: &User,
+    sid: Option<&str>,
+  ) {
+    let user_agent = req
+      .headers()
+      .get(header::USER_AGENT)
+      .and_then(|value| value.to_str().ok())
+      .unwrap_or("unknown")
+      .to_string();
+    let ip = client_ip(req);
+    let location = geoip::locate(&ip);
+    let fingerprint = hex::encode(Sha256::digest(format!(
+      "{user_agent}|{}",
+      location.as_deref().unwrap_or(&ip)
+    )));
+
+    // The device and its alert are stored together, so a new device is never left unreported
+    let mut tx = match data.db.begin().await {
+      Ok(tx) => tx,
+      Err(e) => {
+        println!("{:?}", e);
+        return;
+      },
+    };
+
+    // Sign-ins of the same user take turns from here on, so two new devices at once can't both
+    // count as the first one
+    if let Err(e) = sqlx::query(
+      r#"
+        SELECT id
+        FROM users
+        WHERE id = $1::uuid
+        FOR NO KEY UPDATE
+      "#,
+    )
+    .bind(user.id)
+    .execute(&mut *tx)
+    .await
+    {
+      println!("{:?}", e);
+      return;
+    }
+
+    let known = match sqlx::query_scalar::<_, i64>(
+      r#"
+        SELECT COUNT(*)
+        FROM sign_in_devices
+        WHERE user_id = $1::uuid
+      "#,
+    )
+    .bind(user.id)
+    .fetch_one(&mut *tx)
+    .await
+    {
+      Ok(known) => known,
+      Err(e) => {
+        println!("{:?}", e);
+        return;
+      },
+    };
+
+    // `xmax = 0` only holds for freshly inserted rows
+    let device = sqlx::query_as::<_, (Uuid, bool)>(
+      r#"
+        INSERT INTO sign_in_devices (user_id, fingerprint, user_agent, ip, location)
+        VALUES ($1::uuid, $2, $3, $4, $5)
+        ON CONFLICT (user_id, fingerprint)
+        DO UPDATE SET ip = EXCLUDED.ip, last_seen_at = NOW()
+        RETURNING id, (xmax = 0)
+      "#,
+    )
+    .bind(user.id)
+    .bind(&fingerprint)
+    .bind(&user_agent)
+    .bind(&ip)
+    .bind(&location)
+    .fetch_one(&mut *tx)
+    .await;
+
+    let device_id = match device {
+      // The very first device is the one the account was made on, nothing to warn about
+      Ok((device_id, true)) if known > 0 => device_id,
+      Ok(_) => {
+        if let Err(e) = tx.commit().await {
+          println!("{:?}", e);
+        }
+        return;
+      },
+      Err(e) => {
+        println!("{:?}", e);
+        return;
+      },
+    };
+
+    let expires_at = Utc::now().timestamp() + NOT_ME_TTL_SECS;
+    let payload = format!("{device_id}.{}.{expires_at}", sid.unwrap_or_default());
+    let token = format!("{payload}.{}", signing::sign(NOT_ME_PURPOSE, &payload));
+
+    let locale = user.locale();
+    let unknown = match locale {
+      Locale::En => "Unknown",
+      Locale::Es => "Desconocida",
+    };
+    let email = NewSignInEmail {
+      username: &user.username,
+      email: &user.email,
+      location: location.as_deref().unwrap_or(unknown),
+      signed_in_at: &locale.format_datetime(Utc::now(), user.timezone()),
+      ip: &ip,
+      user_agent: &user_agent,
+      not_me_link: &data.config.links.url(&format!("/not-me?token={token}")),
+    };
+
+    if let Err(e) =
+      EmailOutbox::enqueue(&mut *tx, &data.config.mail, &user.email, locale, &email).await
+    {
+      println!("{:?}", e);
+      return;
+    }
+
+    if let Err(e) = tx.commit().await {
+      println!("{:?}", e);
+    }
+  }
+
+  /// "This wasn't me": signs the reported sign-in out, forgets its device and starts a password
+  /// reset.
+  pub async fn not_me(data: &web::Data<AppState>, token: &str) -> Result<(), AuthMessage> {
+    let (payload, signature) = token
+      .rsplit_once('.')
+      .ok_or(AuthMessage::AuthNotMeTokenInvalid)?;
+    if !signing::verify(NOT_ME_PURPOSE, payload, signature) {
+      return Err(AuthMessage::AuthNotMeTokenInvalid);
+    }
+
+    let mut parts = payload.splitn(3, '.');
+    let (Some(device_id), Some(sid), Some(expires_at)) = (parts.next(), parts.next(), parts.next())
+    else {
+      return Err(AuthMessage::AuthNotMeTokenInvalid);
+    };
+    let device_id = Uuid::parse_str(device_id).map_err(|_| AuthMessage::AuthNotMeTokenInvalid)?;
+    if expires_at.parse::<i64>().unwrap_or_default() < Utc::now().timestamp() {
+      return Err(AuthMessage::AuthNotMeTokenInvalid);
+    }
+
+    let user = sqlx::query_as::<_, User>(
+      r#"
+        DELETE FROM sign_in_devices d
+        USING users u
+        WHERE d.id = $1::uuid AND u.id = d.user_id
+        RETURNING u.*
+      "#,
+    )
+    .bind(device_id)
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthRevokeSessionsFailed
+    })?
+    // Already reported
+    .ok_or(AuthMessage::AuthNotMeTokenInvalid)?;
+
+    if sid.is_empty() {
+      // Bearer sign-ins have no session; drop every refresh token instead
+      TokenService::revoke_all(data, user.id).await?;
+    } else {
+      SessionRegistry::revoke(data, user.id, sid).await;
+    }
+
+    if let Err(e) = otp_code::send_code(data, &user, OtpPurpose::ResetPassword).await {
+      eprintln!("Password reset after sign-in report failed: {:?}", e);
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::test::TestRequest;
+
+  use super::*;
+  use crate::common::{functionalities::password, testing};
+
+  #[actix_web::test]
+  #[ignore = "needs a migrated Postgres (TEST_DATABASE_URL) and Redis (TEST_REDIS_URL)"]
+  async fn concurrent_first_sign_ins_alert_once() {
+    let state = testing::state().await;
+    let username = format!("duck-{}", Uuid::new_v4().simple());
+    let user = sqlx::query_as::<_, User>(
+      r#"
+        INSERT INTO users (username, email, first_name, last_name, password_hash)
+        VALUES ($1, $2, 'Duck', 'Tester', $3)
+        RETURNING *
+      "#,
+    )
+    .bind(&username)
+    .bind(format!("{username}@example.com"))
+    .bind(password::hash("correct horse battery").await.unwrap())
+    .fetch_one(&state.data.db)
+    .await
+    .unwrap();
+
+    let laptop = TestRequest::default()
+      .insert_header((header::USER_AGENT, "laptop"))
+      .to_http_request();
+    let phone = TestRequest::default()
+      .insert_header((header::USER_AGENT, "phone"))
+      .to_http_request();
+    tokio::join!(
+      SignInAlertService::record(&state.data, &laptop, &user, None),
+      SignInAlertService::record(&state.data, &phone, &user, None),
+    );
+
+    // Whichever came first is the account's first device, the other one is new
+    let alerts = sqlx::query_scalar::<_, i64>(
+      r#"
+        SELECT COUNT(*)
+        FROM email_outbox
+        WHERE recipient = $1
+      "#,
+    )
+    .bind(&user.email)
+    .fetch_one(&state.data.db)
+    .await
+    .unwrap();
+    assert_eq!(alerts, 1);
+  }
+}
@@ -1,18 +1,90 @@
 use std::{error::Error, fmt};
 
+use actix_web::http::StatusCode;
 use serde::{Deserialize, Serialize};
 
+use crate::common::i18n::Translate;
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Serialize, Deserialize)]
 pub enum AuthMessage {
   AuthSigninSuccess,
   AuthSignoutSuccess,
+  AuthTwoFactorRequired,
+  AuthTwoFactorEnrollSuccess,
+  AuthTwoFactorEnableSuccess,
+  AuthTwoFactorDisableSuccess,
+  AuthListSessionsSuccess,
+  AuthRevokeSessionSuccess,
+  AuthRevokeOtherSessionsSuccess,
+  AuthTokenIssueSuccess,
+  AuthTokenRefreshSuccess,
+  AuthTokenRevokeSuccess,
+  AuthPersonalTokenCreateSuccess,
+  AuthPersonalTokenListSuccess,
+  AuthPersonalTokenRevokeSuccess,
+  AuthRoleListSuccess,
+  AuthRoleGrantSuccess,
+  AuthRoleRevokeSuccess,
+  AuthOAuthAuthorizeSuccess,
+  AuthNotMeSuccess,
   // ERROR
   AuthUserNotFound,
   AuthInsertUserIdSessionFailed,
-  AuthPasswordInvalid,
   AuthSigninFailed,
+  AuthInvalidCredentials,
+  AuthAccountLocked,
+  AuthTooManyAttempts,
+  AuthThrottleUnavailable,
+  AuthRevokeSessionsFailed,
+  AuthListSessionsFailed,
+  AuthSessionNotFound,
+  AuthEmailNotVerified,
+  AuthTwoFactorFailed,
+  AuthTwoFactorAlreadyEnabled,
+  AuthTwoFactorNotEnrolled,
+  AuthTwoFactorNotEnabled,
+  AuthTwoFactorCodeInvalid,
+  AuthTwoFactorNoPendingSignin,
+  AuthTokenIssueFailed,
+  AuthAccessTokenInvalid,
+  AuthRefreshTokenInvalid,
+  AuthRefreshTokenReused,
+  AuthPersonalTokenCreateFailed,
+  AuthPersonalTokenListFailed,
+  AuthPersonalTokenRevokeFailed,
+  AuthPersonalTokenNotFound,
+  AuthPersonalTokenInvalid,
+  AuthPersonalTokenScopesEmpty,
+  AuthPersonalTokenExpiryInvalid,
+  AuthTokenScopeMissing,
+  AuthForbidden,
+  AuthRoleLookupFailed,
+  AuthRoleGrantFailed,
+  AuthRoleRevokeFailed,
+  AuthRoleNotAssigned,
+  AuthRoleSelfRevoke,
+  AuthOAuthProviderNotConfigured,
+  AuthOAuthStateMismatch,
+  AuthOAuthExchangeFailed,
+  AuthOAuthIdTokenInvalid,
+  AuthOAuthEmailNotVerified,
+  AuthOAuthAccountUnverified,
+  AuthOAuthLinkFailed,
+  AuthNotMeTokenInvalid,
 }
 
+/// Failed sign-ins allowed for a single account inside the window before it gets locked.
+pub const MAX_FAILED_ATTEMPTS_PER_USER: i64 = 5;
+/// Failed sign-ins allowed from a single IP inside the window, across all accounts.
+pub const MAX_FAILED_ATTEMPTS_PER_IP: i64 = 20;
+/// How long (seconds) failed attempts are remembered.
+pub const FAILED_ATTEMPTS_WINDOW_SECS: i64 = 15 * 60;
+/// How long (seconds) an account or IP stays locked once the limit is hit.
+pub const LOCKOUT_DURATION_SECS: i64 = 15 * 60;
+/// When (seconds) to retry a sign-in refused because the failure counters couldn't be reached.
+pub const THROTTLE_UNAVAILABLE_RETRY_SECS: i64 = 30;
+
 impl fmt::Display for AuthMessage {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     write!(f, "{:?}", self)
@@ -20,3 +92,115 @@ impl fmt::Display for AuthMessage {
 }
 
 impl Error for AuthMessage {}
+
+// Only the code for now
+impl Translate for AuthMessage {}
+
+impl AuthMessage {
+  /// Status the message is answered with when it is an error.
+  pub fn status_code(&self) -> StatusCode {
+    use AuthMessage::*;
+
+    match self {
+      AuthUserNotFound
+      | AuthSessionNotFound
+      | AuthPersonalTokenNotFound
+      | AuthRoleNotAssigned
+      | AuthOAuthProviderNotConfigured => StatusCode::NOT_FOUND,
+      AuthInvalidCredentials
+      | AuthTwoFactorRequired
+      | AuthTwoFactorCodeInvalid
+      | AuthTwoFactorNoPendingSignin
+      | AuthAccessTokenInvalid
+      | AuthRefreshTokenInvalid
+      | AuthRefreshTokenReused
+      | AuthPersonalTokenInvalid
+      | AuthOAuthStateMismatch
+      | AuthOAuthIdTokenInvalid => StatusCode::UNAUTHORIZED,
+      AuthForbidden
+      | AuthTokenScopeMissing
+      | AuthEmailNotVerified
+      | AuthRoleSelfRevoke
+      | AuthOAuthEmailNotVerified
+      | AuthOAuthAccountUnverified => StatusCode::FORBIDDEN,
+      AuthTwoFactorAlreadyEnabled => StatusCode::CONFLICT,
+      AuthAccountLocked => StatusCode::LOCKED,
+      AuthTooManyAttempts => StatusCode::TOO_MANY_REQUESTS,
+      AuthThrottleUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+      AuthTwoFactorNotEnrolled
+      | AuthTwoFactorNotEnabled
+      | AuthPersonalTokenScopesEmpty
+      | AuthPersonalTokenExpiryInvalid
+      | AuthNotMeTokenInvalid => StatusCode::BAD_REQUEST,
+      AuthOAuthExchangeFailed => StatusCode::BAD_GATEWAY,
+      AuthInsertUserIdSessionFailed
+      | AuthSigninFailed
+      | AuthRevokeSessionsFailed
+      | AuthListSessionsFailed
+      | AuthTwoFactorFailed
+      | AuthTokenIssueFailed
+      | AuthPersonalTokenCreateFailed
+      | AuthPersonalTokenListFailed
+      | AuthPersonalTokenRevokeFailed
+      | AuthRoleLookupFailed
+      | AuthRoleGrantFailed
+      | AuthRoleRevokeFailed
+      | AuthOAuthLinkFailed => StatusCode::INTERNAL_SERVER_ERROR,
+      AuthSigninSuccess
+      | AuthSignoutSuccess
+      | AuthTwoFactorEnrollSuccess
+      | AuthTwoFactorEnableSuccess
+      | AuthTwoFactorDisableSuccess
+      | AuthListSessionsSuccess
+      | AuthRevokeSessionSuccess
+      | AuthRevokeOtherSessionsSuccess
+      | AuthTokenIssueSuccess
+      | AuthTokenRefreshSuccess
+      | AuthTokenRevokeSuccess
+      | AuthPersonalTokenCreateSuccess
+      | AuthPersonalTokenListSuccess
+      | AuthPersonalTokenRevokeSuccess
+      | AuthRoleListSuccess
+      | AuthRoleGrantSuccess
+      | AuthRoleRevokeSuccess
+      | AuthOAuthAuthorizeSuccess
+      | AuthNotMeSuccess => StatusCode::OK,
+    }
+  }
+}
+
+/// Issuer shown by authenticator apps next to the account name.
+pub const TOTP_ISSUER: &str = "Acme";
+/// How long (seconds) a password-verified sign-in waits for its second factor.
+pub const PENDING_TWO_FACTOR_TTL_SECS: i64 = 5 * 60;
+/// Recovery codes handed out when 2FA gets enabled.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+pub const RECOVERY_CODE_LENGTH: usize = 10;
+
+/// Signing purpose of bearer access tokens.
+pub const ACCESS_TOKEN_PURPOSE: &str = "auth-access-token";
+/// How long (seconds) a bearer access token is accepted.
+pub const ACCESS_TOKEN_TTL_SECS: i64 = 15 * 60;
+/// How long (seconds) a refresh token can be exchanged for a new pair.
+pub const REFRESH_TOKEN_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+pub const REFRESH_TOKEN_LENGTH: usize = 64;
+
+/// Marks a bearer token as a personal access token rather than a short-lived access token.
+pub const PERSONAL_TOKEN_PREFIX: &str = "acme_pat_";
+pub const PERSONAL_TOKEN_LENGTH: usize = 40;
+/// Longest lifetime (days) a personal access token can be created with.
+pub const PERSONAL_TOKEN_MAX_EXPIRY_DAYS: i64 = 365;
+
+/// How long (seconds) the browser has to come back from the provider.
+pub const OAUTH_PENDING_TTL_SECS: i64 = 10 * 60;
+/// How long (seconds) a call to a provider may take to connect, and to complete.
+pub const OAUTH_HTTP_CONNECT_TIMEOUT_SECS: u64 = 5;
+pub const OAUTH_HTTP_TIMEOUT_SECS: u64 = 10;
+/// How long (seconds) an issuer's discovery document and JWKS are reused before being fetched
+/// again; a token signed with a key that isn't in the cached JWKS refetches it right away.
+pub const OAUTH_METADATA_TTL_SECS: u64 = 60 * 60;
+
+/// Signing purpose of the "this wasn't me" link in new sign-in emails.
+pub const NOT_ME_PURPOSE: &str = "auth-not-me";
+/// How long (seconds) the "this wasn't me" link works; as long as the session it reports.
+pub const NOT_ME_TTL_SECS: i64 = 7 * 24 * 60 * 60;
@@ -1,11 +1,79 @@
 use serde::Deserialize;
 use validator::Validate;
 
+use crate::auth::types::TokenScope;
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct SigninDto {
   #[validate(length(min = 1, max = 255, message = "ValidateUsernameLength"))]
   pub username: String,
 
-  #[validate(length(min = 8, message = "ValidatePasswordMinLength"))]
+  #[validate(length(min = 1, message = "ValidatePasswordLength"))]
+  pub password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct TwoFactorCodeDto {
+  #[validate(length(equal = 6, message = "ValidateTotpCodeLength"))]
+  pub code: String,
+}
+
+/// Second sign-in step, either a TOTP `code` or one of the `recovery_code`s.
+#[derive(Debug, Deserialize, Validate)]
+pub struct TwoFactorVerifyDto {
+  #[validate(length(equal = 6, message = "ValidateTotpCodeLength"))]
+  pub code: Option<String>,
+
+  #[validate(length(min = 1, max = 255, message = "ValidateRecoveryCodeLength"))]
+  pub recovery_code: Option<String>,
+}
+
+/// Password sign-in for token clients; the second factor goes in the same request.
+#[derive(Debug, Deserialize, Validate)]
+pub struct TokenSigninDto {
+  #[validate(length(min = 1, max = 255, message = "ValidateUsernameLength"))]
+  pub username: String,
+
+  #[validate(length(min = 1, message = "ValidatePasswordLength"))]
   pub password: String,
+
+  #[validate(length(equal = 6, message = "ValidateTotpCodeLength"))]
+  pub code: Option<String>,
+
+  #[validate(length(min = 1, max = 255, message = "ValidateRecoveryCodeLength"))]
+  pub recovery_code: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RefreshTokenDto {
+  #[validate(length(min = 1, max = 255, message = "ValidateRefreshTokenLength"))]
+  pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreatePersonalTokenDto {
+  #[validate(length(min = 1, max = 255, message = "ValidatePersonalTokenNameLength"))]
+  pub name: String,
+
+  #[validate(length(min = 1, message = "ValidatePersonalTokenScopesLength"))]
+  pub scopes: Vec<TokenScope>,
+
+  #[validate(range(min = 1, max = 365, message = "ValidatePersonalTokenExpiryRange"))]
+  pub expires_in_days: i64,
+}
+
+/// What the provider sent back to the redirect uri.
+#[derive(Debug, Deserialize, Validate)]
+pub struct OAuthCallbackDto {
+  #[validate(length(min = 1, max = 2048, message = "ValidateOAuthCodeLength"))]
+  pub code: String,
+
+  #[validate(length(min = 1, max = 255, message = "ValidateOAuthStateLength"))]
+  pub state: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct NotMeDto {
+  #[validate(length(min = 1, max = 512, message = "ValidateNotMeTokenLength"))]
+  pub token: String,
 }
@@ -1,13 +1,33 @@
+use std::{
+  future::{ready, Ready},
+  rc::Rc,
+};
+
+use futures_util::future::LocalBoxFuture;
+
 use actix_session::SessionExt;
 use actix_web::{
-  body::MessageBody,
-  dev::{ServiceRequest, ServiceResponse},
-  http::StatusCode,
+  body::{EitherBody, MessageBody},
+  dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+  error::InternalError,
+  http::{header, StatusCode},
   middleware::Next,
-  Error,
+  web, Error, FromRequest, HttpMessage, HttpRequest,
 };
+use uuid::Uuid;
 
-use crate::{auth::constants::AuthMessage, common::functionalities::api_res::api_error};
+use crate::{
+  auth::{
+    constants::AuthMessage,
+    personal_tokens::PersonalTokenService,
+    roles::RoleService,
+    sessions::{SessionRegistry, SESSION_ID, SESSION_USER_ID},
+    tokens::TokenService,
+    types::{Grants, Permission, Role, TokenScope},
+  },
+  common::functionalities::api_res::api_error,
+  AppState,
+};
 
 pub async fn auth_middleware(
   req: ServiceRequest,
@@ -15,20 +35,355 @@ pub async fn auth_middleware(
   // session: Session,
 ) -> Result<ServiceResponse<impl MessageBody>, Error> {
   let session = req.get_session();
+  let data = req.app_data::<web::Data<AppState>>().cloned();
+
+  let auth_user = match (bearer_token(req.request()), &data) {
+    // A bearer header never falls back to the cookie
+    (Some(token), Some(data)) if PersonalTokenService::is_personal_token(token) => {
+      PersonalTokenService::authenticate(data, token)
+        .await
+        .ok()
+        .map(|(id, scopes)| AuthUser {
+          id,
+          sid: None,
+          scopes: Some(scopes),
+        })
+    },
+    (Some(token), _) => TokenService::verify_access_token(token).map(|id| AuthUser {
+      id,
+      sid: None,
+      scopes: None,
+    }),
+    (None, _) => {
+      let user_id = session
+        .get::<String>(SESSION_USER_ID)?
+        .and_then(|id| Uuid::parse_str(&id).ok());
+      let sid = session.get::<String>(SESSION_ID)?;
 
-  // Check if user is logged in
-  if session.get::<String>("user_id")?.is_some() {
+      // Check if user is logged in with a session that has not been revoked
+      match (user_id, sid, &data) {
+        (Some(id), Some(sid), Some(data)) if SessionRegistry::is_active(data, id, &sid).await => {
+          SessionRegistry::touch(data, id, &sid).await;
+          Some(AuthUser {
+            id,
+            sid: Some(sid),
+            scopes: None,
+          })
+        },
+        _ => None,
+      }
+    },
+  };
+
+  if let Some(auth_user) = auth_user {
     // User is authenticated, continue to next service
+    req.extensions_mut().insert(auth_user);
     next.call(req).await.map(|res| res.map_into_left_body())
   } else {
     // User not logged in, return unauthorized response
+    session.purge();
+    let message = match bearer_token(req.request()) {
+      Some(token) if PersonalTokenService::is_personal_token(token) => {
+        AuthMessage::AuthPersonalTokenInvalid
+      },
+      Some(_) => AuthMessage::AuthAccessTokenInvalid,
+      None => AuthMessage::AuthSigninFailed,
+    };
     let (req, _payload) = req.into_parts();
     Ok(
       ServiceResponse::new(
         req,
-        api_error::<(), AuthMessage>(StatusCode::UNAUTHORIZED, AuthMessage::AuthSigninFailed),
+        api_error::<(), AuthMessage>(StatusCode::UNAUTHORIZED, message),
       )
       .map_into_right_body(),
     )
   }
 }
+
+/// The token of an `Authorization: Bearer` header, if the request has one.
+fn bearer_token(req: &HttpRequest) -> Option<&str> {
+  req
+    .headers()
+    .get(header::AUTHORIZATION)?
+    .to_str()
+    .ok()?
+    .strip_prefix("Bearer ")
+    .map(str::trim)
+}
+
+/// The signed-in user, resolved by `auth_middleware` from the session cookie, a bearer access
+/// token or a personal access token.
+///
+/// Handlers take this instead of trusting a user id sent in the request body, so it is only
+/// available on routes wrapped by the middleware. Personal access tokens are refused unless the
+/// route is wrapped in `RequireScope`.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+  pub id: Uuid,
+  /// The session the request came in on; `None` for bearer tokens.
+  pub sid: Option<String>,
+  /// The scopes of a personal access token; `None` means full access.
+  pub scopes: Option<Vec<TokenScope>>,
+}
+
+impl AuthUser {
+  pub fn has_scope(&self, scope: TokenScope) -> bool {
+    self
+      .scopes
+      .as_ref()
+      .is_none_or(|scopes| scopes.contains(&scope))
+  }
+}
+
+/// Marks a request whose scope was checked by `RequireScope`.
+#[derive(Clone, Copy)]
+struct ScopeGranted;
+
+impl FromRequest for AuthUser {
+  type Error = Error;
+  type Future = Ready<Result<Self, Self::Error>>;
+
+  fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+    let extensions = req.extensions();
+    ready(match extensions.get::<AuthUser>() {
+      Some(auth_user)
+        if auth_user.scopes.is_some() && extensions.get::<ScopeGranted>().is_none() =>
+      {
+        Err(auth_error(
+          StatusCode::FORBIDDEN,
+          AuthMessage::AuthTokenScopeMissing,
+        ))
+      },
+      Some(auth_user) => Ok(auth_user.clone()),
+      None => Err(auth_error(
+        StatusCode::UNAUTHORIZED,
+        AuthMessage::AuthSigninFailed,
+      )),
+    })
+  }
+}
+
+/// Route middleware rejecting personal access tokens that lack `scope`.
+///
+/// Goes on routes behind `auth_middleware` through the route macro's `wrap` argument, e.g.
+/// `wrap = "RequireScope(TokenScope::UserRead)"`.
+pub struct RequireScope(pub TokenScope);
+
+impl<S, B> Transform<S, ServiceRequest> for RequireScope
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = Error;
+  type Transform = RequireScopeMiddleware<S>;
+  type InitError = ();
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(RequireScopeMiddleware {
+      service,
+      scope: self.0,
+    }))
+  }
+}
+
+pub struct RequireScopeMiddleware<S> {
+  service: S,
+  scope: TokenScope,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireScopeMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  forward_ready!(service);
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let granted = req
+      .extensions()
+      .get::<AuthUser>()
+      .is_some_and(|auth_user| auth_user.has_scope(self.scope));
+
+    if !granted {
+      let (req, _payload) = req.into_parts();
+      let res =
+        api_error::<(), AuthMessage>(StatusCode::FORBIDDEN, AuthMessage::AuthTokenScopeMissing);
+      return Box::pin(ready(Ok(
+        ServiceResponse::new(req, res).map_into_right_body(),
+      )));
+    }
+
+    req.extensions_mut().insert(ScopeGranted);
+    let fut = self.service.call(req);
+    Box::pin(async move { fut.await.map(|res| res.map_into_left_body()) })
+  }
+}
+
+/// A role or permission a route demands, checked against `user_roles` by `require_role` and
+/// `require_permission`.
+#[derive(Debug, Clone, Copy)]
+pub enum AccessRule {
+  Role(Role),
+  Permission(Permission),
+}
+
+/// Route or scope middleware letting only users with `role` through; goes inside
+/// `auth_middleware`, i.e. `.wrap(require_role(..))` before `.wrap(from_fn(auth_middleware))`.
+pub fn require_role(role: Role) -> AccessRule {
+  AccessRule::Role(role)
+}
+
+/// Like `require_role`, for users whose roles carry `permission`.
+pub fn require_permission(permission: Permission) -> AccessRule {
+  AccessRule::Permission(permission)
+}
+
+impl AccessRule {
+  fn allows(&self, grants: &Grants) -> bool {
+    match self {
+      AccessRule::Role(role) => grants.roles.contains(role),
+      AccessRule::Permission(permission) => grants.permissions.contains(permission),
+    }
+  }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AccessRule
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = Error;
+  type Transform = AccessRuleMiddleware<S>;
+  type InitError = ();
+  type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+  fn new_transform(&self, service: S) -> Self::Future {
+    ready(Ok(AccessRuleMiddleware {
+      service: Rc::new(service),
+      rule: *self,
+    }))
+  }
+}
+
+pub struct AccessRuleMiddleware<S> {
+  service: Rc<S>,
+  rule: AccessRule,
+}
+
+impl<S, B> Service<ServiceRequest> for AccessRuleMiddleware<S>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+  B: MessageBody + 'static,
+{
+  type Response = ServiceResponse<EitherBody<B>>;
+  type Error = Error;
+  type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+  forward_ready!(service);
+
+  fn call(&self, req: ServiceRequest) -> Self::Future {
+    let service = Rc::clone(&self.service);
+    let rule = self.rule;
+
+    Box::pin(async move {
+      let user_id = req
+        .extensions()
+        .get::<AuthUser>()
+        .map(|auth_user| auth_user.id);
+      let data = req.app_data::<web::Data<AppState>>().cloned();
+
+      let (status, message) = match (user_id, data) {
+        (Some(user_id), Some(data)) => {
+          // Nested rules on one request share a single lookup
+          let cached = req.extensions().get::<Grants>().cloned();
+          let grants = match cached {
+            Some(grants) => Ok(grants),
+            None => RoleService::grants_of(&data, user_id).await,
+          };
+
+          match grants {
+            Ok(grants) if rule.allows(&grants) => {
+              req.extensions_mut().insert(grants);
+              return service.call(req).await.map(|res| res.map_into_left_body());
+            },
+            Ok(_) => (StatusCode::FORBIDDEN, AuthMessage::AuthForbidden),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e),
+          }
+        },
+        _ => (StatusCode::UNAUTHORIZED, AuthMessage::AuthSigninFailed),
+      };
+
+      let (req, _payload) = req.into_parts();
+      Ok(
+        ServiceResponse::new(req, api_error::<(), AuthMessage>(status, message))
+          .map_into_right_body(),
+      )
+    })
+  }
+}
+
+/// Like `AuthUser`, but only for users who confirmed their email address.
+///
+/// Take this instead of `AuthUser` on routes that need a verified address.
+#[derive(Debug, Clone, Copy)]
+pub struct VerifiedUser {
+  pub id: Uuid,
+}
+
+impl FromRequest for VerifiedUser {
+  type Error = Error;
+  type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+  fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+    let auth_user = AuthUser::from_request(req, payload).into_inner();
+    let data = req.app_data::<web::Data<AppState>>().cloned();
+
+    Box::pin(async move {
+      let auth_user = auth_user?;
+      let data = data.ok_or_else(|| {
+        auth_error(
+          StatusCode::INTERNAL_SERVER_ERROR,
+          AuthMessage::AuthSigninFailed,
+        )
+      })?;
+
+      let verified = sqlx::query_scalar::<_, bool>(
+        r#"
+          SELECT email_verified_at IS NOT NULL
+          FROM users
+          WHERE id = $1::uuid
+        "#,
+      )
+      .bind(auth_user.id)
+      .fetch_optional(&data.db)
+      .await
+      .map_err(|e| {
+        println!("{:?}", e);
+        auth_error(
+          StatusCode::INTERNAL_SERVER_ERROR,
+          AuthMessage::AuthSigninFailed,
+        )
+      })?;
+
+      match verified {
+        Some(true) => Ok(VerifiedUser { id: auth_user.id }),
+        _ => Err(auth_error(
+          StatusCode::FORBIDDEN,
+          AuthMessage::AuthEmailNotVerified,
+        )),
+      }
+    })
+  }
+}
+
+fn auth_error(status: StatusCode, message: AuthMessage) -> Error {
+  let res = api_error::<(), &AuthMessage>(status, &message);
+  InternalError::from_response(message, res).into()
+}
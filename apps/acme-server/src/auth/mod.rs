@@ -1,42 +1,591 @@
 use actix_session::Session;
-use actix_web::{http::StatusCode, post, web, Responder};
+use actix_web::{
+  delete, get, http::StatusCode, middleware::from_fn, post, web, HttpRequest, HttpResponse,
+  Responder,
+};
+use chrono::Utc;
+use uuid::Uuid;
 
 use crate::{
-  auth::{constants::AuthMessage, dto::SigninDto, service::AuthService},
-  common::functionalities::api_res::{api_error, api_success},
+  auth::{
+    alerts::SignInAlertService,
+    constants::{AuthMessage, PENDING_TWO_FACTOR_TTL_SECS},
+    dto::{
+      CreatePersonalTokenDto, NotMeDto, OAuthCallbackDto, RefreshTokenDto, SigninDto,
+      TokenSigninDto, TwoFactorCodeDto, TwoFactorVerifyDto,
+    },
+    guard::{auth_middleware, AuthUser},
+    oauth::{OAuthProvider, OAuthService},
+    personal_tokens::PersonalTokenService,
+    service::AuthService,
+    sessions::{
+      SessionRegistry, SESSION_ID, SESSION_PENDING_TWO_FACTOR_AT,
+      SESSION_PENDING_TWO_FACTOR_USER_ID, SESSION_USER_ID,
+    },
+    throttle::LoginThrottle,
+    tokens::TokenService,
+    two_factor::TwoFactorService,
+    types::{
+      CreatedPersonalToken, OAuthAuthorization, PersonalToken, RecoveryCodes, SessionInfo,
+      TokenPair, TotpEnrollment,
+    },
+  },
+  common::{error::AppError, extractors::ValidatedJson, functionalities::api_res::api_success},
   user::types::User,
   AppState,
 };
 
-mod constants;
+pub mod alerts;
+pub mod constants;
 mod dto;
 pub mod guard;
+mod oauth;
+mod personal_tokens;
+pub mod roles;
 mod service;
+pub mod sessions;
+pub mod throttle;
+pub mod tokens;
+mod two_factor;
+pub mod types;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+  cfg.service(
+    web::scope("/auth")
+      .service(singin)
+      .service(signout)
+      .service(not_me)
+      .service(
+        web::scope("/2fa").service(two_factor_verify).service(
+          web::scope("")
+            .wrap(from_fn(auth_middleware))
+            .service(two_factor_enroll)
+            .service(two_factor_confirm)
+            .service(two_factor_disable),
+        ),
+      )
+      .service(
+        web::scope("/oauth")
+          .service(oauth_authorize)
+          .service(oauth_callback),
+      )
+      .service(
+        web::scope("/token")
+          .service(token_issue)
+          .service(token_refresh)
+          .service(token_revoke),
+      )
+      .service(
+        web::scope("/personal-tokens")
+          .wrap(from_fn(auth_middleware))
+          .service(personal_tokens_create)
+          .service(personal_tokens_list)
+          .service(personal_tokens_revoke),
+      )
+      .service(
+        web::scope("/sessions")
+          .wrap(from_fn(auth_middleware))
+          .service(sessions_list)
+          .service(sessions_revoke_others)
+          .service(sessions_revoke),
+      ),
+  );
+}
 
-pub fn config(cfg: &mut web::ServiceConfig) -> () {
-  cfg.service(web::scope("/auth").service(singin).service(signout));
+pub(crate) fn client_ip(req: &HttpRequest) -> String {
+  req
+    .connection_info()
+    .peer_addr()
+    .unwrap_or("unknown")
+    .to_string()
 }
 
 #[post("signin")]
 async fn singin(
-  credentials: web::Json<SigninDto>,
+  req: HttpRequest,
+  credentials: ValidatedJson<SigninDto>,
   session: Session,
   data: web::Data<AppState>,
-) -> impl Responder {
-  let user = match AuthService::signin(data, credentials).await {
+) -> Result<HttpResponse, AppError> {
+  let ip = client_ip(&req);
+
+  LoginThrottle::check(&data, &credentials.username, &ip).await?;
+
+  let user = match AuthService::signin(&data, &credentials).await {
     Ok(user) => user,
-    Err(e) => return api_error::<User, AuthMessage>(StatusCode::BAD_REQUEST, e),
+    Err(e @ AuthMessage::AuthInvalidCredentials) => {
+      LoginThrottle::record_failure(&data, &credentials.username, &ip).await?;
+      return Err(e.into());
+    },
+    Err(e) => return Err(e.into()),
   };
 
-  session
-    .insert("user_id", user.id.to_string())
-    .expect(&AuthMessage::AuthInsertUserIdSessionFailed.to_string());
+  // The password is right but the session stays pending until the second factor passes; the
+  // failed attempts are only forgotten once it does, or the code could be guessed forever
+  if user.totp_enabled_at.is_some() {
+    SessionRegistry::start_pending_two_factor(&session, user.id)?;
+
+    return Ok(api_success::<(), AuthMessage>(
+      StatusCode::OK,
+      (),
+      AuthMessage::AuthTwoFactorRequired,
+    ));
+  }
+
+  let sid = SessionRegistry::start(&data, &req, &session, user.id).await?;
+  LoginThrottle::clear(&data, &credentials.username).await;
 
-  api_success::<User, AuthMessage>(StatusCode::OK, user, AuthMessage::AuthSigninSuccess)
+  SignInAlertService::record(&data, &req, &user, Some(&sid)).await;
+
+  Ok(api_success::<User, AuthMessage>(
+    StatusCode::OK,
+    user,
+    AuthMessage::AuthSigninSuccess,
+  ))
 }
 
 #[post("/signout")]
-pub async fn signout(session: Session) -> impl Responder {
+pub async fn signout(session: Session, data: web::Data<AppState>) -> impl Responder {
+  if let (Ok(Some(user_id)), Ok(Some(sid))) = (
+    session.get::<Uuid>(SESSION_USER_ID),
+    session.get::<String>(SESSION_ID),
+  ) {
+    SessionRegistry::revoke(&data, user_id, &sid).await;
+  }
   session.purge();
   api_success::<(), AuthMessage>(StatusCode::OK, (), AuthMessage::AuthSignoutSuccess)
 }
+
+#[post("/verify")]
+async fn two_factor_verify(
+  req: HttpRequest,
+  credentials: ValidatedJson<TwoFactorVerifyDto>,
+  session: Session,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let pending_user_id = session
+    .get::<Uuid>(SESSION_PENDING_TWO_FACTOR_USER_ID)
+    .ok()
+    .flatten();
+  let pending_at = session
+    .get::<i64>(SESSION_PENDING_TWO_FACTOR_AT)
+    .ok()
+    .flatten()
+    .unwrap_or_default();
+
+  let user_id = match pending_user_id {
+    Some(user_id) if Utc::now().timestamp() - pending_at <= PENDING_TWO_FACTOR_TTL_SECS => user_id,
+    _ => {
+      session.purge();
+      return Err(AuthMessage::AuthTwoFactorNoPendingSignin.into());
+    },
+  };
+
+  let user = TwoFactorService::pending_user(&data, user_id).await?;
+
+  let ip = client_ip(&req);
+
+  LoginThrottle::check(&data, &user.username, &ip).await?;
+
+  match TwoFactorService::verify(&data, &user, &credentials).await {
+    Ok(_) => {},
+    Err(e @ AuthMessage::AuthTwoFactorCodeInvalid) => {
+      if let Err(lockout) = LoginThrottle::record_failure(&data, &user.username, &ip).await {
+        session.purge();
+        return Err(lockout.into());
+      }
+      return Err(e.into());
+    },
+    Err(e) => return Err(e.into()),
+  }
+
+  let sid = SessionRegistry::start(&data, &req, &session, user.id).await?;
+  LoginThrottle::clear(&data, &user.username).await;
+
+  SignInAlertService::record(&data, &req, &user, Some(&sid)).await;
+
+  Ok(api_success::<User, AuthMessage>(
+    StatusCode::OK,
+    user,
+    AuthMessage::AuthSigninSuccess,
+  ))
+}
+
+#[post("/enroll")]
+async fn two_factor_enroll(
+  auth: AuthUser,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let enrollment = TwoFactorService::enroll(&data, auth.id).await?;
+
+  Ok(api_success::<TotpEnrollment, AuthMessage>(
+    StatusCode::OK,
+    enrollment,
+    AuthMessage::AuthTwoFactorEnrollSuccess,
+  ))
+}
+
+#[post("/confirm")]
+async fn two_factor_confirm(
+  auth: AuthUser,
+  credentials: ValidatedJson<TwoFactorCodeDto>,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let recovery_codes = TwoFactorService::confirm(&data, auth.id, &credentials.code).await?;
+
+  Ok(api_success::<RecoveryCodes, AuthMessage>(
+    StatusCode::OK,
+    RecoveryCodes { recovery_codes },
+    AuthMessage::AuthTwoFactorEnableSuccess,
+  ))
+}
+
+#[post("/disable")]
+async fn two_factor_disable(
+  auth: AuthUser,
+  credentials: ValidatedJson<TwoFactorCodeDto>,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  TwoFactorService::disable(&data, auth.id, &credentials.code).await?;
+
+  Ok(api_success::<(), AuthMessage>(
+    StatusCode::OK,
+    (),
+    AuthMessage::AuthTwoFactorDisableSuccess,
+  ))
+}
+
+#[get("")]
+async fn sessions_list(
+  auth: AuthUser,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let sessions = SessionRegistry::list(&data, auth.id, auth.sid.as_deref()).await?;
+
+  Ok(api_success::<Vec<SessionInfo>, AuthMessage>(
+    StatusCode::OK,
+    sessions,
+    AuthMessage::AuthListSessionsSuccess,
+  ))
+}
+
+/// Signs out everywhere except the session making the request.
+#[post("/revoke-others")]
+async fn sessions_revoke_others(
+  auth: AuthUser,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  SessionRegistry::revoke_others(&data, auth.id, auth.sid.as_deref()).await?;
+
+  Ok(api_success::<(), AuthMessage>(
+    StatusCode::OK,
+    (),
+    AuthMessage::AuthRevokeOtherSessionsSuccess,
+  ))
+}
+
+#[delete("/{id}")]
+async fn sessions_revoke(
+  auth: AuthUser,
+  path: web::Path<String>,
+  session: Session,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let sid = path.into_inner();
+
+  if !SessionRegistry::revoke(&data, auth.id, &sid).await {
+    return Err(AuthMessage::AuthSessionNotFound.into());
+  }
+
+  // Revoking the current session is a sign out
+  if auth.sid.as_deref() == Some(sid.as_str()) {
+    session.purge();
+  }
+
+  Ok(api_success::<(), AuthMessage>(
+    StatusCode::OK,
+    (),
+    AuthMessage::AuthRevokeSessionSuccess,
+  ))
+}
+
+/// Password sign-in for bearer clients; TOTP users send their code in the same request.
+#[post("")]
+async fn token_issue(
+  req: HttpRequest,
+  credentials: ValidatedJson<TokenSigninDto>,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let ip = client_ip(&req);
+  let credentials = credentials.into_inner();
+
+  LoginThrottle::check(&data, &credentials.username, &ip).await?;
+
+  let signin = SigninDto {
+    username: credentials.username,
+    password: credentials.password,
+  };
+
+  let user = match AuthService::signin(&data, &signin).await {
+    Ok(user) => user,
+    Err(e @ AuthMessage::AuthInvalidCredentials) => {
+      LoginThrottle::record_failure(&data, &signin.username, &ip).await?;
+      return Err(e.into());
+    },
+    Err(e) => return Err(e.into()),
+  };
+
+  if user.totp_enabled_at.is_some() {
+    let second_factor = TwoFactorVerifyDto {
+      code: credentials.code,
+      recovery_code: credentials.recovery_code,
+    };
+
+    if second_factor.code.is_none() && second_factor.recovery_code.is_none() {
+      return Err(AuthMessage::AuthTwoFactorRequired.into());
+    }
+
+    match TwoFactorService::verify(&data, &user, &second_factor).await {
+      Ok(_) => {},
+      Err(e @ AuthMessage::AuthTwoFactorCodeInvalid) => {
+        LoginThrottle::record_failure(&data, &signin.username, &ip).await?;
+        return Err(e.into());
+      },
+      Err(e) => return Err(e.into()),
+    }
+  }
+
+  LoginThrottle::clear(&data, &signin.username).await;
+
+  let tokens = TokenService::issue(&data, user.id, None).await?;
+
+  SignInAlertService::record(&data, &req, &user, None).await;
+
+  Ok(api_success::<TokenPair, AuthMessage>(
+    StatusCode::OK,
+    tokens,
+    AuthMessage::AuthTokenIssueSuccess,
+  ))
+}
+
+#[post("/refresh")]
+async fn token_refresh(
+  credentials: ValidatedJson<RefreshTokenDto>,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let tokens = TokenService::refresh(&data, &credentials.refresh_token).await?;
+
+  Ok(api_success::<TokenPair, AuthMessage>(
+    StatusCode::OK,
+    tokens,
+    AuthMessage::AuthTokenRefreshSuccess,
+  ))
+}
+
+/// Sign out for bearer clients.
+#[post("/revoke")]
+async fn token_revoke(
+  credentials: ValidatedJson<RefreshTokenDto>,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  TokenService::revoke(&data, &credentials.refresh_token).await?;
+
+  Ok(api_success::<(), AuthMessage>(
+    StatusCode::OK,
+    (),
+    AuthMessage::AuthTokenRevokeSuccess,
+  ))
+}
+
+/// The plain token is only in this response; the server keeps a hash.
+#[post("")]
+async fn personal_tokens_create(
+  auth: AuthUser,
+  credentials: ValidatedJson<CreatePersonalTokenDto>,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let token = PersonalTokenService::create(&data, auth.id, credentials.into_inner()).await?;
+
+  Ok(api_success::<CreatedPersonalToken, AuthMessage>(
+    StatusCode::CREATED,
+    token,
+    AuthMessage::AuthPersonalTokenCreateSuccess,
+  ))
+}
+
+#[get("")]
+async fn personal_tokens_list(
+  auth: AuthUser,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let tokens = PersonalTokenService::list(&data, auth.id).await?;
+
+  Ok(api_success::<Vec<PersonalToken>, AuthMessage>(
+    StatusCode::OK,
+    tokens,
+    AuthMessage::AuthPersonalTokenListSuccess,
+  ))
+}
+
+#[delete("/{id}")]
+async fn personal_tokens_revoke(
+  auth: AuthUser,
+  path: web::Path<Uuid>,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  PersonalTokenService::revoke(&data, auth.id, path.into_inner()).await?;
+
+  Ok(api_success::<(), AuthMessage>(
+    StatusCode::OK,
+    (),
+    AuthMessage::AuthPersonalTokenRevokeSuccess,
+  ))
+}
+
+/// First leg of a social sign-in; the client sends the browser to `authorization_url`.
+#[get("/{provider}/authorize")]
+async fn oauth_authorize(
+  data: web::Data<AppState>,
+  path: web::Path<OAuthProvider>,
+  session: Session,
+) -> Result<HttpResponse, AppError> {
+  let authorization_url = OAuthService::authorize(&data, &session, path.into_inner()).await?;
+
+  Ok(api_success::<OAuthAuthorization, AuthMessage>(
+    StatusCode::OK,
+    OAuthAuthorization { authorization_url },
+    AuthMessage::AuthOAuthAuthorizeSuccess,
+  ))
+}
+
+/// Second leg: the client posts the `code` and `state` the provider redirected back with.
+#[post("/{provider}/callback")]
+async fn oauth_callback(
+  req: HttpRequest,
+  path: web::Path<OAuthProvider>,
+  credentials: ValidatedJson<OAuthCallbackDto>,
+  session: Session,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let user = OAuthService::callback(
+    &data,
+    &session,
+    path.into_inner(),
+    &credentials.code,
+    &credentials.state,
+  )
+  .await?;
+
+  // The provider counts as the first factor; 2FA still applies
+  if user.totp_enabled_at.is_some() {
+    SessionRegistry::start_pending_two_factor(&session, user.id)?;
+
+    return Ok(api_success::<(), AuthMessage>(
+      StatusCode::OK,
+      (),
+      AuthMessage::AuthTwoFactorRequired,
+    ));
+  }
+
+  let sid = SessionRegistry::start(&data, &req, &session, user.id).await?;
+
+  SignInAlertService::record(&data, &req, &user, Some(&sid)).await;
+
+  Ok(api_success::<User, AuthMessage>(
+    StatusCode::OK,
+    user,
+    AuthMessage::AuthSigninSuccess,
+  ))
+}
+
+/// Target of the "this wasn't me" link in new sign-in emails.
+#[post("/not-me")]
+async fn not_me(
+  credentials: ValidatedJson<NotMeDto>,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  SignInAlertService::not_me(&data, &credentials.token).await?;
+
+  Ok(api_success::<(), AuthMessage>(
+    StatusCode::OK,
+    (),
+    AuthMessage::AuthNotMeSuccess,
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_session::SessionMiddleware;
+  use actix_web::{cookie::Key, test, App};
+  use serde_json::json;
+  use totp_rs::Secret;
+
+  use super::*;
+  use crate::{
+    auth::constants::MAX_FAILED_ATTEMPTS_PER_USER, common::functionalities::password,
+    common::testing, redis,
+  };
+
+  #[actix_web::test]
+  #[ignore = "needs a migrated Postgres (TEST_DATABASE_URL) and Redis (TEST_REDIS_URL)"]
+  async fn password_signins_do_not_reset_the_second_factor_guesses() {
+    let state = testing::state().await;
+    let store = redis::connect_redis(&state.data.config.redis).await;
+    let app = test::init_service(
+      App::new()
+        .app_data(state.data.clone())
+        .wrap(SessionMiddleware::new(store, Key::generate()))
+        .service(web::scope("/v1").configure(config)),
+    )
+    .await;
+
+    let peer = testing::peer_addr();
+    let username = format!("duck-{}", Uuid::new_v4().simple());
+    sqlx::query(
+      r#"
+        INSERT INTO users (username, email, first_name, last_name, password_hash, totp_secret, totp_enabled_at)
+        VALUES ($1, $2, 'Duck', 'Tester', $3, $4, NOW())
+      "#,
+    )
+    .bind(&username)
+    .bind(format!("{username}@example.com"))
+    .bind(password::hash("correct horse battery").await.unwrap())
+    .bind(Secret::generate_secret().to_encoded().to_string())
+    .execute(&state.data.db)
+    .await
+    .unwrap();
+
+    // Each round gets the password right and then the code wrong
+    for round in 1..=MAX_FAILED_ATTEMPTS_PER_USER {
+      let req = test::TestRequest::post()
+        .uri("/v1/auth/signin")
+        .peer_addr(peer)
+        .set_json(json!({ "username": username, "password": "correct horse battery" }))
+        .to_request();
+      let res = test::call_service(&app, req).await;
+      assert_eq!(res.status(), StatusCode::OK);
+      let cookie = res.response().cookies().next().unwrap().into_owned();
+
+      let req = test::TestRequest::post()
+        .uri("/v1/auth/2fa/verify")
+        .peer_addr(peer)
+        .cookie(cookie)
+        .set_json(json!({ "code": "000000" }))
+        .to_request();
+      let res = test::call_service(&app, req).await;
+      if round < MAX_FAILED_ATTEMPTS_PER_USER {
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+      } else {
+        assert_eq!(res.status(), StatusCode::LOCKED);
+      }
+    }
+
+    let req = test::TestRequest::post()
+      .uri("/v1/auth/signin")
+      .peer_addr(peer)
+      .set_json(json!({ "username": username, "password": "correct horse battery" }))
+      .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::LOCKED);
+  }
+}
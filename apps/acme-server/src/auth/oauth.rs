@@ -0,0 +1,662 @@
+use std::{
+  collections::HashMap,
+  sync::{LazyLock, Mutex},
+  time::{Duration, Instant},
+};
+
+use actix_session::Session;
+use actix_web::web;
+use base64::Engine;
+use chrono::Utc;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use reqwest::{header, Client};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+  auth::{
+    constants::{
+      AuthMessage, OAUTH_HTTP_CONNECT_TIMEOUT_SECS, OAUTH_HTTP_TIMEOUT_SECS,
+      OAUTH_METADATA_TTL_SECS, OAUTH_PENDING_TTL_SECS,
+    },
+    roles::RoleService,
+    sessions::{SessionRegistry, SESSION_PENDING_OAUTH},
+    types::Role,
+  },
+  common::{functionalities::password, generators},
+  config::{OAuthClientConfig, OAuthConfig},
+  user::types::User,
+  AppState,
+};
+
+static HTTP: LazyLock<Client> = LazyLock::new(|| {
+  Client::builder()
+    .connect_timeout(Duration::from_secs(OAUTH_HTTP_CONNECT_TIMEOUT_SECS))
+    .timeout(Duration::from_secs(OAUTH_HTTP_TIMEOUT_SECS))
+    .build()
+    .expect("Could not build the OAuth http client")
+});
+
+/// Discovery documents and JWKS by url, with when they were fetched.
+static METADATA: LazyLock<Mutex<HashMap<String, (Instant, Value)>>> =
+  LazyLock::new(Default::default);
+
+const GITHUB_AUTHORIZE_URL: &str = "https://github.com/login/oauth/authorize";
+const GITHUB_TOKEN_URL: &str = "https://github.com/login/oauth/access_token";
+const GITHUB_API_URL: &str = "https://api.github.com";
+const GOOGLE_ISSUER: &str = "https://accounts.google.com";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OAuthProvider {
+  Github,
+  Google,
+  /// Any OpenID Connect issuer, set through `oauth.oidc.issuer` (`OAUTH_OIDC_ISSUER`).
+  Oidc,
+}
+
+impl OAuthProvider {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      OAuthProvider::Github => "github",
+      OAuthProvider::Google => "google",
+      OAuthProvider::Oidc => "oidc",
+    }
+  }
+
+  fn client<'c>(&self, oauth: &'c OAuthConfig) -> &'c OAuthClientConfig {
+    match self {
+      OAuthProvider::Github => &oauth.github,
+      OAuthProvider::Google => &oauth.google,
+      OAuthProvider::Oidc => &oauth.oidc,
+    }
+  }
+
+  /// The provider's client from `oauth`, e.g. `oauth.google` (`OAUTH_GOOGLE_CLIENT_ID`, ...).
+  fn config(&self, oauth: &OAuthConfig) -> Result<ProviderConfig, AuthMessage> {
+    let client = self.client(oauth);
+    if !client.is_configured() {
+      return Err(AuthMessage::AuthOAuthProviderNotConfigured);
+    }
+
+    let issuer = match self {
+      OAuthProvider::Github => None,
+      OAuthProvider::Google => Some(
+        client
+          .issuer
+          .clone()
+          .unwrap_or_else(|| GOOGLE_ISSUER.to_string()),
+      ),
+      OAuthProvider::Oidc => Some(
+        client
+          .issuer
+          .clone()
+          .ok_or(AuthMessage::AuthOAuthProviderNotConfigured)?,
+      ),
+    };
+
+    Ok(ProviderConfig {
+      client_id: client.client_id.clone(),
+      client_secret: client.client_secret.clone(),
+      issuer: issuer.map(|issuer| issuer.trim_end_matches('/').to_string()),
+      redirect_uri: format!(
+        "{}/{}",
+        oauth.redirect_url.trim_end_matches('/'),
+        self.as_str()
+      ),
+    })
+  }
+}
+
+struct ProviderConfig {
+  client_id: String,
+  client_secret: String,
+  /// `None` for plain OAuth2 providers without ID tokens (GitHub).
+  issuer: Option<String>,
+  redirect_uri: String,
+}
+
+/// What the browser carries between the authorize redirect and the callback.
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingOAuth {
+  provider: OAuthProvider,
+  state: String,
+  nonce: String,
+  code_verifier: String,
+  started_at: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Discovery {
+  issuer: String,
+  authorization_endpoint: String,
+  token_endpoint: String,
+  jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+  access_token: String,
+  id_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+  sub: String,
+  nonce: Option<String>,
+  email: Option<String>,
+  /// Some issuers send `"true"` instead of `true`.
+  email_verified: Option<Value>,
+  given_name: Option<String>,
+  family_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubUser {
+  id: i64,
+  login: String,
+  name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubEmail {
+  email: String,
+  primary: bool,
+  verified: bool,
+}
+
+/// The account at the provider, reduced to what linking needs.
+struct ExternalIdentity {
+  subject: String,
+  email: String,
+  email_verified: bool,
+  username_hint: String,
+  first_name: String,
+  last_name: String,
+}
+
+/// "Sign in with GitHub/Google/any OIDC issuer" through the authorization-code flow with PKCE.
+pub struct OAuthService;
+
+impl OAuthService {
+  fn random_token() -> String {
+    generators::code::generate_code(64)
+  }
+
+  fn code_challenge(code_verifier: &str) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier))
+  }
+
+  async fn get_json<T: for<'de> Deserialize<'de>>(
+    url: &str,
+    bearer: Option<&str>,
+  ) -> Result<T, AuthMessage> {
+    let mut request = HTTP
+      .get(url)
+      .header(header::ACCEPT, "application/json")
+      .header(header::USER_AGENT, "acme-server");
+    if let Some(bearer) = bearer {
+      request = request.bearer_auth(bearer);
+    }
+
+    request
+      .send()
+      .await
+      .and_then(|res| res.error_for_status())
+      .map_err(|e| {
+        println!("{:?}", e);
+        AuthMessage::AuthOAuthExchangeFailed
+      })?
+      .json::<T>()
+      .await
+      .map_err(|e| {
+        println!("{:?}", e);
+        AuthMessage::AuthOAuthExchangeFailed
+      })
+  }
+
+  /// `get_json` for issuer metadata, served from `METADATA` while it is younger than
+  /// `OAUTH_METADATA_TTL_SECS` unless `refresh` is set.
+  async fn get_metadata<T: for<'de> Deserialize<'de>>(
+    url: &str,
+    refresh: bool,
+  ) -> Result<T, AuthMessage> {
+    let ttl = Duration::from_secs(OAUTH_METADATA_TTL_SECS);
+    let cached = match METADATA.lock() {
+      Ok(metadata) => metadata
+        .get(url)
+        .filter(|(fetched_at, _)| !refresh && fetched_at.elapsed() < ttl)
+        .map(|(_, value)| value.clone()),
+      Err(_) => None,
+    };
+
+    let value = match cached {
+      Some(value) => value,
+      None => {
+        let value = Self::get_json::<Value>(url, None).await?;
+        if let Ok(mut metadata) = METADATA.lock() {
+          metadata.insert(url.to_string(), (Instant::now(), value.clone()));
+        }
+        value
+      },
+    };
+
+    serde_json::from_value(value).map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthOAuthExchangeFailed
+    })
+  }
+
+  async fn discover(issuer: &str) -> Result<Discovery, AuthMessage> {
+    let discovery =
+      Self::get_metadata::<Discovery>(&format!("{issuer}/.well-known/openid-configuration"), false)
+        .await?;
+
+    if discovery.issuer.trim_end_matches('/') != issuer {
+      return Err(AuthMessage::AuthOAuthIdTokenInvalid);
+    }
+
+    Ok(discovery)
+  }
+
+  /// Starts a sign-in: remembers state, nonce and PKCE verifier in the session and returns
+  /// where to send the browser.
+  pub async fn authorize(
+    data: &web::Data<AppState>,
+    session: &Session,
+    provider: OAuthProvider,
+  ) -> Result<String, AuthMessage> {
+    let config = provider.config(&data.config.oauth)?;
+
+    let pending = PendingOAuth {
+      provider,
+      state: Self::random_token(),
+      nonce: Self::random_token(),
+      code_verifier: Self::random_token(),
+      started_at: Utc::now().timestamp(),
+    };
+
+    let (authorization_endpoint, scope) = match &config.issuer {
+      Some(issuer) => (
+        Self::discover(issuer).await?.authorization_endpoint,
+        "openid email profile",
+      ),
+      None => (GITHUB_AUTHORIZE_URL.to_string(), "read:user user:email"),
+    };
+
+    let mut url = reqwest::Url::parse(&authorization_endpoint).map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthOAuthProviderNotConfigured
+    })?;
+    url
+      .query_pairs_mut()
+      .append_pair("response_type", "code")
+      .append_pair("client_id", &config.client_id)
+      .append_pair("redirect_uri", &config.redirect_uri)
+      .append_pair("scope", scope)
+      .append_pair("state", &pending.state)
+      .append_pair("nonce", &pending.nonce)
+      .append_pair(
+        "code_challenge",
+        &Self::code_challenge(&pending.code_verifier),
+      )
+      .append_pair("code_challenge_method", "S256");
+
+    SessionRegistry::insert(session, SESSION_PENDING_OAUTH, &pending)?;
+
+    Ok(url.to_string())
+  }
+
+  /// Finishes a sign-in started by `authorize` and returns the local user for the identity.
+  pub async fn callback(
+    data: &web::Data<AppState>,
+    session: &Session,
+    provider: OAuthProvider,
+    code: &str,
+    state: &str,
+  ) -> Result<User, AuthMessage> {
+    let pending = session
+      .remove_as::<PendingOAuth>(SESSION_PENDING_OAUTH)
+      .and_then(Result::ok)
+      .ok_or(AuthMessage::AuthOAuthStateMismatch)?;
+
+    if pending.provider != provider
+      || pending.state != state
+      || Utc::now().timestamp() - pending.started_at > OAUTH_PENDING_TTL_SECS
+    {
+      return Err(AuthMessage::AuthOAuthStateMismatch);
+    }
+
+    let config = provider.config(&data.config.oauth)?;
+
+    let identity = match &config.issuer {
+      Some(issuer) => {
+        let discovery = Self::discover(issuer).await?;
+        let tokens = Self::exchange(&config, &discovery.token_endpoint, code, &pending).await?;
+        let id_token = tokens
+          .id_token
+          .ok_or(AuthMessage::AuthOAuthIdTokenInvalid)?;
+        Self::verify_id_token(
+          &config,
+          issuer,
+          &discovery.jwks_uri,
+          &id_token,
+          &pending.nonce,
+        )
+        .await?
+      },
+      None => {
+        let tokens = Self::exchange(&config, GITHUB_TOKEN_URL, code, &pending).await?;
+        Self::github_identity(&tokens.access_token).await?
+      },
+    };
+
+    Self::link_or_create(data, provider, identity).await
+  }
+
+  async fn exchange(
+    config: &ProviderConfig,
+    token_endpoint: &str,
+    code: &str,
+    pending: &PendingOAuth,
+  ) -> Result<TokenResponse, AuthMessage> {
+    HTTP
+      .post(token_endpoint)
+      .header(header::ACCEPT, "application/json")
+      .form(&[
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", &config.redirect_uri),
+        ("client_id", &config.client_id),
+        ("client_secret", &config.client_secret),
+        ("code_verifier", &pending.code_verifier),
+      ])
+      .send()
+      .await
+      .and_then(|res| res.error_for_status())
+      .map_err(|e| {
+        println!("{:?}", e);
+        AuthMessage::AuthOAuthExchangeFailed
+      })?
+      .json::<TokenResponse>()
+      .await
+      .map_err(|e| {
+        println!("{:?}", e);
+        AuthMessage::AuthOAuthExchangeFailed
+      })
+  }
+
+  /// Checks signature (against the issuer's JWKS), issuer, audience, expiry and nonce.
+  async fn verify_id_token(
+    config: &ProviderConfig,
+    issuer: &str,
+    jwks_uri: &str,
+    id_token: &str,
+    nonce: &str,
+  ) -> Result<ExternalIdentity, AuthMessage> {
+    let header = decode_header(id_token).map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthOAuthIdTokenInvalid
+    })?;
+
+    // Only asymmetric keys come from a JWKS; refuse anything signed with a shared secret
+    if matches!(
+      header.alg,
+      Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+      return Err(AuthMessage::AuthOAuthIdTokenInvalid);
+    }
+
+    // A kid the cached JWKS doesn't know may be a key the issuer just rotated in
+    let mut jwks = Self::get_metadata::<JwkSet>(jwks_uri, false).await?;
+    if let Some(kid) = &header.kid
+      && jwks.find(kid).is_none()
+    {
+      jwks = Self::get_metadata::<JwkSet>(jwks_uri, true).await?;
+    }
+    let jwk = match &header.kid {
+      Some(kid) => jwks.find(kid),
+      None => jwks.keys.first(),
+    }
+    .ok_or(AuthMessage::AuthOAuthIdTokenInvalid)?;
+    let key = DecodingKey::from_jwk(jwk).map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthOAuthIdTokenInvalid
+    })?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[issuer, &format!("{issuer}/")]);
+    validation.set_audience(&[&config.client_id]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+      .map_err(|e| {
+        println!("{:?}", e);
+        AuthMessage::AuthOAuthIdTokenInvalid
+      })?
+      .claims;
+
+    if claims.nonce.as_deref() != Some(nonce) {
+      return Err(AuthMessage::AuthOAuthIdTokenInvalid);
+    }
+
+    let email = claims.email.ok_or(AuthMessage::AuthOAuthEmailNotVerified)?;
+    let email_verified = matches!(claims.email_verified, Some(Value::Bool(true)))
+      || matches!(&claims.email_verified, Some(Value::String(v)) if v == "true");
+
+    Ok(ExternalIdentity {
+      subject: claims.sub,
+      username_hint: email.split('@').next().unwrap_or_default().to_string(),
+      email,
+      email_verified,
+      first_name: claims.given_name.unwrap_or_default(),
+      last_name: claims.family_name.unwrap_or_default(),
+    })
+  }
+
+  /// GitHub has no ID token, so the identity comes from its API.
+  async fn github_identity(access_token: &str) -> Result<ExternalIdentity, AuthMessage> {
+    let user =
+      Self::get_json::<GithubUser>(&format!("{GITHUB_API_URL}/user"), Some(access_token)).await?;
+    let emails = Self::get_json::<Vec<GithubEmail>>(
+      &format!("{GITHUB_API_URL}/user/emails"),
+      Some(access_token),
+    )
+    .await?;
+
+    let email = emails
+      .into_iter()
+      .find(|email| email.primary && email.verified)
+      .ok_or(AuthMessage::AuthOAuthEmailNotVerified)?;
+
+    let name = user.name.unwrap_or_default();
+    let (first_name, last_name) = name.split_once(' ').unwrap_or((&name, ""));
+
+    Ok(ExternalIdentity {
+      subject: user.id.to_string(),
+      email: email.email,
+      email_verified: email.verified,
+      first_name: first_name.to_string(),
+      last_name: last_name.to_string(),
+      username_hint: user.login,
+    })
+  }
+
+  /// Finds the user already linked to the identity, links one with the same verified email,
+  /// or creates a new member.
+  async fn link_or_create(
+    data: &web::Data<AppState>,
+    provider: OAuthProvider,
+    identity: ExternalIdentity,
+  ) -> Result<User, AuthMessage> {
+    let linked = sqlx::query_as::<_, User>(
+      r#"
+        SELECT u.*
+        FROM oauth_accounts oa
+        JOIN users u ON u.id = oa.user_id
+        WHERE oa.provider = $1 AND oa.provider_user_id = $2 AND u.deleted_at IS NULL
+      "#,
+    )
+    .bind(provider.as_str())
+    .bind(&identity.subject)
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthOAuthLinkFailed
+    })?;
+
+    if let Some(user) = linked {
+      return Ok(user);
+    }
+
+    // Only an address both sides verified may tie the identity to a local account
+    if !identity.email_verified {
+      return Err(AuthMessage::AuthOAuthEmailNotVerified);
+    }
+
+    let mut tx = data.db.begin().await.map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthOAuthLinkFailed
+    })?;
+
+    let existing = sqlx::query_as::<_, User>(
+      r#"
+        SELECT *
+        FROM users
+        WHERE LOWER(email) = LOWER($1) AND deleted_at IS NULL
+      "#,
+    )
+    .bind(&identity.email)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthOAuthLinkFailed
+    })?;
+
+    let user = match existing {
+      // Whoever registered an unverified address here may not own it
+      Some(user) if user.email_verified_at.is_none() => {
+        return Err(AuthMessage::AuthOAuthAccountUnverified);
+      },
+      Some(user) => user,
+      None => {
+        let username = format!(
+          "{}-{}",
+          identity
+            .username_hint
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+            .take(40)
+            .collect::<String>(),
+          generators::code::generate_code(6).to_lowercase()
+        );
+        // Nobody knows this password; the account signs in through the provider or a reset
+        let password_hash = password::hash(&generators::code::generate_code(32))
+          .await
+          .map_err(|e| {
+            println!("{:?}", e);
+            AuthMessage::AuthOAuthLinkFailed
+          })?;
+
+        let user = sqlx::query_as::<_, User>(
+          r#"
+            INSERT INTO users
+              (username, email, first_name, last_name, password_hash, email_verified_at)
+            VALUES ($1, $2, $3, $4, $5, NOW())
+            RETURNING *
+          "#,
+        )
+        .bind(&username)
+        .bind(&identity.email)
+        .bind(&identity.first_name)
+        .bind(&identity.last_name)
+        .bind(&password_hash)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| {
+          println!("{:?}", e);
+          AuthMessage::AuthOAuthLinkFailed
+        })?;
+
+        RoleService::grant(&mut *tx, user.id, Role::Member, None).await?;
+
+        user
+      },
+    };
+
+    sqlx::query(
+      r#"
+        INSERT INTO oauth_accounts (user_id, provider, provider_user_id, email)
+        VALUES ($1::uuid, $2, $3, $4)
+      "#,
+    )
+    .bind(user.id)
+    .bind(provider.as_str())
+    .bind(&identity.subject)
+    .bind(&identity.email)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthOAuthLinkFailed
+    })?;
+
+    tx.commit().await.map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthOAuthLinkFailed
+    })?;
+
+    Ok(user)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  /// Nothing listens here, so anything that reaches the network fails.
+  const UNREACHABLE: &str = "http://127.0.0.1:9";
+
+  fn remember(url: &str, age: Duration, value: Value) {
+    METADATA
+      .lock()
+      .unwrap()
+      .insert(url.to_string(), (Instant::now() - age, value));
+  }
+
+  #[actix_web::test]
+  async fn fresh_metadata_is_served_from_the_cache() {
+    let url = format!("{UNREACHABLE}/fresh/jwks");
+    remember(&url, Duration::ZERO, json!({ "keys": [] }));
+
+    let jwks = OAuthService::get_metadata::<JwkSet>(&url, false)
+      .await
+      .unwrap();
+
+    assert!(jwks.keys.is_empty());
+  }
+
+  #[actix_web::test]
+  async fn stale_or_refreshed_metadata_is_fetched_again() {
+    let stale = format!("{UNREACHABLE}/stale/jwks");
+    remember(
+      &stale,
+      Duration::from_secs(OAUTH_METADATA_TTL_SECS + 1),
+      json!({ "keys": [] }),
+    );
+    let refreshed = format!("{UNREACHABLE}/refreshed/jwks");
+    remember(&refreshed, Duration::ZERO, json!({ "keys": [] }));
+
+    assert!(OAuthService::get_metadata::<JwkSet>(&stale, false)
+      .await
+      .is_err());
+    assert!(OAuthService::get_metadata::<JwkSet>(&refreshed, true)
+      .await
+      .is_err());
+  }
+}
@@ -0,0 +1,155 @@
+use actix_web::web;
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+  auth::{
+    constants::{
+      AuthMessage, PERSONAL_TOKEN_LENGTH, PERSONAL_TOKEN_MAX_EXPIRY_DAYS, PERSONAL_TOKEN_PREFIX,
+    },
+    dto::CreatePersonalTokenDto,
+    types::{CreatedPersonalToken, PersonalToken, TokenScope},
+  },
+  common::generators,
+  AppState,
+};
+
+/// Long-lived personal access tokens for scripts, limited to the scopes picked at creation.
+pub struct PersonalTokenService;
+
+impl PersonalTokenService {
+  fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+  }
+
+  /// Whether a bearer token should be looked up as a personal access token.
+  pub fn is_personal_token(token: &str) -> bool {
+    token.starts_with(PERSONAL_TOKEN_PREFIX)
+  }
+
+  pub async fn create(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+    credentials: CreatePersonalTokenDto,
+  ) -> Result<CreatedPersonalToken, AuthMessage> {
+    if credentials.scopes.is_empty() {
+      return Err(AuthMessage::AuthPersonalTokenScopesEmpty);
+    }
+    if !(1..=PERSONAL_TOKEN_MAX_EXPIRY_DAYS).contains(&credentials.expires_in_days) {
+      return Err(AuthMessage::AuthPersonalTokenExpiryInvalid);
+    }
+
+    let token = format!(
+      "{PERSONAL_TOKEN_PREFIX}{}",
+      generators::code::generate_code(PERSONAL_TOKEN_LENGTH)
+    );
+    let mut scopes: Vec<&str> = credentials.scopes.iter().map(TokenScope::as_str).collect();
+    scopes.sort_unstable();
+    scopes.dedup();
+
+    let personal_token = sqlx::query_as::<_, PersonalToken>(
+      r#"
+        INSERT INTO personal_access_tokens (user_id, name, scopes, token_hash, expires_at)
+        VALUES ($1::uuid, $2, $3, $4, $5)
+        RETURNING id, name, scopes, status::text AS status, expires_at, last_used_at, created_at
+      "#,
+    )
+    .bind(user_id)
+    .bind(credentials.name.trim())
+    .bind(&scopes)
+    .bind(Self::hash_token(&token))
+    .bind(Utc::now() + Duration::days(credentials.expires_in_days))
+    .fetch_one(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthPersonalTokenCreateFailed
+    })?;
+
+    Ok(CreatedPersonalToken {
+      token,
+      personal_token,
+    })
+  }
+
+  pub async fn list(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+  ) -> Result<Vec<PersonalToken>, AuthMessage> {
+    sqlx::query_as::<_, PersonalToken>(
+      r#"
+        SELECT id, name, scopes,
+          CASE WHEN status = 'active' AND expires_at <= NOW() THEN 'expired' ELSE status::text END
+            AS status,
+          expires_at, last_used_at, created_at
+        FROM personal_access_tokens
+        WHERE user_id = $1::uuid
+        ORDER BY created_at DESC
+      "#,
+    )
+    .bind(user_id)
+    .fetch_all(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthPersonalTokenListFailed
+    })
+  }
+
+  pub async fn revoke(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+    token_id: Uuid,
+  ) -> Result<(), AuthMessage> {
+    let result = sqlx::query(
+      r#"
+        UPDATE personal_access_tokens
+        SET status = 'revoked'
+        WHERE id = $1::uuid AND user_id = $2::uuid AND status = 'active'
+      "#,
+    )
+    .bind(token_id)
+    .bind(user_id)
+    .execute(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthPersonalTokenRevokeFailed
+    })?;
+
+    if result.rows_affected() == 0 {
+      return Err(AuthMessage::AuthPersonalTokenNotFound);
+    }
+
+    Ok(())
+  }
+
+  /// Resolves a live token to its owner and scopes, recording the use.
+  pub async fn authenticate(
+    data: &web::Data<AppState>,
+    token: &str,
+  ) -> Result<(Uuid, Vec<TokenScope>), AuthMessage> {
+    let (user_id, scopes) = sqlx::query_as::<_, (Uuid, Vec<String>)>(
+      r#"
+        UPDATE personal_access_tokens
+        SET last_used_at = NOW()
+        WHERE token_hash = $1 AND status = 'active' AND expires_at > NOW()
+        RETURNING user_id, scopes
+      "#,
+    )
+    .bind(Self::hash_token(token))
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthPersonalTokenInvalid
+    })?
+    .ok_or(AuthMessage::AuthPersonalTokenInvalid)?;
+
+    Ok((
+      user_id,
+      scopes.iter().filter_map(|s| TokenScope::parse(s)).collect(),
+    ))
+  }
+}
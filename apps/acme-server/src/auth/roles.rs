@@ -0,0 +1,119 @@
+use actix_web::web;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::{
+  auth::{
+    constants::AuthMessage,
+    types::{Grants, Permission, Role},
+  },
+  AppState,
+};
+
+/// Roles granted to users and the permissions they carry, both stored in Postgres.
+pub struct RoleService;
+
+impl RoleService {
+  /// Loads the roles of `user_id` together with the permissions they grant.
+  pub async fn grants_of(data: &web::Data<AppState>, user_id: Uuid) -> Result<Grants, AuthMessage> {
+    let rows = sqlx::query_as::<_, (String, Option<String>)>(
+      r#"
+        SELECT r.name, p.name
+        FROM user_roles ur
+        JOIN roles r ON r.id = ur.role_id
+        LEFT JOIN role_permissions rp ON rp.role_id = r.id
+        LEFT JOIN permissions p ON p.id = rp.permission_id
+        WHERE ur.user_id = $1::uuid
+      "#,
+    )
+    .bind(user_id)
+    .fetch_all(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthRoleLookupFailed
+    })?;
+
+    let mut grants = Grants::default();
+    for (role, permission) in rows {
+      if let Some(role) = Role::parse(&role)
+        && !grants.roles.contains(&role)
+      {
+        grants.roles.push(role);
+      }
+      if let Some(permission) = permission.as_deref().and_then(Permission::parse)
+        && !grants.permissions.contains(&permission)
+      {
+        grants.permissions.push(permission);
+      }
+    }
+
+    Ok(grants)
+  }
+
+  /// Grants `role` to `user_id`; granting a role the user already has is a no-op. Fails when the
+  /// user or the role row does not exist.
+  pub async fn grant<'e, E>(
+    executor: E,
+    user_id: Uuid,
+    role: Role,
+    granted_by: Option<Uuid>,
+  ) -> Result<(), AuthMessage>
+  where
+    E: Executor<'e, Database = Postgres>,
+  {
+    let result = sqlx::query(
+      r#"
+        INSERT INTO user_roles (user_id, role_id, granted_by)
+        SELECT u.id, r.id, $3::uuid
+        FROM users u, roles r
+        WHERE u.id = $1::uuid AND r.name = $2
+        ON CONFLICT (user_id, role_id) DO UPDATE SET granted_by = user_roles.granted_by
+      "#,
+    )
+    .bind(user_id)
+    .bind(role.as_str())
+    .bind(granted_by)
+    .execute(executor)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthRoleGrantFailed
+    })?;
+
+    // A repeated grant still touches its row, so nothing affected means the user or role is missing
+    if result.rows_affected() == 0 {
+      return Err(AuthMessage::AuthRoleGrantFailed);
+    }
+
+    Ok(())
+  }
+
+  pub async fn revoke(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+    role: Role,
+  ) -> Result<(), AuthMessage> {
+    let result = sqlx::query(
+      r#"
+        DELETE FROM user_roles ur
+        USING roles r
+        WHERE ur.role_id = r.id AND ur.user_id = $1::uuid AND r.name = $2
+      "#,
+    )
+    .bind(user_id)
+    .bind(role.as_str())
+    .execute(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthRoleRevokeFailed
+    })?;
+
+    if result.rows_affected() == 0 {
+      return Err(AuthMessage::AuthRoleNotAssigned);
+    }
+
+    Ok(())
+  }
+}
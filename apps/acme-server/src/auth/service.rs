@@ -1,5 +1,8 @@
+use tokio::sync::OnceCell;
+
 use crate::{
   auth::{constants::AuthMessage, dto::SigninDto},
+  common::functionalities::password,
   user::types::User,
 };
 
@@ -7,12 +10,16 @@ use actix_web::web;
 
 use crate::AppState;
 
+/// Hash checked against when the username does not exist, so unknown users cost as much as
+/// wrong passwords and can't be told apart by timing.
+static DUMMY_PASSWORD_HASH: OnceCell<Option<String>> = OnceCell::const_new();
+
 pub struct AuthService {}
 
 impl AuthService {
   pub async fn signin(
-    data: web::Data<AppState>,
-    credentials: web::Json<SigninDto>,
+    data: &web::Data<AppState>,
+    credentials: &SigninDto,
   ) -> Result<User, AuthMessage> {
     // fetching the user from the database
     let user = match sqlx::query_as::<_, User>(
@@ -23,20 +30,72 @@ impl AuthService {
       "#,
     )
     .bind(&credentials.username)
-    .fetch_one(&data.db)
+    .fetch_optional(&data.db)
     .await
     {
       Ok(user) => user,
       Err(e) => {
         println!("{:?}", e);
-        return Err(AuthMessage::AuthUserNotFound.into());
+        return Err(AuthMessage::AuthSigninFailed);
       },
     };
 
+    let Some(user) = user else {
+      let dummy = DUMMY_PASSWORD_HASH
+        .get_or_init(|| async { password::hash("acme-dummy-password").await.ok() })
+        .await;
+      if let Some(dummy) = dummy {
+        let _ = password::verify(&credentials.password, dummy).await;
+      }
+      return Err(AuthMessage::AuthInvalidCredentials);
+    };
+
     // verifying the password
-    let _ = bcrypt::verify(&credentials.password, &user.password_hash)
-      .map_err(|_| AuthMessage::AuthPasswordInvalid);
+    // A stored hash that can't be read answers like a wrong password, and counts as one
+    let valid = password::verify(&credentials.password, &user.password_hash)
+      .await
+      .map_err(|e| {
+        println!("{:?}", e);
+        AuthMessage::AuthInvalidCredentials
+      })?;
+
+    if !valid {
+      return Err(AuthMessage::AuthInvalidCredentials);
+    }
+
+    if password::needs_rehash(&user.password_hash) {
+      Self::rehash_password(data, &user, &credentials.password).await;
+    }
 
     Ok(user)
   }
+
+  /// Upgrades a stored hash to the current policy while the plain password is at hand. Failing
+  /// to do so never blocks the sign-in; it is tried again on the next one.
+  async fn rehash_password(data: &web::Data<AppState>, user: &User, plain: &str) {
+    let hashed_password = match password::hash(plain).await {
+      Ok(hashed_password) => hashed_password,
+      Err(e) => {
+        println!("{:?}", e);
+        return;
+      },
+    };
+
+    // Guarded on the old hash so a concurrent password change is not overwritten
+    if let Err(e) = sqlx::query(
+      r#"
+        UPDATE users
+        SET password_hash = $1
+        WHERE id = $2::uuid AND password_hash = $3
+      "#,
+    )
+    .bind(&hashed_password)
+    .bind(user.id)
+    .bind(&user.password_hash)
+    .execute(&data.db)
+    .await
+    {
+      println!("{:?}", e);
+    }
+  }
 }
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+
+use actix_session::Session;
+use actix_web::{http::header, web, HttpRequest};
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+  auth::{constants::AuthMessage, types::SessionInfo},
+  AppState,
+};
+
+/// Session key holding the id of the signed-in user.
+pub const SESSION_USER_ID: &str = "user_id";
+/// Session key holding the id this session is registered under in the per-user index.
+pub const SESSION_ID: &str = "sid";
+/// Session key holding the user that passed the password step but still owes a second factor.
+pub const SESSION_PENDING_TWO_FACTOR_USER_ID: &str = "pending_2fa_user_id";
+/// Session key holding when (unix seconds) the pending second-factor sign-in started.
+pub const SESSION_PENDING_TWO_FACTOR_AT: &str = "pending_2fa_at";
+/// Session key holding state, nonce and PKCE verifier of a social sign-in in progress.
+pub const SESSION_PENDING_OAUTH: &str = "pending_oauth";
+/// Matches the `PersistentSession` ttl configured in `main`.
+const SESSION_INDEX_TTL_SECS: usize = 7 * 24 * 60 * 60;
+
+/// Per-user index of live sessions kept in redis, so sessions can be listed and revoked
+/// server-side.
+///
+/// `auth:sessions:{user_id}` is the set of session ids, and `auth:session:{user_id}:{sid}` a
+/// hash with the details shown to the user.
+pub struct SessionRegistry;
+
+impl SessionRegistry {
+  fn index_key(user_id: Uuid) -> String {
+    format!("auth:sessions:{user_id}")
+  }
+
+  fn details_key(user_id: Uuid, sid: &str) -> String {
+    format!("auth:session:{user_id}:{sid}")
+  }
+
+  /// Signs `user_id` into `session` and records it in the user's session index; returns the new
+  /// session id.
+  pub async fn start(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    session: &Session,
+    user_id: Uuid,
+  ) -> Result<String, AuthMessage> {
+    let sid = Uuid::new_v4().to_string();
+    let mut redis = data.redis.clone();
+    let key = Self::index_key(user_id);
+    let details_key = Self::details_key(user_id, &sid);
+
+    let now = Utc::now().timestamp();
+    let user_agent = req
+      .headers()
+      .get(header::USER_AGENT)
+      .and_then(|value| value.to_str().ok())
+      .unwrap_or_default()
+      .to_string();
+    let ip = req
+      .connection_info()
+      .peer_addr()
+      .unwrap_or_default()
+      .to_string();
+
+    redis::pipe()
+      .sadd(&key, &sid)
+      .ignore()
+      .expire(&key, SESSION_INDEX_TTL_SECS)
+      .ignore()
+      .hset_multiple(
+        &details_key,
+        &[
+          ("created_at", now.to_string()),
+          ("last_seen_at", now.to_string()),
+          ("user_agent", user_agent),
+          ("ip", ip),
+        ],
+      )
+      .ignore()
+      .expire(&details_key, SESSION_INDEX_TTL_SECS)
+      .ignore()
+      .query_async::<_, ()>(&mut redis)
+      .await
+      .map_err(|e| {
+        println!("{:?}", e);
+        AuthMessage::AuthInsertUserIdSessionFailed
+      })?;
+
+    session.renew();
+    session.remove(SESSION_PENDING_TWO_FACTOR_USER_ID);
+    session.remove(SESSION_PENDING_TWO_FACTOR_AT);
+    Self::insert(session, SESSION_USER_ID, user_id.to_string())?;
+    Self::insert(session, SESSION_ID, &sid)?;
+
+    Ok(sid)
+  }
+
+  /// Parks `user_id` in `session` until the second factor passes, without signing them in.
+  pub fn start_pending_two_factor(session: &Session, user_id: Uuid) -> Result<(), AuthMessage> {
+    session.renew();
+    Self::insert(session, SESSION_PENDING_TWO_FACTOR_USER_ID, user_id)?;
+    Self::insert(
+      session,
+      SESSION_PENDING_TWO_FACTOR_AT,
+      Utc::now().timestamp(),
+    )
+  }
+
+  /// Stores `value` under `key`, refusing values the session can't serialize.
+  pub fn insert(session: &Session, key: &str, value: impl Serialize) -> Result<(), AuthMessage> {
+    session.insert(key, value).map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthInsertUserIdSessionFailed
+    })
+  }
+
+  /// Whether the session `sid` of `user_id` has not been revoked.
+  pub async fn is_active(data: &web::Data<AppState>, user_id: Uuid, sid: &str) -> bool {
+    let mut redis = data.redis.clone();
+    redis
+      .sismember(Self::index_key(user_id), sid)
+      .await
+      .unwrap_or_else(|e| {
+        println!("{:?}", e);
+        false
+      })
+  }
+
+  /// Records that the session `sid` of `user_id` was just used, keeping its entry and the index
+  /// alive as long as the session itself.
+  pub async fn touch(data: &web::Data<AppState>, user_id: Uuid, sid: &str) {
+    let mut redis = data.redis.clone();
+    let key = Self::index_key(user_id);
+    let details_key = Self::details_key(user_id, sid);
+
+    let _: Result<(), _> = redis::pipe()
+      .hset(&details_key, "last_seen_at", Utc::now().timestamp())
+      .ignore()
+      .expire(&details_key, SESSION_INDEX_TTL_SECS)
+      .ignore()
+      .expire(&key, SESSION_INDEX_TTL_SECS)
+      .ignore()
+      .query_async(&mut redis)
+      .await;
+  }
+
+  /// Lists the live sessions of `user_id`, flagging `current_sid` as the caller's own.
+  ///
+  /// Sessions whose entry expired are dropped from the index on the way.
+  pub async fn list(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+    current_sid: Option<&str>,
+  ) -> Result<Vec<SessionInfo>, AuthMessage> {
+    let mut redis = data.redis.clone();
+
+    let sids: Vec<String> = redis
+      .smembers(Self::index_key(user_id))
+      .await
+      .map_err(|e| {
+        println!("{:?}", e);
+        AuthMessage::AuthListSessionsFailed
+      })?;
+
+    let mut sessions = Vec::with_capacity(sids.len());
+    for sid in sids {
+      let details: HashMap<String, String> = redis
+        .hgetall(Self::details_key(user_id, &sid))
+        .await
+        .map_err(|e| {
+          println!("{:?}", e);
+          AuthMessage::AuthListSessionsFailed
+        })?;
+
+      if details.is_empty() {
+        let _: Result<(), _> = redis.srem(Self::index_key(user_id), &sid).await;
+        continue;
+      }
+
+      let timestamp = |field: &str| {
+        details
+          .get(field)
+          .and_then(|value| value.parse::<i64>().ok())
+          .and_then(|secs| DateTime::<Utc>::from_timestamp(secs, 0))
+      };
+
+      sessions.push(SessionInfo {
+        current: current_sid == Some(sid.as_str()),
+        created_at: timestamp("created_at"),
+        last_seen_at: timestamp("last_seen_at"),
+        user_agent: details.get("user_agent").cloned().filter(|v| !v.is_empty()),
+        ip: details.get("ip").cloned().filter(|v| !v.is_empty()),
+        id: sid,
+      });
+    }
+
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+
+    Ok(sessions)
+  }
+
+  /// Removes the session `sid` of `user_id` from the index; returns whether it existed.
+  pub async fn revoke(data: &web::Data<AppState>, user_id: Uuid, sid: &str) -> bool {
+    let mut redis = data.redis.clone();
+    let removed: i64 = redis
+      .srem(Self::index_key(user_id), sid)
+      .await
+      .unwrap_or_default();
+    let _: Result<(), _> = redis.del(Self::details_key(user_id, sid)).await;
+
+    removed > 0
+  }
+
+  /// Revokes every session of `user_id` except `keep_sid`; they are rejected on their next
+  /// request.
+  pub async fn revoke_others(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+    keep_sid: Option<&str>,
+  ) -> Result<(), AuthMessage> {
+    let mut redis = data.redis.clone();
+
+    let sids: Vec<String> = redis
+      .smembers(Self::index_key(user_id))
+      .await
+      .map_err(|e| {
+        println!("{:?}", e);
+        AuthMessage::AuthRevokeSessionsFailed
+      })?;
+
+    let mut pipe = redis::pipe();
+    for sid in sids.iter().filter(|sid| Some(sid.as_str()) != keep_sid) {
+      pipe
+        .srem(Self::index_key(user_id), sid)
+        .ignore()
+        .del(Self::details_key(user_id, sid))
+        .ignore();
+    }
+
+    pipe.query_async::<_, ()>(&mut redis).await.map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthRevokeSessionsFailed
+    })
+  }
+
+  /// Revokes every session of `user_id`.
+  pub async fn revoke_all(data: &web::Data<AppState>, user_id: Uuid) -> Result<(), AuthMessage> {
+    Self::revoke_others(data, user_id, None).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_session::SessionExt;
+  use actix_web::test::TestRequest;
+  use serde::{ser::Error, Serializer};
+
+  use super::*;
+  use crate::common::testing;
+
+  /// A value whose serialization always fails, like a map with non-string keys would.
+  struct Unserializable;
+
+  impl Serialize for Unserializable {
+    fn serialize<S: Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+      Err(S::Error::custom("not today"))
+    }
+  }
+
+  #[test]
+  fn unserializable_value_is_refused() {
+    let session = TestRequest::default().to_http_request().get_session();
+
+    let e = SessionRegistry::insert(&session, SESSION_PENDING_OAUTH, Unserializable).unwrap_err();
+
+    assert!(matches!(e, AuthMessage::AuthInsertUserIdSessionFailed));
+    assert!(session
+      .get::<String>(SESSION_PENDING_OAUTH)
+      .unwrap()
+      .is_none());
+  }
+
+  #[test]
+  fn pending_two_factor_is_stored() {
+    let session = TestRequest::default().to_http_request().get_session();
+    let user_id = Uuid::new_v4();
+
+    SessionRegistry::start_pending_two_factor(&session, user_id).unwrap();
+
+    assert_eq!(
+      session
+        .get::<Uuid>(SESSION_PENDING_TWO_FACTOR_USER_ID)
+        .unwrap(),
+      Some(user_id)
+    );
+  }
+
+  #[actix_web::test]
+  #[ignore = "needs a migrated Postgres (TEST_DATABASE_URL) and Redis (TEST_REDIS_URL)"]
+  async fn expired_sessions_leave_the_index() {
+    let state = testing::state().await;
+    let req = TestRequest::default().to_http_request();
+    let user_id = Uuid::new_v4();
+
+    let live = SessionRegistry::start(&state.data, &req, &req.get_session(), user_id)
+      .await
+      .unwrap();
+    let expired = SessionRegistry::start(&state.data, &req, &req.get_session(), user_id)
+      .await
+      .unwrap();
+    let mut redis = state.data.redis.clone();
+    let _: () = redis
+      .del(SessionRegistry::details_key(user_id, &expired))
+      .await
+      .unwrap();
+
+    let sessions = SessionRegistry::list(&state.data, user_id, None)
+      .await
+      .unwrap();
+
+    assert_eq!(
+      sessions.iter().map(|s| s.id.as_str()).collect::<Vec<_>>(),
+      [live.as_str()]
+    );
+    assert!(!SessionRegistry::is_active(&state.data, user_id, &expired).await);
+  }
+
+  #[actix_web::test]
+  #[ignore = "needs a migrated Postgres (TEST_DATABASE_URL) and Redis (TEST_REDIS_URL)"]
+  async fn touch_keeps_the_index_alive() {
+    let state = testing::state().await;
+    let req = TestRequest::default().to_http_request();
+    let user_id = Uuid::new_v4();
+    let sid = SessionRegistry::start(&state.data, &req, &req.get_session(), user_id)
+      .await
+      .unwrap();
+    let mut redis = state.data.redis.clone();
+    let _: () = redis
+      .expire(SessionRegistry::index_key(user_id), 60)
+      .await
+      .unwrap();
+
+    SessionRegistry::touch(&state.data, user_id, &sid).await;
+
+    let ttl: i64 = redis
+      .ttl(SessionRegistry::index_key(user_id))
+      .await
+      .unwrap();
+    assert!(ttl > 60);
+  }
+}
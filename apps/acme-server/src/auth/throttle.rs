@@ -0,0 +1,184 @@
+use actix_web::web;
+use redis::{AsyncCommands, RedisError, RedisResult};
+
+use crate::{
+  auth::constants::{
+    AuthMessage, FAILED_ATTEMPTS_WINDOW_SECS, LOCKOUT_DURATION_SECS, MAX_FAILED_ATTEMPTS_PER_IP,
+    MAX_FAILED_ATTEMPTS_PER_USER, THROTTLE_UNAVAILABLE_RETRY_SECS,
+  },
+  AppState,
+};
+
+/// A sign-in that was refused because the account or the client IP is locked; answered through
+/// `AppError` with a `Retry-After` header.
+#[derive(Debug)]
+pub struct Lockout {
+  pub message: AuthMessage,
+  pub retry_after: i64,
+}
+
+/// Per-account and per-IP failed sign-in counters kept in redis.
+///
+/// Fails closed: while redis can't be reached no sign-in is attempted, otherwise an outage would
+/// lift the limit on password guessing.
+pub struct LoginThrottle;
+
+impl LoginThrottle {
+  fn user_key(kind: &str, username: &str) -> String {
+    format!("auth:{kind}:user:{}", username.to_lowercase())
+  }
+
+  fn ip_key(kind: &str, ip: &str) -> String {
+    format!("auth:{kind}:ip:{ip}")
+  }
+
+  /// Returns the lockout for this username/IP pair if either of them is currently locked, or if
+  /// redis can't tell.
+  pub async fn check(data: &web::Data<AppState>, username: &str, ip: &str) -> Result<(), Lockout> {
+    let mut redis = data.redis.clone();
+
+    let user_ttl = redis.ttl(Self::user_key("lock", username)).await;
+    Self::locked(user_ttl, AuthMessage::AuthAccountLocked)?;
+
+    let ip_ttl = redis.ttl(Self::ip_key("lock", ip)).await;
+    Self::locked(ip_ttl, AuthMessage::AuthTooManyAttempts)
+  }
+
+  /// Reads the TTL of a lock key: a running lock is answered with `message` until it expires.
+  fn locked(ttl: RedisResult<i64>, message: AuthMessage) -> Result<(), Lockout> {
+    match ttl.map_err(Self::unavailable)? {
+      ttl if ttl > 0 => Err(Lockout {
+        message,
+        retry_after: ttl,
+      }),
+      _ => Ok(()),
+    }
+  }
+
+  fn unavailable(e: RedisError) -> Lockout {
+    println!("{:?}", e);
+    Lockout {
+      message: AuthMessage::AuthThrottleUnavailable,
+      retry_after: THROTTLE_UNAVAILABLE_RETRY_SECS,
+    }
+  }
+
+  /// Counts a failed attempt and locks the account and/or IP once their limit is reached.
+  pub async fn record_failure(
+    data: &web::Data<AppState>,
+    username: &str,
+    ip: &str,
+  ) -> Result<(), Lockout> {
+    let mut redis = data.redis.clone();
+
+    let user_failures = Self::bump(&mut redis, &Self::user_key("fail", username))
+      .await
+      .map_err(Self::unavailable)?;
+    let ip_failures = Self::bump(&mut redis, &Self::ip_key("fail", ip))
+      .await
+      .map_err(Self::unavailable)?;
+
+    if user_failures >= MAX_FAILED_ATTEMPTS_PER_USER {
+      Self::lock(&mut redis, &Self::user_key("lock", username))
+        .await
+        .map_err(Self::unavailable)?;
+      let _: Result<(), _> = redis.del(Self::user_key("fail", username)).await;
+      return Err(Lockout {
+        message: AuthMessage::AuthAccountLocked,
+        retry_after: LOCKOUT_DURATION_SECS,
+      });
+    }
+
+    if ip_failures >= MAX_FAILED_ATTEMPTS_PER_IP {
+      Self::lock(&mut redis, &Self::ip_key("lock", ip))
+        .await
+        .map_err(Self::unavailable)?;
+      let _: Result<(), _> = redis.del(Self::ip_key("fail", ip)).await;
+      return Err(Lockout {
+        message: AuthMessage::AuthTooManyAttempts,
+        retry_after: LOCKOUT_DURATION_SECS,
+      });
+    }
+
+    Ok(())
+  }
+
+  /// Forgets the failed attempts of an account after a successful sign-in.
+  pub async fn clear(data: &web::Data<AppState>, username: &str) {
+    let mut redis = data.redis.clone();
+    let _: Result<(), _> = redis.del(Self::user_key("fail", username)).await;
+  }
+
+  async fn bump(redis: &mut redis::aio::ConnectionManager, key: &str) -> RedisResult<i64> {
+    let count: i64 = redis.incr(key, 1).await?;
+
+    // Only the first failure opens the window, later ones must not extend it
+    if count == 1 {
+      let _: () = redis
+        .expire(key, FAILED_ATTEMPTS_WINDOW_SECS as usize)
+        .await?;
+    }
+
+    Ok(count)
+  }
+
+  async fn lock(redis: &mut redis::aio::ConnectionManager, key: &str) -> RedisResult<()> {
+    redis.set_ex(key, 1, LOCKOUT_DURATION_SECS as usize).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::{http::StatusCode, ResponseError};
+  use redis::ErrorKind;
+
+  use super::*;
+  use crate::common::error::AppError;
+
+  fn outage() -> RedisResult<i64> {
+    Err(RedisError::from((ErrorKind::IoError, "connection refused")))
+  }
+
+  #[test]
+  fn redis_outage_refuses_the_signin() {
+    let lockout = LoginThrottle::locked(outage(), AuthMessage::AuthAccountLocked).unwrap_err();
+
+    assert!(matches!(
+      lockout.message,
+      AuthMessage::AuthThrottleUnavailable
+    ));
+    assert_eq!(lockout.retry_after, THROTTLE_UNAVAILABLE_RETRY_SECS);
+    assert_eq!(
+      AppError::from(lockout).status_code(),
+      StatusCode::SERVICE_UNAVAILABLE
+    );
+  }
+
+  #[test]
+  fn running_lock_tells_how_long_is_left() {
+    let lockout = LoginThrottle::locked(Ok(42), AuthMessage::AuthTooManyAttempts).unwrap_err();
+
+    assert!(matches!(lockout.message, AuthMessage::AuthTooManyAttempts));
+    assert_eq!(lockout.retry_after, 42);
+  }
+
+  #[test]
+  fn missing_or_expired_lock_lets_through() {
+    // redis answers -2 for a missing key and -1 for one without expiry
+    assert!(LoginThrottle::locked(Ok(-2), AuthMessage::AuthAccountLocked).is_ok());
+    assert!(LoginThrottle::locked(Ok(-1), AuthMessage::AuthAccountLocked).is_ok());
+    assert!(LoginThrottle::locked(Ok(0), AuthMessage::AuthAccountLocked).is_ok());
+  }
+
+  #[test]
+  fn accounts_are_counted_whatever_the_case() {
+    assert_eq!(
+      LoginThrottle::user_key("fail", "JohnDoe"),
+      LoginThrottle::user_key("fail", "johndoe")
+    );
+    assert_ne!(
+      LoginThrottle::user_key("fail", "johndoe"),
+      LoginThrottle::user_key("lock", "johndoe")
+    );
+  }
+}
@@ -0,0 +1,204 @@
+use actix_web::web;
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+  auth::{
+    constants::{
+      AuthMessage, ACCESS_TOKEN_PURPOSE, ACCESS_TOKEN_TTL_SECS, REFRESH_TOKEN_LENGTH,
+      REFRESH_TOKEN_TTL_SECS,
+    },
+    types::TokenPair,
+  },
+  common::{functionalities::signing, generators},
+  AppState,
+};
+
+/// Bearer credentials for clients that can't keep the session cookie.
+///
+/// Access tokens are `{user_id}.{expires_at}.{signature}` and are checked without a lookup.
+/// Refresh tokens are random, stored hashed, and rotated on every use; all rotations of one
+/// sign-in share a family, so presenting an already-rotated token revokes the whole family.
+pub struct TokenService;
+
+impl TokenService {
+  fn hash_refresh_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+  }
+
+  fn access_token(user_id: Uuid) -> String {
+    let expires_at = Utc::now().timestamp() + ACCESS_TOKEN_TTL_SECS;
+    let payload = format!("{user_id}.{expires_at}");
+    let signature = signing::sign(ACCESS_TOKEN_PURPOSE, &payload);
+
+    format!("{payload}.{signature}")
+  }
+
+  /// Returns the user an access token was issued to, if it is genuine and not expired.
+  pub fn verify_access_token(token: &str) -> Option<Uuid> {
+    let (payload, signature) = token.rsplit_once('.')?;
+    if !signing::verify(ACCESS_TOKEN_PURPOSE, payload, signature) {
+      return None;
+    }
+
+    let (user_id, expires_at) = payload.split_once('.')?;
+    if expires_at.parse::<i64>().ok()? < Utc::now().timestamp() {
+      return None;
+    }
+
+    Uuid::parse_str(user_id).ok()
+  }
+
+  /// Issues a fresh pair; `family_id` continues an existing sign-in, `None` starts a new one.
+  pub async fn issue(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+    family_id: Option<Uuid>,
+  ) -> Result<TokenPair, AuthMessage> {
+    let refresh_token = generators::code::generate_code(REFRESH_TOKEN_LENGTH);
+    let expires_at = Utc::now() + Duration::seconds(REFRESH_TOKEN_TTL_SECS);
+
+    sqlx::query(
+      r#"
+        INSERT INTO refresh_tokens (user_id, family_id, token_hash, expires_at)
+        VALUES ($1::uuid, $2::uuid, $3, $4)
+      "#,
+    )
+    .bind(user_id)
+    .bind(family_id.unwrap_or_else(Uuid::new_v4))
+    .bind(Self::hash_refresh_token(&refresh_token))
+    .bind(expires_at)
+    .execute(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTokenIssueFailed
+    })?;
+
+    Ok(TokenPair {
+      access_token: Self::access_token(user_id),
+      token_type: "Bearer",
+      expires_in: ACCESS_TOKEN_TTL_SECS,
+      refresh_token,
+    })
+  }
+
+  /// Trades a refresh token for a new pair, retiring the old one.
+  pub async fn refresh(
+    data: &web::Data<AppState>,
+    refresh_token: &str,
+  ) -> Result<TokenPair, AuthMessage> {
+    let mut tx = data.db.begin().await.map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTokenIssueFailed
+    })?;
+
+    let token = sqlx::query_as::<_, (Uuid, Uuid, Uuid, bool, bool)>(
+      r#"
+        SELECT id, user_id, family_id, status = 'active', expires_at > NOW()
+        FROM refresh_tokens
+        WHERE token_hash = $1
+        FOR UPDATE
+      "#,
+    )
+    .bind(Self::hash_refresh_token(refresh_token))
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTokenIssueFailed
+    })?;
+
+    let (id, user_id, family_id) = match token {
+      Some((id, user_id, family_id, true, true)) => (id, user_id, family_id),
+      // Already rotated or revoked: whoever holds the family can't be trusted anymore
+      Some((_, _, family_id, false, _)) => {
+        drop(tx);
+        Self::revoke_family(data, family_id).await?;
+        return Err(AuthMessage::AuthRefreshTokenReused);
+      },
+      _ => return Err(AuthMessage::AuthRefreshTokenInvalid),
+    };
+
+    sqlx::query(
+      r#"
+        UPDATE refresh_tokens
+        SET status = 'expired', used_at = NOW()
+        WHERE id = $1::uuid
+      "#,
+    )
+    .bind(id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTokenIssueFailed
+    })?;
+
+    tx.commit().await.map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTokenIssueFailed
+    })?;
+
+    Self::issue(data, user_id, Some(family_id)).await
+  }
+
+  /// Signs a token client out by revoking the family `refresh_token` belongs to.
+  pub async fn revoke(data: &web::Data<AppState>, refresh_token: &str) -> Result<(), AuthMessage> {
+    let family_id = sqlx::query_scalar::<_, Uuid>(
+      r#"
+        SELECT family_id
+        FROM refresh_tokens
+        WHERE token_hash = $1
+      "#,
+    )
+    .bind(Self::hash_refresh_token(refresh_token))
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthRevokeSessionsFailed
+    })?
+    .ok_or(AuthMessage::AuthRefreshTokenInvalid)?;
+
+    Self::revoke_family(data, family_id).await
+  }
+
+  async fn revoke_family(data: &web::Data<AppState>, family_id: Uuid) -> Result<(), AuthMessage> {
+    sqlx::query(
+      r#"
+        UPDATE refresh_tokens
+        SET status = 'revoked'
+        WHERE family_id = $1::uuid AND status = 'active'
+      "#,
+    )
+    .bind(family_id)
+    .execute(&data.db)
+    .await
+    .map(|_| ())
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthRevokeSessionsFailed
+    })
+  }
+
+  /// Revokes every refresh token of `user_id`; their access tokens lapse within minutes.
+  pub async fn revoke_all(data: &web::Data<AppState>, user_id: Uuid) -> Result<(), AuthMessage> {
+    sqlx::query(
+      r#"
+        UPDATE refresh_tokens
+        SET status = 'revoked'
+        WHERE user_id = $1::uuid AND status = 'active'
+      "#,
+    )
+    .bind(user_id)
+    .execute(&data.db)
+    .await
+    .map(|_| ())
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthRevokeSessionsFailed
+    })
+  }
+}
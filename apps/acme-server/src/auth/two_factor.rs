@@ -0,0 +1,336 @@
+use actix_web::web;
+use sha2::{Digest, Sha256};
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+use crate::{
+  auth::{
+    constants::{AuthMessage, RECOVERY_CODE_COUNT, RECOVERY_CODE_LENGTH, TOTP_ISSUER},
+    dto::TwoFactorVerifyDto,
+    types::TotpEnrollment,
+  },
+  common::generators,
+  user::types::User,
+  AppState,
+};
+
+/// RFC 6238 TOTP second factor and its recovery codes.
+pub struct TwoFactorService;
+
+impl TwoFactorService {
+  fn totp(secret: &str, username: &str) -> Result<TOTP, AuthMessage> {
+    let secret = Secret::Encoded(secret.to_string())
+      .to_bytes()
+      .map_err(|e| {
+        println!("{:?}", e);
+        AuthMessage::AuthTwoFactorFailed
+      })?;
+
+    TOTP::new(
+      Algorithm::SHA1,
+      6,
+      1,
+      30,
+      secret,
+      Some(TOTP_ISSUER.to_string()),
+      username.replace(':', ""),
+    )
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTwoFactorFailed
+    })
+  }
+
+  fn hash_recovery_code(code: &str) -> String {
+    hex::encode(Sha256::digest(code.trim().as_bytes()))
+  }
+
+  async fn find_user(data: &web::Data<AppState>, user_id: Uuid) -> Result<User, AuthMessage> {
+    sqlx::query_as::<_, User>(
+      r#"
+        SELECT *
+        FROM users
+        WHERE id = $1::uuid
+      "#,
+    )
+    .bind(user_id)
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTwoFactorFailed
+    })?
+    .ok_or(AuthMessage::AuthUserNotFound)
+  }
+
+  /// Loads the user a pending second-factor sign-in belongs to.
+  pub async fn pending_user(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+  ) -> Result<User, AuthMessage> {
+    let user = Self::find_user(data, user_id).await?;
+
+    if user.totp_enabled_at.is_none() {
+      return Err(AuthMessage::AuthTwoFactorNoPendingSignin);
+    }
+
+    Ok(user)
+  }
+
+  /// Creates a new secret for the user. It only becomes active once `confirm` succeeds.
+  pub async fn enroll(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+  ) -> Result<TotpEnrollment, AuthMessage> {
+    let user = Self::find_user(data, user_id).await?;
+
+    if user.totp_enabled_at.is_some() {
+      return Err(AuthMessage::AuthTwoFactorAlreadyEnabled);
+    }
+
+    let secret = match Secret::generate_secret().to_encoded() {
+      Secret::Encoded(secret) => secret,
+      Secret::Raw(_) => return Err(AuthMessage::AuthTwoFactorFailed),
+    };
+    let totp = Self::totp(&secret, &user.username)?;
+
+    sqlx::query(
+      r#"
+        UPDATE users
+        SET totp_secret = $1, updated_at = NOW()
+        WHERE id = $2::uuid
+      "#,
+    )
+    .bind(&secret)
+    .bind(user_id)
+    .execute(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTwoFactorFailed
+    })?;
+
+    Ok(TotpEnrollment {
+      otpauth_uri: totp.get_url(),
+      secret,
+    })
+  }
+
+  /// Turns 2FA on once the user proved their authenticator works, and returns fresh
+  /// recovery codes. This is the only time the plain codes are available.
+  pub async fn confirm(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+    code: &str,
+  ) -> Result<Vec<String>, AuthMessage> {
+    let user = Self::find_user(data, user_id).await?;
+
+    if user.totp_enabled_at.is_some() {
+      return Err(AuthMessage::AuthTwoFactorAlreadyEnabled);
+    }
+
+    Self::check_totp(data, &user, code).await?;
+
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+      .map(|_| generators::code::generate_code(RECOVERY_CODE_LENGTH))
+      .collect();
+    let hashes: Vec<String> = codes.iter().map(|c| Self::hash_recovery_code(c)).collect();
+
+    let mut tx = data.db.begin().await.map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTwoFactorFailed
+    })?;
+
+    sqlx::query(
+      r#"
+        UPDATE users
+        SET totp_enabled_at = NOW(), updated_at = NOW()
+        WHERE id = $1::uuid
+      "#,
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTwoFactorFailed
+    })?;
+
+    sqlx::query(
+      r#"
+        DELETE FROM recovery_codes
+        WHERE user_id = $1::uuid
+      "#,
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTwoFactorFailed
+    })?;
+
+    sqlx::query(
+      r#"
+        INSERT INTO recovery_codes (user_id, code_hash)
+        SELECT $1::uuid, UNNEST($2::varchar[])
+      "#,
+    )
+    .bind(user_id)
+    .bind(&hashes)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTwoFactorFailed
+    })?;
+
+    tx.commit().await.map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTwoFactorFailed
+    })?;
+
+    Ok(codes)
+  }
+
+  /// Checks the second factor of a sign-in, either a TOTP code or an unused recovery code.
+  pub async fn verify(
+    data: &web::Data<AppState>,
+    user: &User,
+    credentials: &TwoFactorVerifyDto,
+  ) -> Result<(), AuthMessage> {
+    match (&credentials.code, &credentials.recovery_code) {
+      (Some(code), _) => Self::check_totp(data, user, code).await,
+      (None, Some(recovery_code)) => Self::use_recovery_code(data, user.id, recovery_code).await,
+      (None, None) => Err(AuthMessage::AuthTwoFactorCodeInvalid),
+    }
+  }
+
+  /// Turns 2FA off after checking a current code, and drops the recovery codes.
+  pub async fn disable(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+    code: &str,
+  ) -> Result<(), AuthMessage> {
+    let user = Self::find_user(data, user_id).await?;
+
+    if user.totp_enabled_at.is_none() {
+      return Err(AuthMessage::AuthTwoFactorNotEnabled);
+    }
+
+    Self::check_totp(data, &user, code).await?;
+
+    let mut tx = data.db.begin().await.map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTwoFactorFailed
+    })?;
+
+    sqlx::query(
+      r#"
+        UPDATE users
+        SET totp_secret = NULL, totp_enabled_at = NULL, updated_at = NOW()
+        WHERE id = $1::uuid
+      "#,
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTwoFactorFailed
+    })?;
+
+    sqlx::query(
+      r#"
+        DELETE FROM recovery_codes
+        WHERE user_id = $1::uuid
+      "#,
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTwoFactorFailed
+    })?;
+
+    tx.commit().await.map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTwoFactorFailed
+    })?;
+
+    Ok(())
+  }
+
+  async fn check_totp(
+    data: &web::Data<AppState>,
+    user: &User,
+    code: &str,
+  ) -> Result<(), AuthMessage> {
+    let secret = user
+      .totp_secret
+      .as_deref()
+      .ok_or(AuthMessage::AuthTwoFactorNotEnrolled)?;
+    let totp = Self::totp(secret, &user.username)?;
+
+    let valid = totp.check_current(code).map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTwoFactorFailed
+    })?;
+
+    if !valid {
+      return Err(AuthMessage::AuthTwoFactorCodeInvalid);
+    }
+
+    // A code stays valid for the whole skew window, so remember it to refuse replays
+    let mut redis = data.redis.clone();
+    let fresh: Option<String> = redis::cmd("SET")
+      .arg(format!("auth:totp-used:{}:{code}", user.id))
+      .arg(1)
+      .arg("NX")
+      .arg("EX")
+      .arg(totp.step * 3)
+      .query_async(&mut redis)
+      .await
+      .map_err(|e| {
+        println!("{:?}", e);
+        AuthMessage::AuthTwoFactorFailed
+      })?;
+
+    if fresh.is_none() {
+      return Err(AuthMessage::AuthTwoFactorCodeInvalid);
+    }
+
+    Ok(())
+  }
+
+  async fn use_recovery_code(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+    recovery_code: &str,
+  ) -> Result<(), AuthMessage> {
+    let used = sqlx::query_scalar::<_, Uuid>(
+      r#"
+        UPDATE recovery_codes
+        SET used_at = NOW()
+        WHERE user_id = $1::uuid
+          AND code_hash = $2
+          AND used_at IS NULL
+        RETURNING id
+      "#,
+    )
+    .bind(user_id)
+    .bind(Self::hash_recovery_code(recovery_code))
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      AuthMessage::AuthTwoFactorFailed
+    })?;
+
+    match used {
+      Some(_) => Ok(()),
+      None => Err(AuthMessage::AuthTwoFactorCodeInvalid),
+    }
+  }
+}
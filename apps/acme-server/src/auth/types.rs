@@ -0,0 +1,158 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+pub struct TotpEnrollment {
+  pub secret: String,
+  pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodes {
+  pub recovery_codes: Vec<String>,
+}
+
+/// A live session as shown in the user's session list.
+#[derive(Debug, Serialize)]
+pub struct SessionInfo {
+  pub id: String,
+  pub current: bool,
+  pub created_at: Option<DateTime<Utc>>,
+  pub last_seen_at: Option<DateTime<Utc>>,
+  pub user_agent: Option<String>,
+  pub ip: Option<String>,
+}
+
+/// Credentials handed to bearer clients by the token endpoints.
+#[derive(Debug, Serialize)]
+pub struct TokenPair {
+  pub access_token: String,
+  pub token_type: &'static str,
+  pub expires_in: i64,
+  pub refresh_token: String,
+}
+
+/// What a personal access token may do; sessions and bearer access tokens may do everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenScope {
+  #[serde(rename = "words:read")]
+  WordsRead,
+  #[serde(rename = "words:write")]
+  WordsWrite,
+  #[serde(rename = "user:read")]
+  UserRead,
+  #[serde(rename = "user:write")]
+  UserWrite,
+}
+
+impl TokenScope {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      TokenScope::WordsRead => "words:read",
+      TokenScope::WordsWrite => "words:write",
+      TokenScope::UserRead => "user:read",
+      TokenScope::UserWrite => "user:write",
+    }
+  }
+
+  pub fn parse(scope: &str) -> Option<Self> {
+    match scope {
+      "words:read" => Some(TokenScope::WordsRead),
+      "words:write" => Some(TokenScope::WordsWrite),
+      "user:read" => Some(TokenScope::UserRead),
+      "user:write" => Some(TokenScope::UserWrite),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct PersonalToken {
+  pub id: Uuid,
+  pub name: String,
+  pub scopes: Vec<String>,
+  pub status: String,
+  pub expires_at: DateTime<Utc>,
+  pub last_used_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+}
+
+/// A newly created personal access token; `token` is never shown again.
+#[derive(Debug, Serialize)]
+pub struct CreatedPersonalToken {
+  pub token: String,
+  #[serde(flatten)]
+  pub personal_token: PersonalToken,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+  Admin,
+  Editor,
+  Member,
+}
+
+impl Role {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Role::Admin => "admin",
+      Role::Editor => "editor",
+      Role::Member => "member",
+    }
+  }
+
+  pub fn parse(role: &str) -> Option<Self> {
+    match role {
+      "admin" => Some(Role::Admin),
+      "editor" => Some(Role::Editor),
+      "member" => Some(Role::Member),
+      _ => None,
+    }
+  }
+}
+
+/// What a role allows; which roles carry which permissions lives in the `role_permissions` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Permission {
+  #[serde(rename = "words:read")]
+  WordsRead,
+  #[serde(rename = "words:write")]
+  WordsWrite,
+  #[serde(rename = "profile:read")]
+  ProfileRead,
+  #[serde(rename = "profile:write")]
+  ProfileWrite,
+  #[serde(rename = "users:read")]
+  UsersRead,
+  #[serde(rename = "newsletter:send")]
+  NewsletterSend,
+}
+
+impl Permission {
+  pub fn parse(permission: &str) -> Option<Self> {
+    match permission {
+      "words:read" => Some(Permission::WordsRead),
+      "words:write" => Some(Permission::WordsWrite),
+      "profile:read" => Some(Permission::ProfileRead),
+      "profile:write" => Some(Permission::ProfileWrite),
+      "users:read" => Some(Permission::UsersRead),
+      "newsletter:send" => Some(Permission::NewsletterSend),
+      _ => None,
+    }
+  }
+}
+
+/// The roles of a user and everything they allow.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Grants {
+  pub roles: Vec<Role>,
+  pub permissions: Vec<Permission>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OAuthAuthorization {
+  pub authorization_url: String,
+}
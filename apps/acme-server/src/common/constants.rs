@@ -0,0 +1,54 @@
+use std::{error::Error, fmt};
+
+use actix_web::http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::common::i18n::{Locale, Translate};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum CommonMessage {
+  // Error
+  ValidationFailed,
+  JsonPayloadInvalid,
+  InternalServerError,
+}
+
+impl fmt::Display for CommonMessage {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?}", self)
+  }
+}
+
+impl Error for CommonMessage {}
+
+impl CommonMessage {
+  /// Status the message is answered with when it is an error.
+  pub fn status_code(&self) -> StatusCode {
+    match self {
+      CommonMessage::ValidationFailed => StatusCode::UNPROCESSABLE_ENTITY,
+      CommonMessage::JsonPayloadInvalid => StatusCode::BAD_REQUEST,
+      CommonMessage::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+  }
+}
+
+impl Translate for CommonMessage {
+  fn translate(&self, locale: Locale) -> Option<&'static str> {
+    Some(match (self, locale) {
+      (CommonMessage::ValidationFailed, Locale::En) => "Some fields are not valid.",
+      (CommonMessage::ValidationFailed, Locale::Es) => "Algunos campos no son válidos.",
+      (CommonMessage::JsonPayloadInvalid, Locale::En) => {
+        "The request body is not valid JSON for this endpoint."
+      },
+      (CommonMessage::JsonPayloadInvalid, Locale::Es) => {
+        "El cuerpo de la petición no es un JSON válido para este endpoint."
+      },
+      (CommonMessage::InternalServerError, Locale::En) => {
+        "Something went wrong on our side. Please try again."
+      },
+      (CommonMessage::InternalServerError, Locale::Es) => {
+        "Algo ha fallado por nuestra parte. Inténtalo de nuevo."
+      },
+    })
+  }
+}
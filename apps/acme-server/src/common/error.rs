@@ -0,0 +1,230 @@
+use std::fmt;
+
+use actix_web::{
+  http::{header, StatusCode},
+  HttpResponse, ResponseError,
+};
+use serde::{Serialize, Serializer};
+
+use crate::{
+  auth::{constants::AuthMessage, throttle::Lockout},
+  common::{
+    constants::CommonMessage,
+    functionalities::api_res::api_error,
+    i18n::{Locale, Translate},
+  },
+  dev::constants::DevMessage,
+  otp_code::constants::OtpCodeMessage,
+  subscribers::constants::SubscriberMessage,
+  user::constants::UserMessage,
+  words::constants::WordsMessage,
+};
+
+/// Error side of every handler: answers with the module's message in the usual `ApiResult`
+/// envelope, under the status that message maps to.
+#[derive(Debug)]
+pub enum AppError {
+  Auth(AuthMessage),
+  User(UserMessage),
+  Words(WordsMessage),
+  OtpCode(OtpCodeMessage),
+  Subscriber(SubscriberMessage),
+  Common(CommonMessage),
+  Dev(DevMessage),
+  /// Sign-in throttling, which also tells the client when to retry.
+  Lockout(Lockout),
+}
+
+impl From<AuthMessage> for AppError {
+  fn from(message: AuthMessage) -> Self {
+    AppError::Auth(message)
+  }
+}
+
+impl From<UserMessage> for AppError {
+  fn from(message: UserMessage) -> Self {
+    AppError::User(message)
+  }
+}
+
+impl From<WordsMessage> for AppError {
+  fn from(message: WordsMessage) -> Self {
+    AppError::Words(message)
+  }
+}
+
+impl From<OtpCodeMessage> for AppError {
+  fn from(message: OtpCodeMessage) -> Self {
+    AppError::OtpCode(message)
+  }
+}
+
+impl From<SubscriberMessage> for AppError {
+  fn from(message: SubscriberMessage) -> Self {
+    AppError::Subscriber(message)
+  }
+}
+
+impl From<CommonMessage> for AppError {
+  fn from(message: CommonMessage) -> Self {
+    AppError::Common(message)
+  }
+}
+
+impl From<DevMessage> for AppError {
+  fn from(message: DevMessage) -> Self {
+    AppError::Dev(message)
+  }
+}
+
+impl From<Lockout> for AppError {
+  fn from(lockout: Lockout) -> Self {
+    AppError::Lockout(lockout)
+  }
+}
+
+/// Serializes as the bare message, so clients see the same `message` as before.
+impl Serialize for AppError {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    match self {
+      AppError::Auth(message) => message.serialize(serializer),
+      AppError::User(message) => message.serialize(serializer),
+      AppError::Words(message) => message.serialize(serializer),
+      AppError::OtpCode(message) => message.serialize(serializer),
+      AppError::Subscriber(message) => message.serialize(serializer),
+      AppError::Common(message) => message.serialize(serializer),
+      AppError::Dev(message) => message.serialize(serializer),
+      AppError::Lockout(lockout) => lockout.message.serialize(serializer),
+    }
+  }
+}
+
+impl fmt::Display for AppError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      AppError::Auth(message) => message.fmt(f),
+      AppError::User(message) => message.fmt(f),
+      AppError::Words(message) => message.fmt(f),
+      AppError::OtpCode(message) => message.fmt(f),
+      AppError::Subscriber(message) => message.fmt(f),
+      AppError::Common(message) => message.fmt(f),
+      AppError::Dev(message) => message.fmt(f),
+      AppError::Lockout(lockout) => lockout.message.fmt(f),
+    }
+  }
+}
+
+impl Translate for AppError {
+  fn translate(&self, locale: Locale) -> Option<&'static str> {
+    match self {
+      AppError::Auth(message) => message.translate(locale),
+      AppError::User(message) => message.translate(locale),
+      AppError::Words(message) => message.translate(locale),
+      AppError::OtpCode(message) => message.translate(locale),
+      AppError::Subscriber(message) => message.translate(locale),
+      AppError::Common(message) => message.translate(locale),
+      AppError::Dev(message) => message.translate(locale),
+      AppError::Lockout(lockout) => lockout.message.translate(locale),
+    }
+  }
+}
+
+impl ResponseError for AppError {
+  fn status_code(&self) -> StatusCode {
+    match self {
+      AppError::Auth(message) => message.status_code(),
+      AppError::User(message) => message.status_code(),
+      AppError::Words(message) => message.status_code(),
+      AppError::OtpCode(message) => message.status_code(),
+      AppError::Subscriber(message) => message.status_code(),
+      AppError::Common(message) => message.status_code(),
+      AppError::Dev(message) => message.status_code(),
+      AppError::Lockout(lockout) => lockout.message.status_code(),
+    }
+  }
+
+  fn error_response(&self) -> HttpResponse {
+    let mut res = api_error::<(), &AppError>(self.status_code(), self);
+
+    if let AppError::Lockout(lockout) = self {
+      res.headers_mut().insert(
+        header::RETRY_AFTER,
+        header::HeaderValue::from(lockout.retry_after.max(1)),
+      );
+    }
+
+    res
+  }
+}
+
+/// Name of the unique constraint behind `e`, when a duplicate is what went wrong.
+pub fn unique_violation(e: &sqlx::Error) -> Option<&str> {
+  match e {
+    sqlx::Error::Database(db) if db.is_unique_violation() => Some(db.constraint().unwrap_or("")),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::{body::to_bytes, http::header::HeaderMap};
+  use serde_json::Value;
+
+  use super::*;
+
+  async fn respond(error: AppError) -> (StatusCode, HeaderMap, Value) {
+    let res = error.error_response();
+    let (status, headers) = (res.status(), res.headers().clone());
+    let body = to_bytes(res.into_body()).await.unwrap();
+
+    (status, headers, serde_json::from_slice(&body).unwrap())
+  }
+
+  #[actix_web::test]
+  async fn not_found_keeps_the_envelope() {
+    let (status, _, body) = respond(UserMessage::UserNotFound.into()).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["message"], "UserNotFound");
+    assert_eq!(body["status"], "Error");
+    assert!(body["data"].is_null());
+  }
+
+  #[test]
+  fn statuses_follow_the_message() {
+    let cases: Vec<(AppError, StatusCode)> = vec![
+      (WordsMessage::WordNotFound.into(), StatusCode::NOT_FOUND),
+      (UserMessage::UserEmailTaken.into(), StatusCode::CONFLICT),
+      (
+        AuthMessage::AuthInvalidCredentials.into(),
+        StatusCode::UNAUTHORIZED,
+      ),
+      (AuthMessage::AuthForbidden.into(), StatusCode::FORBIDDEN),
+      (
+        UserMessage::UserCreateFailed.into(),
+        StatusCode::INTERNAL_SERVER_ERROR,
+      ),
+      (
+        OtpCodeMessage::OtpCodeResendCooldown.into(),
+        StatusCode::TOO_MANY_REQUESTS,
+      ),
+    ];
+
+    for (error, expected) in cases {
+      assert_eq!(error.status_code(), expected, "{error}");
+    }
+  }
+
+  #[actix_web::test]
+  async fn lockout_tells_when_to_retry() {
+    let lockout = Lockout {
+      message: AuthMessage::AuthAccountLocked,
+      retry_after: 90,
+    };
+    let (status, headers, body) = respond(lockout.into()).await;
+
+    assert_eq!(status, StatusCode::LOCKED);
+    assert_eq!(body["message"], "AuthAccountLocked");
+    assert_eq!(headers.get(header::RETRY_AFTER).unwrap(), "90");
+  }
+}
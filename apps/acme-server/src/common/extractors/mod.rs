@@ -0,0 +1,156 @@
+use std::{
+  collections::BTreeMap,
+  ops::{Deref, DerefMut},
+};
+
+use actix_web::{
+  dev::Payload,
+  error::{InternalError, JsonPayloadError},
+  http::StatusCode,
+  web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+use validator::{Validate, ValidationErrors};
+
+use crate::common::{
+  constants::CommonMessage,
+  functionalities::api_res::{api_error, detail, ApiResult, Status},
+  middlewares::RequestId,
+};
+
+/// JSON body that has passed its `validator` rules.
+///
+/// Rule failures answer 422 with the error codes of each field, e.g.
+/// `{"email": ["ValidateEmailShape"]}`, as the envelope's data.
+pub struct ValidatedJson<T>(pub T);
+
+impl<T> ValidatedJson<T> {
+  pub fn into_inner(self) -> T {
+    self.0
+  }
+}
+
+impl<T> Deref for ValidatedJson<T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    &self.0
+  }
+}
+
+impl<T> DerefMut for ValidatedJson<T> {
+  fn deref_mut(&mut self) -> &mut T {
+    &mut self.0
+  }
+}
+
+impl<T> FromRequest for ValidatedJson<T>
+where
+  T: DeserializeOwned + Validate + 'static,
+{
+  type Error = Error;
+  type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+  fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+    let json = web::Json::<T>::from_request(req, payload);
+
+    Box::pin(async move {
+      let value = json.await?.into_inner();
+
+      match value.validate() {
+        Ok(()) => Ok(ValidatedJson(value)),
+        Err(errors) => {
+          let response = validation_error(&errors);
+          Err(InternalError::from_response(errors, response).into())
+        },
+      }
+    })
+  }
+}
+
+/// Answers malformed or mistyped JSON bodies inside the usual envelope; set through
+/// `web::JsonConfig::error_handler`.
+pub fn json_error_handler(err: JsonPayloadError, req: &HttpRequest) -> Error {
+  match req.extensions().get::<RequestId>() {
+    Some(RequestId(request_id)) => println!("Request {request_id}: {:?}", err),
+    None => println!("{:?}", err),
+  }
+
+  let response =
+    api_error::<(), CommonMessage>(StatusCode::BAD_REQUEST, CommonMessage::JsonPayloadInvalid);
+  InternalError::from_response(err, response).into()
+}
+
+fn validation_error(errors: &ValidationErrors) -> HttpResponse {
+  let fields: BTreeMap<&str, Vec<String>> = errors
+    .field_errors()
+    .into_iter()
+    .map(|(field, errors)| {
+      let codes = errors
+        .iter()
+        .map(|e| e.message.as_deref().unwrap_or(&e.code).to_string())
+        .collect();
+      (field, codes)
+    })
+    .collect();
+
+  HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY).json(ApiResult {
+    data: Some(fields),
+    detail: detail(&CommonMessage::ValidationFailed),
+    message: CommonMessage::ValidationFailed,
+    status: Status::Error,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::{test, App};
+  use serde::Deserialize;
+  use serde_json::{json, Value};
+
+  use super::*;
+
+  #[derive(Deserialize, Validate)]
+  struct SignupDto {
+    #[validate(email(message = "ValidateEmailShape"))]
+    email: String,
+  }
+
+  async fn signup(body: ValidatedJson<SignupDto>) -> HttpResponse {
+    HttpResponse::Ok().body(body.into_inner().email)
+  }
+
+  async fn call(req: test::TestRequest) -> (StatusCode, Value) {
+    let app = test::init_service(
+      App::new()
+        .app_data(web::JsonConfig::default().error_handler(json_error_handler))
+        .route("/signup", web::post().to(signup)),
+    )
+    .await;
+
+    let res = test::call_service(&app, req.uri("/signup").to_request()).await;
+    (res.status(), test::read_body_json(res).await)
+  }
+
+  #[actix_web::test]
+  async fn rule_failures_answer_422_with_field_codes() {
+    let (status, body) = call(test::TestRequest::post().set_json(json!({ "email": "nope" }))).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["message"], "ValidationFailed");
+    assert_eq!(body["data"]["email"], json!(["ValidateEmailShape"]));
+  }
+
+  #[actix_web::test]
+  async fn malformed_json_gets_the_envelope() {
+    let req = test::TestRequest::post()
+      .insert_header(("content-type", "application/json"))
+      .set_payload("{\"email\":");
+    let (status, body) = call(req).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "JsonPayloadInvalid");
+    assert_eq!(body["status"], "Error");
+  }
+}
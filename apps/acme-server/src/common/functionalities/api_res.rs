@@ -1,13 +1,16 @@
 use actix_web::{http::StatusCode, HttpResponse};
 use serde::{Deserialize, Serialize};
 
+use crate::common::i18n::{Locale, Translate};
+
 pub fn api_error<T, M>(status: StatusCode, message: M) -> HttpResponse
 where
   T: Serialize,
-  M: Serialize,
+  M: Serialize + Translate,
 {
   HttpResponse::build(status).json(ApiResult::<T, M> {
     data: None,
+    detail: detail(&message),
     message,
     status: Status::Error,
   })
@@ -16,26 +19,31 @@ where
 pub fn api_success<T, M>(status: StatusCode, data: T, message: M) -> HttpResponse
 where
   T: Serialize,
-  M: Serialize,
+  M: Serialize + Translate,
 {
   HttpResponse::build(status).json(ApiResult::<T, M> {
     data: Some(data),
+    detail: detail(&message),
     message,
     status: Status::Ok,
   })
 }
 
+/// `message` in the language of the request being handled.
+pub fn detail<M: Translate>(message: &M) -> Option<String> {
+  message.translate(Locale::current()).map(str::to_string)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApiResult<T, E> {
   pub data: Option<T>,
+  /// The machine-readable `message`, for people.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub detail: Option<String>,
   pub message: E,
   pub status: Status,
 }
 
-pub enum ApiError {
-  Err(sqlx::Error),
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Status {
   Ok,
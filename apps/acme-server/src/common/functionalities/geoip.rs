@@ -0,0 +1,49 @@
+use std::{net::IpAddr, sync::OnceLock};
+
+use maxminddb::{geoip2, Reader};
+
+use crate::config::GeoIpConfig;
+
+static READER: OnceLock<Reader<Vec<u8>>> = OnceLock::new();
+
+/// Opens the City database at `geoip.db_path`; called once at startup. Lookups report nothing
+/// when it is missing.
+pub fn init(config: &GeoIpConfig) {
+  match Reader::open_readfile(&config.db_path) {
+    Ok(reader) => {
+      let _ = READER.set(reader);
+    },
+    Err(e) => println!("GeoIP database unavailable at {}: {:?}", config.db_path, e),
+  }
+}
+
+/// Approximate location of `ip` as "City, Region, Country", or `None` when unknown.
+pub fn locate(ip: &str) -> Option<String> {
+  let reader = READER.get()?;
+  let ip = ip.parse::<IpAddr>().ok()?;
+  let city = reader.lookup::<geoip2::City>(ip).ok()?;
+
+  let english = |names: Option<&std::collections::BTreeMap<&str, &str>>| {
+    names
+      .and_then(|names| names.get("en"))
+      .map(|name| name.to_string())
+  };
+
+  let parts: Vec<String> = [
+    city.city.as_ref().and_then(|c| english(c.names.as_ref())),
+    city
+      .subdivisions
+      .as_ref()
+      .and_then(|s| s.first())
+      .and_then(|s| english(s.names.as_ref())),
+    city
+      .country
+      .as_ref()
+      .and_then(|c| english(c.names.as_ref())),
+  ]
+  .into_iter()
+  .flatten()
+  .collect();
+
+  (!parts.is_empty()).then(|| parts.join(", "))
+}
@@ -1 +1,4 @@
 pub mod api_res;
+pub mod geoip;
+pub mod password;
+pub mod signing;
@@ -0,0 +1,141 @@
+use std::sync::OnceLock;
+
+use argon2::{
+  password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+  Algorithm, Argon2, Params, Version,
+};
+
+use crate::config::PasswordConfig;
+
+static PARAMS: OnceLock<Params> = OnceLock::new();
+
+/// Sets the Argon2id costs new hashes are made with; called once at startup with the password
+/// settings, which `Config::load` has already checked.
+pub fn init(config: &PasswordConfig) {
+  match config.argon2_params() {
+    Ok(params) => {
+      let _ = PARAMS.set(params);
+    },
+    Err(e) => println!("{:?}", e),
+  }
+}
+
+/// The configured costs; the argon2 defaults, which match the default configuration, until `init`.
+fn params() -> Params {
+  PARAMS.get().cloned().unwrap_or_default()
+}
+
+fn argon2() -> Argon2<'static> {
+  Argon2::new(Algorithm::Argon2id, Version::V0x13, params())
+}
+
+fn is_bcrypt(hash: &str) -> bool {
+  ["$2a$", "$2b$", "$2x$", "$2y$"]
+    .iter()
+    .any(|prefix| hash.starts_with(prefix))
+}
+
+/// Runs a hash or a check on the blocking pool; Argon2 is tuned to take tens of milliseconds,
+/// which would otherwise stall every request sharing the async worker.
+async fn blocking<T: Send + 'static>(
+  f: impl FnOnce() -> Result<T, anyhow::Error> + Send + 'static,
+) -> Result<T, anyhow::Error> {
+  tokio::task::spawn_blocking(f).await?
+}
+
+/// Hashes `password` with Argon2id under the current cost policy.
+pub async fn hash(password: &str) -> Result<String, anyhow::Error> {
+  let password = password.to_string();
+  blocking(move || hash_with(&argon2(), &password)).await
+}
+
+fn hash_with(argon2: &Argon2<'_>, password: &str) -> Result<String, anyhow::Error> {
+  let salt =
+    SaltString::encode_b64(&rand::random::<[u8; 16]>()).map_err(|e| anyhow::anyhow!("{e}"))?;
+
+  argon2
+    .hash_password(password.as_bytes(), &salt)
+    .map(|hash| hash.to_string())
+    .map_err(|e| anyhow::anyhow!("{e}"))
+}
+
+/// Checks `password` against a stored hash, picking the algorithm from the hash itself so legacy
+/// bcrypt hashes keep working.
+pub async fn verify(password: &str, hash: &str) -> Result<bool, anyhow::Error> {
+  let (password, hash) = (password.to_string(), hash.to_string());
+  blocking(move || check(&password, &hash)).await
+}
+
+fn check(password: &str, hash: &str) -> Result<bool, anyhow::Error> {
+  if is_bcrypt(hash) {
+    return Ok(bcrypt::verify(password, hash)?);
+  }
+
+  let parsed = PasswordHash::new(hash).map_err(|e| anyhow::anyhow!("{e}"))?;
+  match argon2().verify_password(password.as_bytes(), &parsed) {
+    Ok(()) => Ok(true),
+    Err(argon2::password_hash::Error::Password) => Ok(false),
+    Err(e) => Err(anyhow::anyhow!("{e}")),
+  }
+}
+
+/// Whether a stored hash falls short of the current policy: bcrypt, another Argon2 variant, or
+/// different Argon2id costs.
+pub fn needs_rehash(hash: &str) -> bool {
+  let Ok(parsed) = PasswordHash::new(hash) else {
+    return true;
+  };
+  if parsed.algorithm != Algorithm::Argon2id.ident()
+    || parsed.version != Some(Version::V0x13.into())
+  {
+    return true;
+  }
+
+  let current = params();
+  match Params::try_from(&parsed) {
+    Ok(params) => {
+      params.m_cost() != current.m_cost()
+        || params.t_cost() != current.t_cost()
+        || params.p_cost() != current.p_cost()
+    },
+    Err(_) => true,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn garbage_hashes_error_instead_of_panicking() {
+    assert!(check("hunter22", "not-a-hash").is_err());
+    assert!(needs_rehash("not-a-hash"));
+  }
+
+  #[test]
+  fn legacy_bcrypt_hashes_verify_and_need_rehash() {
+    let legacy = bcrypt::hash("hunter22", 4).unwrap();
+
+    assert!(check("hunter22", &legacy).unwrap());
+    assert!(!check("hunter23", &legacy).unwrap());
+    assert!(needs_rehash(&legacy));
+  }
+
+  #[test]
+  fn hashing_failures_error_instead_of_panicking() {
+    // Argon2 accepts a 4 byte output, the PHC string format doesn't
+    let params = Params::new(8, 1, 1, Some(4)).unwrap();
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+
+    assert!(hash_with(&argon2, "hunter22").is_err());
+  }
+
+  #[actix_web::test]
+  async fn current_hashes_are_kept() {
+    let current = hash("hunter22").await.unwrap();
+
+    assert!(verify("hunter22", &current).await.unwrap());
+    assert!(!verify("hunter23", &current).await.unwrap());
+    assert!(!needs_rehash(&current));
+  }
+}
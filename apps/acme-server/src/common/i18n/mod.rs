@@ -0,0 +1,159 @@
+/*
+ * Languages the API and the emails speak.
+ *
+ * The locale of the request being handled comes from `Accept-Language` (see the
+ * `negotiate_locale` middleware) and is what `api_success`/`api_error` translate their message
+ * into. Emails use the recipient's own `settings.locale` instead, falling back to the request's.
+ */
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// A supported language; anything else falls back to English.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+  #[default]
+  En,
+  Es,
+}
+
+tokio::task_local! {
+  static LOCALE: Locale;
+}
+
+impl Locale {
+  pub const ALL: [Locale; 2] = [Locale::En, Locale::Es];
+
+  /// BCP 47 tag, as sent in `Content-Language`.
+  pub fn tag(self) -> &'static str {
+    match self {
+      Locale::En => "en",
+      Locale::Es => "es",
+    }
+  }
+
+  /// Matches a language tag on its primary subtag, so `es-MX` is Spanish.
+  pub fn from_tag(tag: &str) -> Option<Locale> {
+    let primary = tag.trim().split(['-', '_']).next().unwrap_or_default();
+    Locale::ALL
+      .into_iter()
+      .find(|locale| locale.tag().eq_ignore_ascii_case(primary))
+  }
+
+  /// The supported language the client prefers most, from an `Accept-Language` value.
+  pub fn negotiate(accept_language: &str) -> Option<Locale> {
+    let mut best: Option<(Locale, f32)> = None;
+
+    for range in accept_language.split(',') {
+      let mut parts = range.split(';');
+      let tag = parts.next().unwrap_or_default();
+      let quality = parts
+        .find_map(|param| param.trim().strip_prefix("q="))
+        .and_then(|q| q.trim().parse::<f32>().ok())
+        .unwrap_or(1.0);
+
+      if let Some(locale) = Locale::from_tag(tag)
+        && quality > 0.0
+        && best.is_none_or(|(_, best)| quality > best)
+      {
+        best = Some((locale, quality));
+      }
+    }
+
+    best.map(|(locale, _)| locale)
+  }
+
+  /// Locale of the request being handled, English outside of one.
+  pub fn current() -> Locale {
+    LOCALE.try_with(|locale| *locale).unwrap_or_default()
+  }
+
+  /// Runs `f` with `self` as the current locale.
+  pub async fn scope<F: Future>(self, f: F) -> F::Output {
+    LOCALE.scope(self, f).await
+  }
+
+  /// `at` as a date and time for a reader in `timezone`.
+  pub fn format_datetime(self, at: DateTime<Utc>, timezone: Tz) -> String {
+    let at = at.with_timezone(&timezone);
+
+    match self {
+      Locale::En => at.format("%B %-d, %Y at %-I:%M %p %Z").to_string(),
+      Locale::Es => format!(
+        "{} de {} de {}, {:02}:{:02} {}",
+        at.day(),
+        MONTHS_ES[at.month0() as usize],
+        at.year(),
+        at.hour(),
+        at.minute(),
+        at.format("%Z")
+      ),
+    }
+  }
+}
+
+const MONTHS_ES: [&str; 12] = [
+  "enero",
+  "febrero",
+  "marzo",
+  "abril",
+  "mayo",
+  "junio",
+  "julio",
+  "agosto",
+  "septiembre",
+  "octubre",
+  "noviembre",
+  "diciembre",
+];
+
+/// A message code with human-readable text to send alongside it.
+pub trait Translate {
+  /// Text for `locale`; `None` when the code is all the client gets.
+  fn translate(&self, _locale: Locale) -> Option<&'static str> {
+    None
+  }
+}
+
+impl<T: Translate + ?Sized> Translate for &T {
+  fn translate(&self, locale: Locale) -> Option<&'static str> {
+    (**self).translate(locale)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::TimeZone;
+
+  use super::*;
+
+  #[test]
+  fn negotiates_by_quality_and_primary_subtag() {
+    assert_eq!(
+      Locale::negotiate("es-MX,es;q=0.9,en;q=0.8"),
+      Some(Locale::Es)
+    );
+    assert_eq!(
+      Locale::negotiate("fr-FR, en;q=0.5, es;q=0.7"),
+      Some(Locale::Es)
+    );
+    assert_eq!(Locale::negotiate("de, es;q=0"), None);
+    assert_eq!(Locale::negotiate(""), None);
+  }
+
+  #[test]
+  fn formats_in_the_reader_timezone() {
+    let at = Utc.with_ymd_and_hms(2026, 1, 5, 23, 30, 0).unwrap();
+
+    assert_eq!(
+      Locale::En.format_datetime(at, chrono_tz::America::New_York),
+      "January 5, 2026 at 6:30 PM EST"
+    );
+    assert_eq!(
+      Locale::Es.format_datetime(at, chrono_tz::Europe::Madrid),
+      "6 de enero de 2026, 00:30 CET"
+    );
+  }
+}
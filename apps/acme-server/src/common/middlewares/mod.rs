@@ -0,0 +1,205 @@
+use std::panic::AssertUnwindSafe;
+
+use actix_web::{
+  body::MessageBody,
+  dev::{ServiceRequest, ServiceResponse},
+  error::InternalError,
+  http::{
+    header::{self, HeaderName, HeaderValue},
+    StatusCode,
+  },
+  middleware::Next,
+  Error, HttpMessage,
+};
+use futures_util::FutureExt;
+use uuid::Uuid;
+
+use crate::common::{constants::CommonMessage, functionalities::api_res::api_error, i18n::Locale};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Id of the request being handled, taken from `X-Request-Id` or generated, and echoed back on
+/// the response.
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Outermost middleware: tags the request with a `RequestId` and turns a panicking handler into
+/// a logged 500 `ApiResult` instead of a dropped connection.
+pub async fn catch_panic(
+  req: ServiceRequest,
+  next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+  let request_id = req
+    .headers()
+    .get(&REQUEST_ID_HEADER)
+    .and_then(|value| value.to_str().ok())
+    .filter(|value| !value.is_empty() && value.len() <= 128)
+    .map(str::to_string)
+    .unwrap_or_else(|| Uuid::new_v4().to_string());
+  req.extensions_mut().insert(RequestId(request_id.clone()));
+
+  let method = req.method().clone();
+  let path = req.path().to_string();
+  let header = HeaderValue::from_str(&request_id).ok();
+
+  match AssertUnwindSafe(next.call(req)).catch_unwind().await {
+    Ok(res) => {
+      let mut res = res?;
+      if let Some(header) = header {
+        res.headers_mut().insert(REQUEST_ID_HEADER, header);
+      }
+      Ok(res)
+    },
+    Err(panic) => {
+      let reason = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic");
+      eprintln!("Request {request_id} panicked on {method} {path}: {reason}");
+
+      // The request went down with the handler, so the response travels as an error
+      let mut res = api_error::<(), CommonMessage>(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        CommonMessage::InternalServerError,
+      );
+      if let Some(header) = header {
+        res.headers_mut().insert(REQUEST_ID_HEADER, header);
+      }
+      Err(InternalError::from_response(CommonMessage::InternalServerError, res).into())
+    },
+  }
+}
+
+/// Picks the response language from `Accept-Language` and makes it the current `Locale` while the
+/// request is handled, echoing it back in `Content-Language`.
+pub async fn negotiate_locale(
+  req: ServiceRequest,
+  next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+  let locale = req
+    .headers()
+    .get(header::ACCEPT_LANGUAGE)
+    .and_then(|value| value.to_str().ok())
+    .and_then(Locale::negotiate)
+    .unwrap_or_default();
+
+  let mut res = locale.scope(next.call(req)).await?;
+  res.headers_mut().insert(
+    header::CONTENT_LANGUAGE,
+    HeaderValue::from_static(locale.tag()),
+  );
+  Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::{
+    body::to_bytes,
+    dev::{Service, ServiceResponse},
+    middleware::from_fn,
+    test, web, App, HttpResponse,
+  };
+  use serde_json::Value;
+
+  use super::*;
+
+  /// What the server writes back when the app errors out, as it does for a panic.
+  async fn panicked<S, R, B>(app: &S, req: R) -> HttpResponse
+  where
+    S: Service<R, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody + 'static,
+  {
+    match app.call(req).await {
+      Ok(res) => res.into_parts().1.map_into_boxed_body(),
+      Err(e) => e.error_response(),
+    }
+  }
+
+  async fn boom() -> HttpResponse {
+    panic!("boom")
+  }
+
+  async fn fine() -> HttpResponse {
+    HttpResponse::Ok().finish()
+  }
+
+  #[actix_web::test]
+  async fn panicking_handler_answers_500_envelope() {
+    let app = test::init_service(
+      App::new()
+        .wrap(from_fn(catch_panic))
+        .route("/boom", web::get().to(boom)),
+    )
+    .await;
+
+    let res = panicked(&app, test::TestRequest::get().uri("/boom").to_request()).await;
+
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(res.headers().contains_key(&REQUEST_ID_HEADER));
+    let body = to_bytes(res.into_body()).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["message"], "InternalServerError");
+    assert_eq!(body["status"], "Error");
+    assert!(body["data"].is_null());
+  }
+
+  #[actix_web::test]
+  async fn worker_keeps_serving_after_a_panic() {
+    let app = test::init_service(
+      App::new()
+        .wrap(from_fn(catch_panic))
+        .route("/boom", web::get().to(boom))
+        .route("/fine", web::get().to(fine)),
+    )
+    .await;
+
+    panicked(&app, test::TestRequest::get().uri("/boom").to_request()).await;
+    let res = test::call_service(&app, test::TestRequest::get().uri("/fine").to_request()).await;
+
+    assert_eq!(res.status(), StatusCode::OK);
+  }
+
+  #[actix_web::test]
+  async fn incoming_request_id_is_echoed() {
+    let app = test::init_service(
+      App::new()
+        .wrap(from_fn(catch_panic))
+        .route("/boom", web::get().to(boom)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+      .uri("/boom")
+      .insert_header((REQUEST_ID_HEADER, "req-42"))
+      .to_request();
+    let res = panicked(&app, req).await;
+
+    assert_eq!(res.headers().get(&REQUEST_ID_HEADER).unwrap(), "req-42");
+  }
+
+  #[actix_web::test]
+  async fn messages_are_translated_for_accept_language() {
+    async fn missing() -> Result<HttpResponse, crate::common::error::AppError> {
+      Err(crate::user::constants::UserMessage::UserNotFound.into())
+    }
+
+    let app = test::init_service(
+      App::new()
+        .wrap(from_fn(negotiate_locale))
+        .route("/missing", web::get().to(missing)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+      .uri("/missing")
+      .insert_header((header::ACCEPT_LANGUAGE, "es-ES,en;q=0.5"))
+      .to_request();
+    let res = test::call_service(&app, req).await;
+
+    assert_eq!(res.headers().get(header::CONTENT_LANGUAGE).unwrap(), "es");
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["message"], "UserNotFound");
+    assert_eq!(body["detail"], "No hemos encontrado ese usuario.");
+  }
+}
@@ -1,3 +1,10 @@
+pub mod constants;
+pub mod error;
+pub mod extractors;
 pub mod functionalities;
 pub mod generators;
+pub mod i18n;
+pub mod middlewares;
+#[cfg(test)]
+pub mod testing;
 pub mod validators;
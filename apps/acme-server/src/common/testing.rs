@@ -0,0 +1,54 @@
+use std::{env, net::SocketAddr, sync::Arc};
+
+use ::sqlx::postgres::PgPoolOptions;
+use actix_web::web;
+use uuid::Uuid;
+
+use crate::{
+  common::functionalities::signing,
+  config::{Config, MailTransport},
+  email::mailer::MemoryMailer,
+  redis, AppState,
+};
+
+/// Application state for tests that go through the handlers, with the mailer it sends through
+/// so the test can look at what was delivered.
+pub struct TestState {
+  pub data: web::Data<AppState>,
+  pub mailer: Arc<MemoryMailer>,
+}
+
+/// State backed by a migrated Postgres at `TEST_DATABASE_URL` and the Redis at `TEST_REDIS_URL`
+/// (the configured defaults otherwise).
+pub async fn state() -> TestState {
+  let mut config = Config::default();
+  config.database.url =
+    env::var("TEST_DATABASE_URL").unwrap_or("postgres://postgres@localhost/acme_test".to_string());
+  if let Ok(url) = env::var("TEST_REDIS_URL") {
+    config.redis.url = url;
+  }
+  config.mail.transport = MailTransport::Memory;
+  signing::init(&config.session.secret);
+
+  let mailer = Arc::new(MemoryMailer::default());
+  let db = PgPoolOptions::new()
+    .connect_lazy(&config.database.url)
+    .expect("Invalid TEST_DATABASE_URL");
+  let redis = redis::connect_redis_manager(&config.redis).await;
+
+  let data = web::Data::new(AppState {
+    db,
+    redis,
+    mailer: mailer.clone(),
+    config,
+  });
+
+  TestState { data, mailer }
+}
+
+/// A client address of its own, so per-IP throttling left over from earlier runs doesn't leak
+/// into the test.
+pub fn peer_addr() -> SocketAddr {
+  let [a, b, c, ..] = Uuid::new_v4().into_bytes();
+  SocketAddr::from(([10, a, b, c], 40000))
+}
@@ -1,9 +1,19 @@
-use uuid::Uuid;
+use chrono_tz::Tz;
 use validator::ValidationError;
 
-pub fn validate_uuid(id: &str) -> Result<(), ValidationError> {
-  match Uuid::parse_str(id) {
+use crate::common::i18n::Locale;
+
+pub fn validate_locale(tag: &str) -> Result<(), ValidationError> {
+  match Locale::from_tag(tag) {
+    Some(_) => Ok(()),
+    None => Err(ValidationError::new("ValidateLocaleUnsupported")),
+  }
+}
+
+/// An IANA zone name like `Europe/Madrid`.
+pub fn validate_timezone(name: &str) -> Result<(), ValidationError> {
+  match name.parse::<Tz>() {
     Ok(_) => Ok(()),
-    Err(_) => Err(ValidationError::new("ValidateInvalidUuid")),
+    Err(_) => Err(ValidationError::new("ValidateTimezoneUnknown")),
   }
 }
@@ -0,0 +1,892 @@
+/*
+ * Application configuration.
+ *
+ * Settings are layered, later layers winning:
+ *   1. built-in defaults, good for local development;
+ *   2. the TOML file at `CONFIG_FILE` (default `config.toml`, optional) — its top-level tables,
+ *      then the `[profiles.<APP_ENV>]` tables on top when `APP_ENV` is set;
+ *   3. environment variables (`PORT`, `DATABASE_URL`, `MAIL_HOST`, ...).
+ *
+ * The result is checked once at startup, so a bad setting stops the server with a list of
+ * everything that is wrong instead of a panic on the first request that needs it.
+ */
+
+use std::{env, fmt, fs, io, str::FromStr};
+
+use base64::Engine;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+  /// Profile name, from `APP_ENV`.
+  #[serde(skip)]
+  pub env: String,
+  /// Whether `APP_ENV` was set, rather than `env` falling back to `development`.
+  #[serde(skip)]
+  pub env_is_explicit: bool,
+  pub server: ServerConfig,
+  pub database: DatabaseConfig,
+  pub redis: RedisConfig,
+  pub session: SessionConfig,
+  pub mail: MailConfig,
+  pub links: LinksConfig,
+  pub password: PasswordConfig,
+  pub oauth: OAuthConfig,
+  pub geoip: GeoIpConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+  pub port: u16,
+  /// Origins allowed to call the API with credentials.
+  pub cors_origins: Vec<String>,
+  /// Mounts `/v1/dev`, tools for local development like the email previews. Only allowed when
+  /// `APP_ENV` is explicitly `development` or `test`.
+  pub dev_routes: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+  pub url: String,
+  pub max_connections: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedisConfig {
+  pub url: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+  /// Base64, at least 64 bytes once decoded; also keys the HMAC signatures.
+  pub secret: String,
+  pub cookie_name: String,
+  pub cookie_domain: Option<String>,
+  pub cookie_secure: bool,
+  pub ttl_days: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MailConfig {
+  pub transport: MailTransport,
+  pub host: String,
+  pub port: u16,
+  pub username: String,
+  pub password: String,
+  pub tls: MailTls,
+  /// Where the `file` transport writes its `.eml` files.
+  pub drop_dir: String,
+  /// Sender mailbox, e.g. `Acme App <admin@example.com>`; each email puts its own name in front
+  /// of the address.
+  pub from: String,
+  /// Where replies to emails that accept them go.
+  pub support: String,
+}
+
+/// Which `Mailer` delivers outgoing email.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MailTransport {
+  Smtp,
+  /// Writes `.eml` files to `mail.drop_dir`, for local development.
+  File,
+  /// Keeps messages in memory, for tests.
+  Memory,
+}
+
+/// How the SMTP connection is secured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MailTls {
+  /// Plain text, for local catchers like Mailpit/Mailhog.
+  None,
+  /// Upgrade with STARTTLS, usually on port 587.
+  Starttls,
+  /// TLS from the first byte, usually on port 465.
+  Implicit,
+}
+
+impl FromStr for MailTransport {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value {
+      "smtp" => Ok(MailTransport::Smtp),
+      "file" => Ok(MailTransport::File),
+      "memory" => Ok(MailTransport::Memory),
+      _ => Err("expected one of `smtp`, `file`, `memory`".to_string()),
+    }
+  }
+}
+
+impl FromStr for MailTls {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<Self, Self::Err> {
+    match value {
+      "none" => Ok(MailTls::None),
+      "starttls" => Ok(MailTls::Starttls),
+      "implicit" => Ok(MailTls::Implicit),
+      _ => Err("expected one of `none`, `starttls`, `implicit`".to_string()),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinksConfig {
+  /// Base URL of the web app the links in emails point to.
+  pub frontend_url: String,
+  /// Public base URL of this API, for links that mail clients call directly, like one-click
+  /// unsubscribe.
+  pub api_url: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordConfig {
+  pub min_length: usize,
+  pub max_length: usize,
+  /// Lowest zxcvbn score accepted, 0 to 4.
+  pub min_score: u8,
+  /// How many previous passwords can't be reused; 0 turns the check off.
+  pub history_size: i64,
+  /// Have I Been Pwned range files, one `{SHA-1 prefix}.txt` per 5 hex prefix holding
+  /// `{suffix}:{count}` lines; the check is skipped when the directory is missing.
+  pub breached_dir: String,
+  /// Argon2id costs for new hashes; stored hashes with other costs are upgraded on sign-in.
+  pub argon2_memory_kib: u32,
+  pub argon2_iterations: u32,
+  pub argon2_parallelism: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OAuthConfig {
+  /// Base of the callback URLs registered with the providers; the provider name is appended.
+  pub redirect_url: String,
+  pub github: OAuthClientConfig,
+  pub google: OAuthClientConfig,
+  /// Any OpenID Connect issuer.
+  pub oidc: OAuthClientConfig,
+}
+
+/// A provider's client; the provider is offered once both id and secret are set.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OAuthClientConfig {
+  pub client_id: String,
+  pub client_secret: String,
+  /// OpenID Connect issuer; Google has a default, GitHub doesn't use one.
+  pub issuer: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeoIpConfig {
+  /// The offline GeoLite2/GeoIP2 City database; locations are left out when it is missing.
+  pub db_path: String,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Config {
+      env: "development".to_string(),
+      env_is_explicit: false,
+      server: ServerConfig::default(),
+      database: DatabaseConfig::default(),
+      redis: RedisConfig::default(),
+      session: SessionConfig::default(),
+      mail: MailConfig::default(),
+      links: LinksConfig::default(),
+      password: PasswordConfig::default(),
+      oauth: OAuthConfig::default(),
+      geoip: GeoIpConfig::default(),
+    }
+  }
+}
+
+impl Default for ServerConfig {
+  fn default() -> Self {
+    ServerConfig {
+      port: 8080,
+      cors_origins: vec![
+        "http://localhost:3001".to_string(),
+        "http://localhost:3000".to_string(),
+      ],
+      dev_routes: false,
+    }
+  }
+}
+
+impl Default for DatabaseConfig {
+  fn default() -> Self {
+    DatabaseConfig {
+      url: String::new(),
+      max_connections: 5,
+    }
+  }
+}
+
+impl Default for RedisConfig {
+  fn default() -> Self {
+    RedisConfig {
+      url: "redis://localhost:6380".to_string(),
+    }
+  }
+}
+
+impl Default for SessionConfig {
+  fn default() -> Self {
+    SessionConfig {
+      secret: String::new(),
+      cookie_name: "acme-session".to_string(),
+      cookie_domain: Some("localhost".to_string()),
+      // Required for localhost without HTTPS; turn on in deployed profiles
+      cookie_secure: false,
+      ttl_days: 7,
+    }
+  }
+}
+
+impl Default for MailConfig {
+  fn default() -> Self {
+    MailConfig {
+      transport: MailTransport::Smtp,
+      host: String::new(),
+      port: 1025,
+      username: String::new(),
+      password: String::new(),
+      tls: MailTls::None,
+      drop_dir: "tmp/emails".to_string(),
+      from: "Acme App <admin@example.com>".to_string(),
+      support: "Acme Support <support@example.com>".to_string(),
+    }
+  }
+}
+
+impl Default for LinksConfig {
+  fn default() -> Self {
+    LinksConfig {
+      frontend_url: "http://localhost:3000".to_string(),
+      api_url: "http://localhost:8080".to_string(),
+    }
+  }
+}
+
+impl Default for PasswordConfig {
+  fn default() -> Self {
+    PasswordConfig {
+      min_length: 8,
+      max_length: 128,
+      min_score: 3,
+      history_size: 5,
+      breached_dir: "data/pwned-passwords".to_string(),
+      // OWASP minimums
+      argon2_memory_kib: 19 * 1024,
+      argon2_iterations: 2,
+      argon2_parallelism: 1,
+    }
+  }
+}
+
+impl Default for OAuthConfig {
+  fn default() -> Self {
+    OAuthConfig {
+      redirect_url: "http://localhost:3000/auth/callback".to_string(),
+      github: OAuthClientConfig::default(),
+      google: OAuthClientConfig::default(),
+      oidc: OAuthClientConfig::default(),
+    }
+  }
+}
+
+impl Default for GeoIpConfig {
+  fn default() -> Self {
+    GeoIpConfig {
+      db_path: "data/GeoLite2-City.mmdb".to_string(),
+    }
+  }
+}
+
+impl PasswordConfig {
+  /// Argon2 parameters for the configured costs.
+  pub fn argon2_params(&self) -> Result<argon2::Params, argon2::Error> {
+    argon2::Params::new(
+      self.argon2_memory_kib,
+      self.argon2_iterations,
+      self.argon2_parallelism,
+      None,
+    )
+  }
+}
+
+impl OAuthClientConfig {
+  pub fn is_configured(&self) -> bool {
+    !self.client_id.is_empty() && !self.client_secret.is_empty()
+  }
+}
+
+impl LinksConfig {
+  /// Absolute frontend URL for `path` (which starts with `/`).
+  pub fn url(&self, path: &str) -> String {
+    format!("{}{path}", self.frontend_url.trim_end_matches('/'))
+  }
+
+  /// Absolute API URL for `path` (which starts with `/`).
+  pub fn api(&self, path: &str) -> String {
+    format!("{}{path}", self.api_url.trim_end_matches('/'))
+  }
+}
+
+/// Everything wrong with the configuration, one line each.
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "Invalid configuration:")?;
+    for problem in &self.0 {
+      writeln!(f, "  - {problem}")?;
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+  /// Loads defaults, the TOML file and the environment, then checks the result.
+  pub fn load() -> Result<Config, ConfigError> {
+    Self::load_from(&|name| env::var(name).ok())
+  }
+
+  /// `load` with the environment variables looked up through `vars`.
+  fn load_from(vars: Vars) -> Result<Config, ConfigError> {
+    let explicit_env = vars("APP_ENV");
+    let env_name = explicit_env.clone().unwrap_or("development".to_string());
+    let mut problems = Vec::new();
+
+    let mut config = match Self::from_file(vars("CONFIG_FILE"), explicit_env.as_deref()) {
+      Ok(config) => config,
+      Err(problem) => return Err(ConfigError(vec![problem])),
+    };
+    config.env = env_name;
+    config.env_is_explicit = explicit_env.is_some();
+    config.apply_env(vars, &mut problems);
+    config.check(&mut problems);
+
+    if problems.is_empty() {
+      Ok(config)
+    } else {
+      Err(ConfigError(problems))
+    }
+  }
+
+  /// Reads `explicit` (`CONFIG_FILE`), or `config.toml` when it is there.
+  fn from_file(explicit: Option<String>, profile: Option<&str>) -> Result<Config, String> {
+    let path = explicit.clone().unwrap_or("config.toml".to_string());
+
+    let contents = match fs::read_to_string(&path) {
+      Ok(contents) => contents,
+      // Only a file that was asked for has to exist
+      Err(e) if e.kind() == io::ErrorKind::NotFound && explicit.is_none() => {
+        return Ok(Config::default());
+      },
+      Err(e) => return Err(format!("{path}: {e}")),
+    };
+
+    Self::from_toml(&path, &contents, profile)
+  }
+
+  /// Parses the TOML `contents` read from `path`, with the `profile` tables laid over it.
+  ///
+  /// Without an explicit `APP_ENV` no profile applies, so nothing meant for development (like
+  /// `dev_routes`) is picked up by a server that was started without saying where it runs.
+  fn from_toml(path: &str, contents: &str, profile: Option<&str>) -> Result<Config, String> {
+    let mut table: toml::Table = contents.parse().map_err(|e| format!("{path}: {e}"))?;
+    let mut profiles = match table.remove("profiles") {
+      Some(toml::Value::Table(profiles)) => profiles,
+      Some(_) => return Err(format!("{path}: `profiles` must be a table")),
+      None => toml::Table::new(),
+    };
+    if let Some(profile) = profile {
+      match profiles.remove(profile) {
+        Some(toml::Value::Table(overlay)) => merge(&mut table, overlay),
+        Some(_) => return Err(format!("{path}: `profiles.{profile}` must be a table")),
+        None => {},
+      }
+    }
+
+    toml::Value::Table(table)
+      .try_into()
+      .map_err(|e| format!("{path}: {e}"))
+  }
+
+  fn apply_env(&mut self, vars: Vars, problems: &mut Vec<String>) {
+    override_with(vars, problems, "PORT", &mut self.server.port);
+    if let Some(origins) = vars("CORS_ORIGINS") {
+      self.server.cors_origins = origins
+        .split(',')
+        .map(|origin| origin.trim().to_string())
+        .filter(|origin| !origin.is_empty())
+        .collect();
+    }
+    override_with(vars, problems, "DEV_ROUTES", &mut self.server.dev_routes);
+
+    override_with(vars, problems, "DATABASE_URL", &mut self.database.url);
+    override_with(
+      vars,
+      problems,
+      "DATABASE_MAX_CONNECTIONS",
+      &mut self.database.max_connections,
+    );
+    override_with(vars, problems, "REDIS_URL", &mut self.redis.url);
+
+    override_with(vars, problems, "SESSION_SECRET", &mut self.session.secret);
+    if let Some(domain) = vars("SESSION_COOKIE_DOMAIN") {
+      self.session.cookie_domain = Some(domain).filter(|domain| !domain.is_empty());
+    }
+    override_with(
+      vars,
+      problems,
+      "SESSION_COOKIE_SECURE",
+      &mut self.session.cookie_secure,
+    );
+    override_with(
+      vars,
+      problems,
+      "SESSION_TTL_DAYS",
+      &mut self.session.ttl_days,
+    );
+
+    override_with(vars, problems, "MAIL_TRANSPORT", &mut self.mail.transport);
+    override_with(vars, problems, "MAIL_HOST", &mut self.mail.host);
+    override_with(vars, problems, "MAIL_SMTP_PORT", &mut self.mail.port);
+    override_with(vars, problems, "MAIL_USERNAME", &mut self.mail.username);
+    override_with(vars, problems, "MAIL_PASSWORD", &mut self.mail.password);
+    override_with(vars, problems, "MAIL_TLS", &mut self.mail.tls);
+    override_with(vars, problems, "MAIL_DROP_DIR", &mut self.mail.drop_dir);
+    override_with(vars, problems, "MAIL_FROM", &mut self.mail.from);
+    override_with(vars, problems, "MAIL_SUPPORT", &mut self.mail.support);
+
+    override_with(vars, problems, "FRONTEND_URL", &mut self.links.frontend_url);
+    override_with(vars, problems, "API_URL", &mut self.links.api_url);
+
+    override_with(
+      vars,
+      problems,
+      "PASSWORD_MIN_LENGTH",
+      &mut self.password.min_length,
+    );
+    override_with(
+      vars,
+      problems,
+      "PASSWORD_MAX_LENGTH",
+      &mut self.password.max_length,
+    );
+    override_with(
+      vars,
+      problems,
+      "PASSWORD_MIN_SCORE",
+      &mut self.password.min_score,
+    );
+    override_with(
+      vars,
+      problems,
+      "PASSWORD_HISTORY_SIZE",
+      &mut self.password.history_size,
+    );
+    override_with(
+      vars,
+      problems,
+      "PASSWORD_BREACHED_DIR",
+      &mut self.password.breached_dir,
+    );
+    override_with(
+      vars,
+      problems,
+      "PASSWORD_ARGON2_MEMORY_KIB",
+      &mut self.password.argon2_memory_kib,
+    );
+    override_with(
+      vars,
+      problems,
+      "PASSWORD_ARGON2_ITERATIONS",
+      &mut self.password.argon2_iterations,
+    );
+    override_with(
+      vars,
+      problems,
+      "PASSWORD_ARGON2_PARALLELISM",
+      &mut self.password.argon2_parallelism,
+    );
+
+    override_with(
+      vars,
+      problems,
+      "OAUTH_REDIRECT_URL",
+      &mut self.oauth.redirect_url,
+    );
+    for (name, client) in [
+      ("GITHUB", &mut self.oauth.github),
+      ("GOOGLE", &mut self.oauth.google),
+      ("OIDC", &mut self.oauth.oidc),
+    ] {
+      override_with(
+        vars,
+        problems,
+        &format!("OAUTH_{name}_CLIENT_ID"),
+        &mut client.client_id,
+      );
+      override_with(
+        vars,
+        problems,
+        &format!("OAUTH_{name}_CLIENT_SECRET"),
+        &mut client.client_secret,
+      );
+      if let Some(issuer) = vars(&format!("OAUTH_{name}_ISSUER")) {
+        client.issuer = Some(issuer).filter(|issuer| !issuer.is_empty());
+      }
+    }
+
+    override_with(vars, problems, "GEOIP_DB_PATH", &mut self.geoip.db_path);
+  }
+
+  fn check(&self, problems: &mut Vec<String>) {
+    if self.database.url.is_empty() {
+      problems.push("database.url (DATABASE_URL) must be set".to_string());
+    } else if !has_scheme(&self.database.url, &["postgres", "postgresql"]) {
+      problems.push("database.url (DATABASE_URL) must be a postgres:// URL".to_string());
+    }
+    if self.database.max_connections == 0 {
+      problems.push("database.max_connections must be at least 1".to_string());
+    }
+
+    if !has_scheme(&self.redis.url, &["redis", "rediss"]) {
+      problems.push("redis.url (REDIS_URL) must be a redis:// or rediss:// URL".to_string());
+    }
+
+    if self.session.secret.is_empty() {
+      problems.push("session.secret (SESSION_SECRET) must be set".to_string());
+    } else {
+      match base64::engine::general_purpose::STANDARD.decode(&self.session.secret) {
+        Ok(key) if key.len() >= 64 => {},
+        Ok(key) => problems.push(format!(
+          "session.secret (SESSION_SECRET) must decode to at least 64 bytes, got {}",
+          key.len()
+        )),
+        Err(e) => problems.push(format!(
+          "session.secret (SESSION_SECRET) is not valid base64: {e}"
+        )),
+      }
+    }
+    if self.session.cookie_name.is_empty() {
+      problems.push("session.cookie_name must not be empty".to_string());
+    }
+    if self.session.ttl_days <= 0 {
+      problems.push("session.ttl_days (SESSION_TTL_DAYS) must be positive".to_string());
+    }
+
+    match self.mail.transport {
+      MailTransport::Smtp if self.mail.host.is_empty() => {
+        problems.push("mail.host (MAIL_HOST) must be set for the smtp transport".to_string());
+      },
+      MailTransport::File if self.mail.drop_dir.is_empty() => {
+        problems
+          .push("mail.drop_dir (MAIL_DROP_DIR) must be set for the file transport".to_string());
+      },
+      _ => {},
+    }
+    if let Err(e) = self.mail.from.parse::<lettre::message::Mailbox>() {
+      problems.push(format!("mail.from (MAIL_FROM) is not a valid mailbox: {e}"));
+    }
+    if let Err(e) = self.mail.support.parse::<lettre::message::Mailbox>() {
+      problems.push(format!(
+        "mail.support (MAIL_SUPPORT) is not a valid mailbox: {e}"
+      ));
+    }
+
+    for origin in &self.server.cors_origins {
+      if !has_scheme(origin, &["http", "https"]) {
+        problems.push(format!(
+          "server.cors_origins (CORS_ORIGINS): `{origin}` is not an http(s) origin"
+        ));
+      }
+    }
+    // A deployment that forgot APP_ENV lands on the development profile, so that alone isn't
+    // enough to expose the dev tools
+    if self.server.dev_routes
+      && !(self.env_is_explicit && matches!(self.env.as_str(), "development" | "test"))
+    {
+      problems.push(
+        "server.dev_routes (DEV_ROUTES) is only allowed with APP_ENV set to `development` or `test`"
+          .to_string(),
+      );
+    }
+    if !has_scheme(&self.links.frontend_url, &["http", "https"]) {
+      problems.push("links.frontend_url (FRONTEND_URL) must be an http(s) URL".to_string());
+    }
+    if !has_scheme(&self.links.api_url, &["http", "https"]) {
+      problems.push("links.api_url (API_URL) must be an http(s) URL".to_string());
+    }
+
+    if self.password.min_length == 0 {
+      problems.push("password.min_length (PASSWORD_MIN_LENGTH) must be at least 1".to_string());
+    }
+    if self.password.max_length < self.password.min_length {
+      problems.push(
+        "password.max_length (PASSWORD_MAX_LENGTH) must not be below password.min_length"
+          .to_string(),
+      );
+    }
+    if self.password.min_score > 4 {
+      problems.push("password.min_score (PASSWORD_MIN_SCORE) must be between 0 and 4".to_string());
+    }
+    if self.password.history_size < 0 {
+      problems
+        .push("password.history_size (PASSWORD_HISTORY_SIZE) must not be negative".to_string());
+    }
+    if let Err(e) = self.password.argon2_params() {
+      problems.push(format!(
+        "password.argon2_* (PASSWORD_ARGON2_*) are not valid Argon2 costs: {e}"
+      ));
+    }
+
+    if !has_scheme(&self.oauth.redirect_url, &["http", "https"]) {
+      problems.push("oauth.redirect_url (OAUTH_REDIRECT_URL) must be an http(s) URL".to_string());
+    }
+    for (name, client) in [
+      ("github", &self.oauth.github),
+      ("google", &self.oauth.google),
+      ("oidc", &self.oauth.oidc),
+    ] {
+      let env_name = name.to_uppercase();
+      if client.client_id.is_empty() != client.client_secret.is_empty() {
+        problems.push(format!(
+          "oauth.{name}.client_id and client_secret (OAUTH_{env_name}_CLIENT_ID, \
+           OAUTH_{env_name}_CLIENT_SECRET) must be set together"
+        ));
+      }
+      if let Some(issuer) = &client.issuer
+        && !has_scheme(issuer, &["https", "http"])
+      {
+        problems.push(format!(
+          "oauth.{name}.issuer (OAUTH_{env_name}_ISSUER) must be an http(s) URL"
+        ));
+      }
+    }
+    if self.oauth.oidc.is_configured() && self.oauth.oidc.issuer.is_none() {
+      problems
+        .push("oauth.oidc.issuer (OAUTH_OIDC_ISSUER) must be set for the oidc client".to_string());
+    }
+  }
+}
+
+/// Looks up an environment variable; the process environment outside of tests.
+type Vars<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+/// Replaces `target` with the parsed value of `name` when it is set.
+fn override_with<T: std::str::FromStr>(
+  vars: Vars,
+  problems: &mut Vec<String>,
+  name: &str,
+  target: &mut T,
+) where
+  T::Err: fmt::Display,
+{
+  if let Some(value) = vars(name) {
+    match value.parse() {
+      Ok(value) => *target = value,
+      Err(e) => problems.push(format!("{name}: `{value}` is invalid: {e}")),
+    }
+  }
+}
+
+fn has_scheme(url: &str, schemes: &[&str]) -> bool {
+  url
+    .split_once("://")
+    .is_some_and(|(scheme, rest)| schemes.contains(&scheme) && !rest.is_empty())
+}
+
+/// Lays `overlay` over `base`, recursing into tables so a profile only lists what it changes.
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+  for (key, value) in overlay {
+    match (base.get_mut(&key), value) {
+      (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+      (_, value) => {
+        base.insert(key, value);
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn problems(config: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+    config.check(&mut problems);
+    problems
+  }
+
+  /// An environment holding only `pairs`.
+  fn vars<'a>(pairs: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+    |name| {
+      pairs
+        .iter()
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+    }
+  }
+
+  const FILE: &str = r#"
+    [server]
+    port = 4000
+    cors_origins = ["https://app.example.com"]
+
+    [mail]
+    from = "Acme <hello@example.com>"
+
+    [profiles.staging.server]
+    port = 4100
+  "#;
+
+  #[test]
+  fn profile_overrides_only_the_keys_it_lists() {
+    let config = Config::from_toml("config.toml", FILE, Some("staging")).unwrap();
+
+    assert_eq!(config.server.port, 4100);
+    assert_eq!(config.server.cors_origins, ["https://app.example.com"]);
+    assert_eq!(config.mail.from, "Acme <hello@example.com>");
+
+    let config = Config::from_toml("config.toml", FILE, Some("production")).unwrap();
+    assert_eq!(config.server.port, 4000);
+  }
+
+  #[test]
+  fn no_profile_applies_without_app_env() {
+    let secret = base64::engine::general_purpose::STANDARD.encode([7u8; 64]);
+    let mut env = vec![
+      (
+        "CONFIG_FILE",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/config.example.toml"),
+      ),
+      ("DATABASE_URL", "postgres://localhost/acme"),
+      ("SESSION_SECRET", secret.as_str()),
+    ];
+
+    // The example turns `dev_routes` on for development, which an unset `APP_ENV` must not pick up
+    let config = Config::load_from(&vars(&env)).unwrap();
+    assert!(!config.env_is_explicit);
+    assert!(!config.server.dev_routes);
+
+    env.push(("APP_ENV", "development"));
+    let config = Config::load_from(&vars(&env)).unwrap();
+    assert!(config.server.dev_routes);
+  }
+
+  #[test]
+  fn environment_overrides_the_file() {
+    let mut config = Config::from_toml("config.toml", FILE, Some("staging")).unwrap();
+    let mut problems = Vec::new();
+
+    config.apply_env(
+      &vars(&[
+        ("PORT", "5000"),
+        ("CORS_ORIGINS", "https://a.example.com, "),
+      ]),
+      &mut problems,
+    );
+
+    assert!(problems.is_empty());
+    assert_eq!(config.server.port, 5000);
+    assert_eq!(config.server.cors_origins, ["https://a.example.com"]);
+    assert_eq!(config.mail.from, "Acme <hello@example.com>");
+  }
+
+  #[test]
+  fn invalid_environment_values_are_reported() {
+    let mut config = Config::default();
+    let mut problems = Vec::new();
+
+    config.apply_env(
+      &vars(&[("PORT", "eighty"), ("SESSION_COOKIE_SECURE", "yes please")]),
+      &mut problems,
+    );
+
+    assert_eq!(problems.len(), 2);
+    assert!(problems[0].starts_with("PORT: `eighty` is invalid"));
+    assert!(problems[1].starts_with("SESSION_COOKIE_SECURE"));
+    assert_eq!(config.server.port, Config::default().server.port);
+  }
+
+  #[test]
+  fn invalid_argon2_costs_are_reported() {
+    let mut config = Config::default();
+    config.password.argon2_parallelism = 0;
+
+    assert!(problems(&config)
+      .iter()
+      .any(|problem| problem.starts_with("password.argon2_*")));
+  }
+
+  #[test]
+  fn oauth_clients_need_both_id_and_secret() {
+    let mut config = Config::default();
+    config.oauth.github.client_id = "id".to_string();
+
+    assert!(problems(&config)
+      .iter()
+      .any(|problem| problem.starts_with("oauth.github.client_id")));
+  }
+
+  #[test]
+  fn oidc_client_needs_an_issuer() {
+    let mut config = Config::default();
+    config.oauth.oidc.client_id = "id".to_string();
+    config.oauth.oidc.client_secret = "secret".to_string();
+
+    assert!(problems(&config)
+      .iter()
+      .any(|problem| problem.starts_with("oauth.oidc.issuer")));
+  }
+
+  #[test]
+  fn dev_routes_need_an_explicit_development_env() {
+    let mut config = Config::default();
+    config.server.dev_routes = true;
+    let refused = |config: &Config| {
+      problems(config)
+        .iter()
+        .any(|problem| problem.starts_with("server.dev_routes"))
+    };
+
+    assert!(refused(&config));
+
+    config.env_is_explicit = true;
+    assert!(!refused(&config));
+
+    config.env = "test".to_string();
+    assert!(!refused(&config));
+
+    config.env = "staging".to_string();
+    assert!(refused(&config));
+  }
+
+  #[test]
+  fn default_password_and_oauth_settings_pass() {
+    let problems = problems(&Config::default());
+
+    assert!(!problems
+      .iter()
+      .any(|problem| problem.starts_with("password.") || problem.starts_with("oauth.")));
+  }
+}
@@ -0,0 +1,45 @@
+use std::{error::Error, fmt};
+
+use actix_web::http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::common::i18n::Translate;
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Serialize, Deserialize)]
+pub enum DevMessage {
+  // Success
+  DevEmailListSuccess,
+  DevEmailQueueSuccess,
+
+  // Error
+  DevEmailNotFound,
+  DevEmailLocaleUnsupported,
+  DevEmailRenderFailed,
+  DevEmailQueueFailed,
+}
+
+impl fmt::Display for DevMessage {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?}", self)
+  }
+}
+
+impl Error for DevMessage {}
+
+// Developers read the code
+impl Translate for DevMessage {}
+
+impl DevMessage {
+  /// Status the message is answered with when it is an error.
+  pub fn status_code(&self) -> StatusCode {
+    use DevMessage::*;
+
+    match self {
+      DevEmailNotFound => StatusCode::NOT_FOUND,
+      DevEmailLocaleUnsupported => StatusCode::BAD_REQUEST,
+      DevEmailRenderFailed | DevEmailQueueFailed => StatusCode::INTERNAL_SERVER_ERROR,
+      DevEmailListSuccess | DevEmailQueueSuccess => StatusCode::OK,
+    }
+  }
+}
@@ -0,0 +1,16 @@
+use serde::Deserialize;
+use validator::Validate;
+
+#[derive(Debug, Deserialize)]
+pub struct PreviewQuery {
+  /// Defaults to the request's `Accept-Language`.
+  pub locale: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SendPreviewDto {
+  #[validate(email(message = "ValidateEmailShape"))]
+  pub to: String,
+
+  pub locale: Option<String>,
+}
@@ -0,0 +1,205 @@
+/*
+ * Tools for local development, mounted under `/v1/dev` only when `server.dev_routes` is on.
+ *
+ * `/emails` lists the catalogue and renders every template with the fixture data from
+ * `email::fixtures`, as HTML to open in a browser or as the plain-text part. `send` queues one
+ * in the outbox like any other email, so with `mail.transport = "smtp"` pointed at Mailpit or
+ * Mailhog it lands in the local mail catcher a few seconds later.
+ */
+
+use actix_web::{get, http::StatusCode, post, web, HttpResponse};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+  common::{
+    error::AppError, extractors::ValidatedJson, functionalities::api_res::api_success, i18n::Locale,
+  },
+  dev::{
+    constants::DevMessage,
+    dto::{PreviewQuery, SendPreviewDto},
+  },
+  email::{
+    catalogue::{EmailKind, RenderedEmail},
+    fixtures,
+    outbox::EmailOutbox,
+  },
+  AppState,
+};
+
+pub mod constants;
+mod dto;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+  cfg.service(
+    web::scope("/dev")
+      .service(dev_emails)
+      .service(dev_email_html)
+      .service(dev_email_text)
+      .service(dev_email_send),
+  );
+}
+
+#[derive(Serialize)]
+struct EmailPreview {
+  kind: EmailKind,
+  subject: &'static str,
+  sender: &'static str,
+  locales: Vec<&'static str>,
+  html: String,
+  text: String,
+}
+
+#[derive(Serialize)]
+struct QueuedPreview {
+  id: Uuid,
+}
+
+/// Every catalogue email with the links to its previews.
+#[get("/emails")]
+async fn dev_emails() -> Result<HttpResponse, AppError> {
+  let emails = EmailKind::ALL
+    .into_iter()
+    .map(|kind| {
+      let entry = kind.entry();
+      EmailPreview {
+        kind,
+        subject: entry.subject,
+        sender: entry.sender,
+        locales: kind.locales().iter().map(|locale| locale.tag()).collect(),
+        html: format!("/v1/dev/emails/{}/html", kind.name()),
+        text: format!("/v1/dev/emails/{}/text", kind.name()),
+      }
+    })
+    .collect();
+
+  Ok(api_success::<Vec<EmailPreview>, DevMessage>(
+    StatusCode::OK,
+    emails,
+    DevMessage::DevEmailListSuccess,
+  ))
+}
+
+#[get("/emails/{kind}/html")]
+async fn dev_email_html(
+  path: web::Path<String>,
+  query: web::Query<PreviewQuery>,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let email = preview(&data, &path, query.locale.as_deref())?;
+
+  Ok(
+    HttpResponse::Ok()
+      .content_type("text/html; charset=utf-8")
+      .body(email.html),
+  )
+}
+
+#[get("/emails/{kind}/text")]
+async fn dev_email_text(
+  path: web::Path<String>,
+  query: web::Query<PreviewQuery>,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let email = preview(&data, &path, query.locale.as_deref())?;
+
+  Ok(
+    HttpResponse::Ok()
+      .content_type("text/plain; charset=utf-8")
+      .body(email.text),
+  )
+}
+
+/// Queues the preview for `to`; the outbox worker delivers it through the configured mailer.
+#[post("/emails/{kind}/send")]
+async fn dev_email_send(
+  path: web::Path<String>,
+  data: web::Data<AppState>,
+  credentials: ValidatedJson<SendPreviewDto>,
+) -> Result<HttpResponse, AppError> {
+  let email = preview(&data, &path, credentials.locale.as_deref())?;
+
+  let id = EmailOutbox::enqueue_rendered(&data.db, &credentials.to, &email)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      DevMessage::DevEmailQueueFailed
+    })?;
+
+  Ok(api_success::<QueuedPreview, DevMessage>(
+    StatusCode::OK,
+    QueuedPreview { id },
+    DevMessage::DevEmailQueueSuccess,
+  ))
+}
+
+fn preview(
+  data: &web::Data<AppState>,
+  kind: &str,
+  locale: Option<&str>,
+) -> Result<RenderedEmail, DevMessage> {
+  let kind = EmailKind::from_name(kind).ok_or(DevMessage::DevEmailNotFound)?;
+  let locale = match locale {
+    Some(tag) => Locale::from_tag(tag).ok_or(DevMessage::DevEmailLocaleUnsupported)?,
+    None => Locale::current(),
+  };
+
+  fixtures::render(&data.config.mail, kind, locale).map_err(|e| {
+    println!("{:?}", e);
+    DevMessage::DevEmailRenderFailed
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::{test, App};
+  use serde_json::json;
+
+  use super::*;
+  use crate::common::testing;
+
+  #[actix_web::test]
+  #[ignore = "needs a migrated Postgres (TEST_DATABASE_URL) and Redis (TEST_REDIS_URL)"]
+  async fn queued_preview_reaches_the_mailer() {
+    let state = testing::state().await;
+    let app = test::init_service(
+      App::new()
+        .app_data(state.data.clone())
+        .service(web::scope("/v1").configure(config)),
+    )
+    .await;
+    let to = format!("duck-{}@example.com", Uuid::new_v4());
+
+    let req = test::TestRequest::post()
+      .uri("/v1/dev/emails/sign-in-code/send")
+      .set_json(json!({ "to": to, "locale": "es" }))
+      .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    while EmailOutbox::deliver_due(&state.data).await.unwrap() > 0 {}
+
+    let sent = state.mailer.sent();
+    let message = sent
+      .iter()
+      .find(|message| {
+        message
+          .envelope()
+          .to()
+          .iter()
+          .any(|to_| to_.to_string() == to)
+      })
+      .expect("the preview was delivered");
+    let headers = message.headers();
+    assert_eq!(
+      headers.get_raw("Subject").unwrap(),
+      EmailKind::SignInCode.subject(Locale::Es)
+    );
+    assert!(headers
+      .get_raw("From")
+      .unwrap()
+      .contains("admin@example.com"));
+    let body = String::from_utf8(message.formatted()).unwrap();
+    assert!(body.contains("Inicia sesi"));
+  }
+}
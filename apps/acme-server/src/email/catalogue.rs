@@ -0,0 +1,357 @@
+use askama::Template;
+use lettre::message::Mailbox;
+use serde::Serialize;
+
+use crate::{
+  common::i18n::Locale,
+  config::MailConfig,
+  email::{
+    emails::{
+      AccountVerificationEmail, EventInvitationEmail, FeedbackRequestEmail, NewSignInEmail,
+      NewsletterConfirmationEmail, NewsletterEmail, OrderConfirmationEmail,
+      PromotionalDiscountEmail, ReengagementWinbackEmail, ResetPasswordEmail,
+      ShippingNotificationEmail, SignInCodeEmail, UpdateUserEmail, WelcomeEmail,
+    },
+    plain_text,
+  },
+};
+
+/// Every email we know how to send, one per template in `templates/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmailKind {
+  Welcome,
+  UpdateUser,
+  ResetPassword,
+  AccountVerification,
+  SignInCode,
+  NewSignIn,
+  NewsletterConfirmation,
+  Newsletter,
+  EventInvitation,
+  FeedbackRequest,
+  OrderConfirmation,
+  PromotionalDiscount,
+  ReengagementWinback,
+  ShippingNotification,
+}
+
+/// Where replies to an email should go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyTo {
+  /// Nobody reads replies, e.g. to one-time codes.
+  Nobody,
+  /// The support mailbox, `mail.support`.
+  Support,
+}
+
+/// How an email of one kind goes out.
+#[derive(Debug, Clone, Copy)]
+pub struct EmailEntry {
+  pub subject: &'static str,
+  /// Display name in front of the `mail.from` address.
+  pub sender: &'static str,
+  pub reply_to: ReplyTo,
+}
+
+impl EmailKind {
+  pub const ALL: [EmailKind; 14] = [
+    EmailKind::Welcome,
+    EmailKind::UpdateUser,
+    EmailKind::ResetPassword,
+    EmailKind::AccountVerification,
+    EmailKind::SignInCode,
+    EmailKind::NewSignIn,
+    EmailKind::NewsletterConfirmation,
+    EmailKind::Newsletter,
+    EmailKind::EventInvitation,
+    EmailKind::FeedbackRequest,
+    EmailKind::OrderConfirmation,
+    EmailKind::PromotionalDiscount,
+    EmailKind::ReengagementWinback,
+    EmailKind::ShippingNotification,
+  ];
+
+  /// Kebab-case name, as serialized and used in the dev preview routes.
+  pub fn name(self) -> &'static str {
+    match self {
+      EmailKind::Welcome => "welcome",
+      EmailKind::UpdateUser => "update-user",
+      EmailKind::ResetPassword => "reset-password",
+      EmailKind::AccountVerification => "account-verification",
+      EmailKind::SignInCode => "sign-in-code",
+      EmailKind::NewSignIn => "new-sign-in",
+      EmailKind::NewsletterConfirmation => "newsletter-confirmation",
+      EmailKind::Newsletter => "newsletter",
+      EmailKind::EventInvitation => "event-invitation",
+      EmailKind::FeedbackRequest => "feedback-request",
+      EmailKind::OrderConfirmation => "order-confirmation",
+      EmailKind::PromotionalDiscount => "promotional-discount",
+      EmailKind::ReengagementWinback => "reengagement-winback",
+      EmailKind::ShippingNotification => "shipping-notification",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<EmailKind> {
+    EmailKind::ALL.into_iter().find(|kind| kind.name() == name)
+  }
+
+  /// Locales with a template of their own; the others get English.
+  pub fn locales(self) -> &'static [Locale] {
+    match self {
+      EmailKind::Welcome
+      | EmailKind::UpdateUser
+      | EmailKind::ResetPassword
+      | EmailKind::AccountVerification
+      | EmailKind::SignInCode
+      | EmailKind::NewSignIn
+      | EmailKind::NewsletterConfirmation => &Locale::ALL,
+      _ => &[Locale::En],
+    }
+  }
+
+  pub fn entry(self) -> EmailEntry {
+    let (subject, sender, reply_to) = match self {
+      EmailKind::Welcome => ("Welcome to Acme", "Acme", ReplyTo::Support),
+      EmailKind::UpdateUser => (
+        "Your profile was updated",
+        "Acme Accounts",
+        ReplyTo::Support,
+      ),
+      EmailKind::ResetPassword => ("Reset your password", "Acme Accounts", ReplyTo::Nobody),
+      EmailKind::AccountVerification => (
+        "Verify your email address",
+        "Acme Accounts",
+        ReplyTo::Nobody,
+      ),
+      EmailKind::SignInCode => ("Your Acme sign-in code", "Acme Accounts", ReplyTo::Nobody),
+      EmailKind::NewSignIn => (
+        "New sign-in to your account",
+        "Acme Security",
+        ReplyTo::Support,
+      ),
+      EmailKind::NewsletterConfirmation => (
+        "Confirm your subscription",
+        "Acme Newsletter",
+        ReplyTo::Nobody,
+      ),
+      EmailKind::Newsletter => ("The Acme newsletter", "Acme Newsletter", ReplyTo::Support),
+      EmailKind::EventInvitation => ("You're invited", "Acme Events", ReplyTo::Support),
+      EmailKind::FeedbackRequest => ("We'd love your feedback", "Acme", ReplyTo::Support),
+      EmailKind::OrderConfirmation => ("Your order is confirmed", "Acme Orders", ReplyTo::Support),
+      EmailKind::PromotionalDiscount => ("An offer just for you", "Acme", ReplyTo::Nobody),
+      EmailKind::ReengagementWinback => ("We miss you", "Acme", ReplyTo::Support),
+      EmailKind::ShippingNotification => {
+        ("Your order is on the way", "Acme Orders", ReplyTo::Support)
+      },
+    };
+
+    EmailEntry {
+      subject,
+      sender,
+      reply_to,
+    }
+  }
+
+  /// Subject line in `locale`, the English one when there is no translation.
+  pub fn subject(self, locale: Locale) -> &'static str {
+    let translated = match (self, locale) {
+      (_, Locale::En) => None,
+      (EmailKind::Welcome, Locale::Es) => Some("Te damos la bienvenida a Acme"),
+      (EmailKind::UpdateUser, Locale::Es) => Some("Tu perfil se ha actualizado"),
+      (EmailKind::ResetPassword, Locale::Es) => Some("Restablece tu contraseña"),
+      (EmailKind::AccountVerification, Locale::Es) => Some("Verifica tu dirección de correo"),
+      (EmailKind::SignInCode, Locale::Es) => Some("Tu código para entrar en Acme"),
+      (EmailKind::NewSignIn, Locale::Es) => Some("Nuevo inicio de sesión en tu cuenta"),
+      (EmailKind::NewsletterConfirmation, Locale::Es) => Some("Confirma tu suscripción"),
+      _ => None,
+    };
+
+    translated.unwrap_or(self.entry().subject)
+  }
+}
+
+/// An askama template that has an entry in the catalogue.
+pub trait CatalogueEmail: Template {
+  const KIND: EmailKind;
+
+  /// The email in `locale`, English when the template has no translation.
+  fn render_in(&self, _locale: Locale) -> askama::Result<String> {
+    self.render()
+  }
+
+  /// One-click unsubscribe URL for list mail, sent as `List-Unsubscribe`.
+  fn list_unsubscribe(&self) -> Option<&str> {
+    None
+  }
+}
+
+/// Catalogues an email as `kind`, with its Spanish template under `templates/es/` when given.
+/// The translated template sees the same fields as the English one.
+macro_rules! catalogue_email {
+  ($email:ident => $kind:ident, es: $es:literal) => {
+    impl CatalogueEmail for $email<'_> {
+      const KIND: EmailKind = EmailKind::$kind;
+
+      fn render_in(&self, locale: Locale) -> askama::Result<String> {
+        #[derive(Template)]
+        #[template(path = $es)]
+        struct Es<'e, 'a>(&'e $email<'a>);
+
+        impl<'a> std::ops::Deref for Es<'_, 'a> {
+          type Target = $email<'a>;
+
+          fn deref(&self) -> &Self::Target {
+            self.0
+          }
+        }
+
+        match locale {
+          Locale::En => self.render(),
+          Locale::Es => Es(self).render(),
+        }
+      }
+    }
+  };
+  ($email:ty => $kind:ident) => {
+    impl CatalogueEmail for $email {
+      const KIND: EmailKind = EmailKind::$kind;
+    }
+  };
+}
+
+catalogue_email!(WelcomeEmail => Welcome, es: "es/welcome_email.html");
+catalogue_email!(UpdateUserEmail => UpdateUser, es: "es/update_user_info.html");
+catalogue_email!(ResetPasswordEmail => ResetPassword, es: "es/password-reset.html");
+catalogue_email!(AccountVerificationEmail => AccountVerification, es: "es/account-verification.html");
+catalogue_email!(SignInCodeEmail => SignInCode, es: "es/sign-in-code.html");
+catalogue_email!(NewSignInEmail => NewSignIn, es: "es/new_sign_in.html");
+catalogue_email!(
+  NewsletterConfirmationEmail => NewsletterConfirmation,
+  es: "es/newsletter-confirmation.html"
+);
+catalogue_email!(EventInvitationEmail => EventInvitation);
+catalogue_email!(FeedbackRequestEmail => FeedbackRequest);
+catalogue_email!(OrderConfirmationEmail => OrderConfirmation);
+catalogue_email!(PromotionalDiscountEmail => PromotionalDiscount);
+catalogue_email!(ReengagementWinbackEmail => ReengagementWinback);
+catalogue_email!(ShippingNotificationEmail => ShippingNotification);
+
+impl CatalogueEmail for NewsletterEmail<'_> {
+  const KIND: EmailKind = EmailKind::Newsletter;
+
+  fn list_unsubscribe(&self) -> Option<&str> {
+    Some(self.one_click_unsubscribe)
+  }
+}
+
+/// A catalogue email with its headers resolved, ready for the outbox.
+#[derive(Debug)]
+pub struct RenderedEmail {
+  pub subject: String,
+  pub sender: Mailbox,
+  pub reply_to: Option<Mailbox>,
+  pub html: String,
+  pub text: String,
+  pub list_unsubscribe: Option<String>,
+}
+
+impl RenderedEmail {
+  pub fn render<T: CatalogueEmail>(
+    config: &MailConfig,
+    email: &T,
+    locale: Locale,
+  ) -> Result<Self, anyhow::Error> {
+    let entry = T::KIND.entry();
+    let from: Mailbox = config.from.parse()?;
+    let html = email.render_in(locale)?;
+
+    Ok(RenderedEmail {
+      subject: T::KIND.subject(locale).to_string(),
+      sender: Mailbox::new(Some(entry.sender.to_string()), from.email),
+      reply_to: match entry.reply_to {
+        ReplyTo::Nobody => None,
+        ReplyTo::Support => Some(config.support.parse()?),
+      },
+      text: plain_text::from_html(&html),
+      html,
+      list_unsubscribe: email.list_unsubscribe().map(str::to_string),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn renders_in_the_recipient_locale() {
+    let email = SignInCodeEmail {
+      username: "duck",
+      sign_in_code: "123456",
+      sign_in_link: "https://example.com/signin",
+    };
+
+    let en = email.render_in(Locale::En).unwrap();
+    let es = email.render_in(Locale::Es).unwrap();
+
+    assert!(en.contains("Sign In to Acme") && en.contains("123456"));
+    assert!(es.contains("Inicia sesión en Acme") && es.contains("123456"));
+    assert!(es.contains(r#"lang="es""#));
+  }
+
+  /// A field that can't be displayed, failing the render like a broken filter would.
+  struct Unprintable;
+
+  impl std::fmt::Display for Unprintable {
+    fn fmt(&self, _f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      Err(std::fmt::Error)
+    }
+  }
+
+  #[derive(Template)]
+  #[template(source = "<p>{{ value }}</p>", ext = "html")]
+  struct BrokenEmail {
+    value: Unprintable,
+  }
+
+  impl CatalogueEmail for BrokenEmail {
+    const KIND: EmailKind = EmailKind::Welcome;
+  }
+
+  #[test]
+  fn template_failures_error_instead_of_panicking() {
+    let email = BrokenEmail { value: Unprintable };
+
+    let e = RenderedEmail::render(&MailConfig::default(), &email, Locale::En);
+
+    assert!(e.is_err());
+  }
+
+  #[test]
+  fn unparsable_sender_errors_instead_of_panicking() {
+    let config = MailConfig {
+      from: "not a mailbox".to_string(),
+      ..MailConfig::default()
+    };
+    let email = SignInCodeEmail {
+      username: "duck",
+      sign_in_code: "123456",
+      sign_in_link: "https://example.com/signin",
+    };
+
+    assert!(RenderedEmail::render(&config, &email, Locale::En).is_err());
+  }
+
+  #[test]
+  fn subjects_fall_back_to_english() {
+    assert_eq!(
+      EmailKind::ResetPassword.subject(Locale::Es),
+      "Restablece tu contraseña"
+    );
+    assert_eq!(
+      EmailKind::Newsletter.subject(Locale::Es),
+      "The Acme newsletter"
+    );
+  }
+}
@@ -0,0 +1,12 @@
+/// How often (seconds) the outbox worker looks for due emails.
+pub const OUTBOX_POLL_INTERVAL_SECS: u64 = 5;
+/// How many emails one pass of the worker claims.
+pub const OUTBOX_BATCH_SIZE: i64 = 20;
+/// Delivery attempts before an email is moved to the dead letters.
+pub const OUTBOX_MAX_ATTEMPTS: i32 = 8;
+/// Wait (seconds) after the first failed attempt; doubled after every further one.
+pub const OUTBOX_BASE_BACKOFF_SECS: i64 = 30;
+/// Longest wait (seconds) between two attempts.
+pub const OUTBOX_MAX_BACKOFF_SECS: i64 = 6 * 60 * 60;
+/// How long (seconds) a claimed email is left to its worker before another one may retry it.
+pub const OUTBOX_LEASE_SECS: i64 = 5 * 60;
@@ -1,12 +1,28 @@
 use askama::Template;
 use serde::Serialize;
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct UpdatedField<'a> {
   pub label: &'a str,
   pub value: String,
 }
 
+impl UpdatedField<'_> {
+  /// Label for the Spanish template; the English one shows the column name.
+  pub fn label_es(&self) -> &str {
+    match self.label {
+      "username" => "nombre de usuario",
+      "email" => "correo",
+      "first_name" => "nombre",
+      "last_name" => "apellidos",
+      "password_hash" => "contraseña",
+      "locale" => "idioma",
+      "timezone" => "zona horaria",
+      label => label,
+    }
+  }
+}
+
 #[derive(Template, Serialize)]
 #[template(path = "update_user_info.html")]
 pub struct UpdateUserEmail<'a> {
@@ -31,3 +47,72 @@ pub struct ResetPasswordEmail<'a> {
   pub reset_code: &'a str,
   pub reset_link: &'a str,
 }
+
+#[derive(Template, Serialize)]
+#[template(path = "account-verification.html")]
+pub struct AccountVerificationEmail<'a> {
+  pub username: &'a str,
+  pub verification_code: &'a str,
+  pub verification_link: &'a str,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "sign-in-code.html")]
+pub struct SignInCodeEmail<'a> {
+  pub username: &'a str,
+  pub sign_in_code: &'a str,
+  pub sign_in_link: &'a str,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "new_sign_in.html")]
+pub struct NewSignInEmail<'a> {
+  pub username: &'a str,
+  pub email: &'a str,
+  pub location: &'a str,
+  pub signed_in_at: &'a str,
+  pub ip: &'a str,
+  pub user_agent: &'a str,
+  pub not_me_link: &'a str,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "newsletter-confirmation.html")]
+pub struct NewsletterConfirmationEmail<'a> {
+  pub confirm_link: &'a str,
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "newsletter.html")]
+pub struct NewsletterEmail<'a> {
+  /// Page in the web app, linked from the footer.
+  pub unsubscribe_link: &'a str,
+  /// API endpoint mail clients POST to, sent as `List-Unsubscribe`.
+  pub one_click_unsubscribe: &'a str,
+}
+
+// The marketing templates are static for now, their content is written into the HTML
+
+#[derive(Template, Serialize)]
+#[template(path = "event-invitation.html")]
+pub struct EventInvitationEmail;
+
+#[derive(Template, Serialize)]
+#[template(path = "feedback-request.html")]
+pub struct FeedbackRequestEmail;
+
+#[derive(Template, Serialize)]
+#[template(path = "order-confirmation.html")]
+pub struct OrderConfirmationEmail;
+
+#[derive(Template, Serialize)]
+#[template(path = "promotional-discount.html")]
+pub struct PromotionalDiscountEmail;
+
+#[derive(Template, Serialize)]
+#[template(path = "reengagement-winback.html")]
+pub struct ReengagementWinbackEmail;
+
+#[derive(Template, Serialize)]
+#[template(path = "shipping-notification.html")]
+pub struct ShippingNotificationEmail;
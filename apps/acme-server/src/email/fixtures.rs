@@ -0,0 +1,156 @@
+use crate::{
+  common::i18n::Locale,
+  config::MailConfig,
+  email::{
+    catalogue::{EmailKind, RenderedEmail},
+    emails::{
+      AccountVerificationEmail, EventInvitationEmail, FeedbackRequestEmail, NewSignInEmail,
+      NewsletterConfirmationEmail, NewsletterEmail, OrderConfirmationEmail,
+      PromotionalDiscountEmail, ReengagementWinbackEmail, ResetPasswordEmail,
+      ShippingNotificationEmail, SignInCodeEmail, UpdateUserEmail, UpdatedField, WelcomeEmail,
+    },
+  },
+};
+
+/// Renders `kind` with made-up data, for the dev previews and the template snapshots. Nothing in
+/// here depends on the clock or the environment, so the output only changes with the templates.
+pub fn render(
+  config: &MailConfig,
+  kind: EmailKind,
+  locale: Locale,
+) -> Result<RenderedEmail, anyhow::Error> {
+  let signed_in_at =
+    locale.format_datetime("2026-01-05T23:30:00Z".parse()?, chrono_tz::Europe::Madrid);
+
+  match kind {
+    EmailKind::Welcome => RenderedEmail::render(
+      config,
+      &WelcomeEmail {
+        username: "duck",
+        dashboard_url: "https://example.com/dashboard/",
+        current_year: 2026,
+      },
+      locale,
+    ),
+    EmailKind::UpdateUser => RenderedEmail::render(
+      config,
+      &UpdateUserEmail {
+        username: "duck",
+        view_profile_link: "https://example.com/profile",
+        updated_fields: vec![
+          UpdatedField {
+            label: "first_name",
+            value: "Donald".to_string(),
+          },
+          UpdatedField {
+            label: "password_hash",
+            value: "$argon2id$v=19$fixture".to_string(),
+          },
+          UpdatedField {
+            label: "timezone",
+            value: "Europe/Madrid".to_string(),
+          },
+        ],
+        last_update: &signed_in_at,
+      },
+      locale,
+    ),
+    EmailKind::ResetPassword => RenderedEmail::render(
+      config,
+      &ResetPasswordEmail {
+        username: "duck",
+        reset_code: "482913",
+        reset_link: "https://example.com/reset-password?user_id=fixture&code=482913",
+      },
+      locale,
+    ),
+    EmailKind::AccountVerification => RenderedEmail::render(
+      config,
+      &AccountVerificationEmail {
+        username: "duck",
+        verification_code: "275064",
+        verification_link: "https://example.com/verify-email?user_id=fixture&code=275064",
+      },
+      locale,
+    ),
+    EmailKind::SignInCode => RenderedEmail::render(
+      config,
+      &SignInCodeEmail {
+        username: "duck",
+        sign_in_code: "913370",
+        sign_in_link: "https://example.com/signin/magic-link?token=fixture",
+      },
+      locale,
+    ),
+    EmailKind::NewSignIn => RenderedEmail::render(
+      config,
+      &NewSignInEmail {
+        username: "duck",
+        email: "duck@example.com",
+        location: "Madrid, Spain",
+        signed_in_at: &signed_in_at,
+        ip: "203.0.113.7",
+        user_agent: "Mozilla/5.0 (Macintosh; Intel Mac OS X 14_5) Firefox/128.0",
+        not_me_link: "https://example.com/not-me?token=fixture",
+      },
+      locale,
+    ),
+    EmailKind::NewsletterConfirmation => RenderedEmail::render(
+      config,
+      &NewsletterConfirmationEmail {
+        confirm_link: "https://example.com/newsletter/confirm?token=fixture",
+      },
+      locale,
+    ),
+    EmailKind::Newsletter => RenderedEmail::render(
+      config,
+      &NewsletterEmail {
+        unsubscribe_link: "https://example.com/newsletter/unsubscribe?token=fixture",
+        one_click_unsubscribe: "https://api.example.com/v1/subscribers/unsubscribe?token=fixture",
+      },
+      locale,
+    ),
+    EmailKind::EventInvitation => RenderedEmail::render(config, &EventInvitationEmail, locale),
+    EmailKind::FeedbackRequest => RenderedEmail::render(config, &FeedbackRequestEmail, locale),
+    EmailKind::OrderConfirmation => RenderedEmail::render(config, &OrderConfirmationEmail, locale),
+    EmailKind::PromotionalDiscount => {
+      RenderedEmail::render(config, &PromotionalDiscountEmail, locale)
+    },
+    EmailKind::ReengagementWinback => {
+      RenderedEmail::render(config, &ReengagementWinbackEmail, locale)
+    },
+    EmailKind::ShippingNotification => {
+      RenderedEmail::render(config, &ShippingNotificationEmail, locale)
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Every template in every locale it has, as the plain text a reader would get. A template that
+  // fails to render, or renders differently, fails here; `cargo insta review` takes the new
+  // output once the change is intended.
+  #[test]
+  fn every_template_renders() {
+    let config = MailConfig::default();
+
+    for kind in EmailKind::ALL {
+      for locale in kind.locales() {
+        let email = render(&config, kind, *locale).unwrap();
+        assert!(email.html.contains("</html>"), "{} cut short", kind.name());
+
+        let reply_to = email.reply_to.map(|reply_to| reply_to.to_string());
+        let rendered = format!(
+          "Subject: {}\nFrom: {}\nReply-To: {}\n\n{}",
+          email.subject,
+          email.sender,
+          reply_to.as_deref().unwrap_or("-"),
+          email.text
+        );
+        insta::assert_snapshot!(format!("{}-{}", kind.name(), locale.tag()), rendered);
+      }
+    }
+  }
+}
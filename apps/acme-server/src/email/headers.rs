@@ -0,0 +1,68 @@
+use std::error::Error;
+
+use lettre::message::header::{Header, HeaderName, HeaderValue};
+
+/// `List-Unsubscribe` (RFC 2369), the URL mail clients offer as their own unsubscribe button.
+#[derive(Debug, Clone)]
+pub struct ListUnsubscribe(pub String);
+
+impl Header for ListUnsubscribe {
+  fn name() -> HeaderName {
+    HeaderName::new_from_ascii_str("List-Unsubscribe")
+  }
+
+  fn parse(s: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+    let url = s.trim().trim_start_matches('<').trim_end_matches('>');
+    Ok(ListUnsubscribe(url.to_string()))
+  }
+
+  fn display(&self) -> HeaderValue {
+    HeaderValue::new(Self::name(), format!("<{}>", self.0))
+  }
+}
+
+/// `List-Unsubscribe-Post` (RFC 8058): the client unsubscribes with a single POST to the
+/// `List-Unsubscribe` URL, without opening a page.
+#[derive(Debug, Clone)]
+pub struct ListUnsubscribePost;
+
+impl Header for ListUnsubscribePost {
+  fn name() -> HeaderName {
+    HeaderName::new_from_ascii_str("List-Unsubscribe-Post")
+  }
+
+  fn parse(_s: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+    Ok(ListUnsubscribePost)
+  }
+
+  fn display(&self) -> HeaderValue {
+    HeaderValue::new(Self::name(), "List-Unsubscribe=One-Click".to_string())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use lettre::Message;
+
+  use super::*;
+
+  #[test]
+  fn one_click_unsubscribe_headers() {
+    let message = Message::builder()
+      .from("Acme Newsletter <admin@example.com>".parse().unwrap())
+      .to("duck@example.com".parse().unwrap())
+      .subject("The Acme newsletter")
+      .header(ListUnsubscribe(
+        "https://api.example.com/v1/subscribers/unsubscribe?token=abc".to_string(),
+      ))
+      .header(ListUnsubscribePost)
+      .body("Quack".to_string())
+      .unwrap();
+
+    let eml = String::from_utf8(message.formatted()).unwrap();
+    assert!(eml.contains(
+      "List-Unsubscribe: <https://api.example.com/v1/subscribers/unsubscribe?token=abc>\r\n"
+    ));
+    assert!(eml.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click\r\n"));
+  }
+}
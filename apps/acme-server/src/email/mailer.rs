@@ -0,0 +1,203 @@
+use std::{
+  fmt, fs,
+  path::PathBuf,
+  sync::{Arc, Mutex},
+};
+
+use chrono::Utc;
+use lettre::{
+  transport::smtp::{
+    authentication::Credentials,
+    client::{Tls, TlsParameters},
+  },
+  Message, SmtpTransport, Transport,
+};
+use uuid::Uuid;
+
+use crate::config::{MailConfig, MailTls, MailTransport};
+
+/// Why a message wasn't handed over, and whether trying again can help.
+#[derive(Debug)]
+pub enum MailerError {
+  Transient(String),
+  Permanent(String),
+}
+
+impl fmt::Display for MailerError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      MailerError::Transient(e) => write!(f, "transient: {e}"),
+      MailerError::Permanent(e) => write!(f, "permanent: {e}"),
+    }
+  }
+}
+
+/// Something that takes a finished message off our hands. Sending may block, so callers on the
+/// async runtime go through `spawn_blocking`.
+pub trait Mailer: Send + Sync {
+  fn send(&self, message: &Message) -> Result<(), MailerError>;
+}
+
+/// Builds the backend picked by `mail.transport`.
+pub fn from_config(config: &MailConfig) -> Arc<dyn Mailer> {
+  match config.transport {
+    MailTransport::Smtp => Arc::new(SmtpMailer::new(config)),
+    MailTransport::File => Arc::new(FileMailer::new(&config.drop_dir)),
+    MailTransport::Memory => Arc::new(MemoryMailer::default()),
+  }
+}
+
+/// Delivers through an SMTP relay.
+pub struct SmtpMailer {
+  transport: SmtpTransport,
+}
+
+impl SmtpMailer {
+  pub fn new(config: &MailConfig) -> Self {
+    let tls = match config.tls {
+      // Fine for local dev servers like Mailpit/Mailhog
+      MailTls::None => Tls::None,
+      MailTls::Starttls => Tls::Required(Self::tls_parameters(&config.host)),
+      MailTls::Implicit => Tls::Wrapper(Self::tls_parameters(&config.host)),
+    };
+
+    let mut builder = SmtpTransport::builder_dangerous(&config.host)
+      .port(config.port)
+      .tls(tls);
+
+    // Only add credentials if provided
+    if !config.username.is_empty() {
+      builder = builder.credentials(Credentials::new(
+        config.username.clone(),
+        config.password.clone(),
+      ));
+    }
+
+    SmtpMailer {
+      transport: builder.build(),
+    }
+  }
+
+  fn tls_parameters(host: &str) -> TlsParameters {
+    TlsParameters::new(host.to_string()).expect("Invalid MAIL_HOST for TLS")
+  }
+}
+
+impl Mailer for SmtpMailer {
+  fn send(&self, message: &Message) -> Result<(), MailerError> {
+    self.transport.send(message).map(|_| ()).map_err(|e| {
+      if e.is_permanent() {
+        MailerError::Permanent(e.to_string())
+      } else {
+        MailerError::Transient(e.to_string())
+      }
+    })
+  }
+}
+
+/// Writes every message to `{dir}/{timestamp}-{id}.eml`, to open in a mail client during local
+/// development.
+pub struct FileMailer {
+  dir: PathBuf,
+}
+
+impl FileMailer {
+  pub fn new(dir: &str) -> Self {
+    FileMailer { dir: dir.into() }
+  }
+}
+
+impl Mailer for FileMailer {
+  fn send(&self, message: &Message) -> Result<(), MailerError> {
+    let name = format!(
+      "{}-{}.eml",
+      Utc::now().format("%Y%m%dT%H%M%S%.3f"),
+      Uuid::new_v4()
+    );
+
+    fs::create_dir_all(&self.dir)
+      .and_then(|_| fs::write(self.dir.join(name), message.formatted()))
+      .map_err(|e| MailerError::Transient(format!("{}: {e}", self.dir.display())))
+  }
+}
+
+/// Keeps every message in memory, for tests to look at.
+#[derive(Default)]
+pub struct MemoryMailer {
+  sent: Mutex<Vec<Message>>,
+}
+
+impl MemoryMailer {
+  /// Everything sent so far, oldest first.
+  #[cfg(test)]
+  pub fn sent(&self) -> Vec<Message> {
+    self
+      .sent
+      .lock()
+      .map(|sent| sent.clone())
+      .unwrap_or_default()
+  }
+}
+
+impl Mailer for MemoryMailer {
+  fn send(&self, message: &Message) -> Result<(), MailerError> {
+    self
+      .sent
+      .lock()
+      .map_err(|e| MailerError::Transient(e.to_string()))?
+      .push(message.clone());
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use lettre::message::header::ContentType;
+
+  use super::*;
+
+  fn message(subject: &str) -> Message {
+    Message::builder()
+      .from("Acme App <admin@example.com>".parse().unwrap())
+      .to("duck@example.com".parse().unwrap())
+      .subject(subject)
+      .header(ContentType::TEXT_HTML)
+      .body("<p>Quack</p>".to_string())
+      .unwrap()
+  }
+
+  #[test]
+  fn memory_mailer_captures_in_order() {
+    let mailer = MemoryMailer::default();
+
+    mailer.send(&message("First")).unwrap();
+    mailer.send(&message("Second")).unwrap();
+
+    let subjects: Vec<_> = mailer
+      .sent()
+      .iter()
+      .map(|message| message.headers().get_raw("Subject").unwrap().to_string())
+      .collect();
+    assert_eq!(subjects, ["First", "Second"]);
+  }
+
+  #[test]
+  fn file_mailer_drops_eml_files() {
+    let dir = std::env::temp_dir().join(format!("acme-mailer-{}", Uuid::new_v4()));
+    let mailer = FileMailer::new(dir.to_str().unwrap());
+
+    mailer.send(&message("Dropped")).unwrap();
+
+    let files: Vec<_> = fs::read_dir(&dir)
+      .unwrap()
+      .map(|e| e.unwrap().path())
+      .collect();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].extension().unwrap(), "eml");
+    let eml = fs::read_to_string(&files[0]).unwrap();
+    assert!(eml.contains("Subject: Dropped"));
+    assert!(eml.contains("To: duck@example.com"));
+
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
@@ -0,0 +1,284 @@
+use std::{sync::Arc, time::Duration};
+
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use lettre::{message::MultiPart, Message};
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, FromRow, PgPool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+  common::i18n::Locale,
+  config::MailConfig,
+  email::{
+    catalogue::{CatalogueEmail, RenderedEmail},
+    constants::{
+      OUTBOX_BASE_BACKOFF_SECS, OUTBOX_BATCH_SIZE, OUTBOX_LEASE_SECS, OUTBOX_MAX_ATTEMPTS,
+      OUTBOX_MAX_BACKOFF_SECS, OUTBOX_POLL_INTERVAL_SECS,
+    },
+    headers::{ListUnsubscribe, ListUnsubscribePost},
+    mailer::{Mailer, MailerError},
+  },
+  AppState,
+};
+
+/// Where an outbox email is on its way to the recipient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "email_outbox_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum EmailOutboxStatus {
+  Pending,
+  /// Claimed by a worker; taken back once its lease runs out.
+  Sending,
+  Sent,
+  /// Gave up after `OUTBOX_MAX_ATTEMPTS`, or the message could never be built.
+  Dead,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
+pub struct OutboxEmail {
+  pub id: Uuid,
+  pub recipient: String,
+  pub sender: String,
+  pub reply_to: Option<String>,
+  pub subject: String,
+  /// Emptied, like `text`, once the email is sent or dead.
+  pub html: String,
+  pub text: String,
+  /// One-click unsubscribe URL, for list mail like the newsletter.
+  pub list_unsubscribe: Option<String>,
+  pub status: EmailOutboxStatus,
+  pub attempts: i32,
+  pub next_attempt_at: DateTime<Utc>,
+  pub last_error: Option<String>,
+  pub sent_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+pub struct EmailOutbox;
+
+impl EmailOutbox {
+  /// Renders a catalogue email and queues it; pass the transaction of the change it reports on,
+  /// so both land or neither.
+  pub async fn enqueue<'e, E, T>(
+    executor: E,
+    config: &MailConfig,
+    recipient: &str,
+    locale: Locale,
+    email: &T,
+  ) -> Result<Uuid, anyhow::Error>
+  where
+    E: Executor<'e, Database = Postgres>,
+    T: CatalogueEmail,
+  {
+    let email = RenderedEmail::render(config, email, locale)?;
+
+    Ok(Self::enqueue_rendered(executor, recipient, &email).await?)
+  }
+
+  /// Queues an email that is already rendered.
+  pub async fn enqueue_rendered<'e, E>(
+    executor: E,
+    recipient: &str,
+    email: &RenderedEmail,
+  ) -> Result<Uuid, sqlx::Error>
+  where
+    E: Executor<'e, Database = Postgres>,
+  {
+    sqlx::query_scalar::<_, Uuid>(
+      r#"
+        INSERT INTO email_outbox (recipient, sender, reply_to, subject, html, text, list_unsubscribe)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id
+      "#,
+    )
+    .bind(recipient)
+    .bind(email.sender.to_string())
+    .bind(email.reply_to.as_ref().map(|reply_to| reply_to.to_string()))
+    .bind(&email.subject)
+    .bind(&email.html)
+    .bind(&email.text)
+    .bind(&email.list_unsubscribe)
+    .fetch_one(executor)
+    .await
+  }
+
+  /// Delivers due emails forever; spawned once at startup.
+  pub async fn run_worker(data: web::Data<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(OUTBOX_POLL_INTERVAL_SECS));
+
+    loop {
+      interval.tick().await;
+
+      // Keep going while there's a backlog instead of waiting for the next tick
+      loop {
+        match Self::deliver_due(&data).await {
+          Ok(count) if count < OUTBOX_BATCH_SIZE as usize => break,
+          Ok(_) => {},
+          Err(e) => {
+            eprintln!("Email outbox failed: {:?}", e);
+            break;
+          },
+        }
+      }
+    }
+  }
+
+  /// Claims a batch of due emails and tries each once; returns how many were claimed.
+  pub async fn deliver_due(data: &web::Data<AppState>) -> Result<usize, sqlx::Error> {
+    Self::bury_abandoned(&data.db).await?;
+    let emails = Self::claim(&data.db).await?;
+
+    for email in &emails {
+      let result = Self::deliver(&data.mailer, email).await;
+      if let Err(e) = Self::record(&data.db, email, result).await {
+        println!("{:?}", e);
+      }
+    }
+
+    Ok(emails.len())
+  }
+
+  // `SKIP LOCKED` lets several workers share the table; the lease hands an email back if its
+  // worker died mid-send
+  async fn claim(db: &PgPool) -> Result<Vec<OutboxEmail>, sqlx::Error> {
+    sqlx::query_as::<_, OutboxEmail>(
+      r#"
+        UPDATE email_outbox
+        SET status = 'sending',
+            attempts = attempts + 1,
+            next_attempt_at = NOW() + make_interval(secs => $2),
+            updated_at = NOW()
+        WHERE id IN (
+          SELECT id
+          FROM email_outbox
+          WHERE status IN ('pending', 'sending')
+            AND next_attempt_at <= NOW()
+            AND attempts < $3
+          ORDER BY next_attempt_at
+          LIMIT $1
+          FOR UPDATE SKIP LOCKED
+        )
+        RETURNING *
+      "#,
+    )
+    .bind(OUTBOX_BATCH_SIZE)
+    .bind(OUTBOX_LEASE_SECS as f64)
+    .bind(OUTBOX_MAX_ATTEMPTS)
+    .fetch_all(db)
+    .await
+  }
+
+  /// Gives up on emails whose worker died during their last attempt, which `claim` no longer
+  /// takes back.
+  async fn bury_abandoned(db: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+      r#"
+        UPDATE email_outbox
+        SET status = 'dead',
+            html = '',
+            text = '',
+            last_error = COALESCE(last_error, 'lease expired on the last attempt'),
+            updated_at = NOW()
+        WHERE status = 'sending'
+          AND next_attempt_at <= NOW()
+          AND attempts >= $1
+      "#,
+    )
+    .bind(OUTBOX_MAX_ATTEMPTS)
+    .execute(db)
+    .await?;
+
+    Ok(())
+  }
+
+  async fn deliver(mailer: &Arc<dyn Mailer>, email: &OutboxEmail) -> Result<(), MailerError> {
+    let message = Self::message(email).map_err(MailerError::Permanent)?;
+
+    // SMTP blocks, keep it off the async workers
+    let mailer = mailer.clone();
+    tokio::task::spawn_blocking(move || mailer.send(&message))
+      .await
+      .map_err(|e| MailerError::Transient(e.to_string()))?
+  }
+
+  fn message(email: &OutboxEmail) -> Result<Message, String> {
+    let mut builder = Message::builder()
+      .from(email.sender.parse().map_err(|e| format!("from: {e}"))?)
+      .to(email.recipient.parse().map_err(|e| format!("to: {e}"))?)
+      .subject(&email.subject);
+    if let Some(reply_to) = &email.reply_to {
+      builder = builder.reply_to(reply_to.parse().map_err(|e| format!("reply-to: {e}"))?);
+    }
+    if let Some(url) = &email.list_unsubscribe {
+      builder = builder
+        .header(ListUnsubscribe(url.clone()))
+        .header(ListUnsubscribePost);
+    }
+
+    builder
+      .multipart(MultiPart::alternative_plain_html(
+        email.text.clone(),
+        email.html.clone(),
+      ))
+      .map_err(|e| e.to_string())
+  }
+
+  async fn record(
+    db: &PgPool,
+    email: &OutboxEmail,
+    result: Result<(), MailerError>,
+  ) -> Result<(), sqlx::Error> {
+    let (status, error, retry_in) = match result {
+      Ok(()) => (EmailOutboxStatus::Sent, None, 0),
+      Err(MailerError::Permanent(e)) => (EmailOutboxStatus::Dead, Some(e), 0),
+      Err(MailerError::Transient(e)) if email.attempts >= OUTBOX_MAX_ATTEMPTS => {
+        (EmailOutboxStatus::Dead, Some(e), 0)
+      },
+      Err(MailerError::Transient(e)) => (
+        EmailOutboxStatus::Pending,
+        Some(e),
+        Self::backoff_secs(email.attempts),
+      ),
+    };
+
+    if let Some(error) = &error {
+      eprintln!(
+        "Email {} to {} failed (attempt {}, now {:?}): {error}",
+        email.id, email.recipient, email.attempts, status
+      );
+    }
+
+    // Bodies can carry live codes and links, so they are only kept while a retry needs them
+    sqlx::query(
+      r#"
+        UPDATE email_outbox
+        SET status = $2,
+            last_error = $3,
+            next_attempt_at = NOW() + make_interval(secs => $4),
+            sent_at = CASE WHEN $2 = 'sent'::email_outbox_status THEN NOW() END,
+            html = CASE WHEN $2 = 'pending'::email_outbox_status THEN html ELSE '' END,
+            text = CASE WHEN $2 = 'pending'::email_outbox_status THEN text ELSE '' END,
+            updated_at = NOW()
+        WHERE id = $1::uuid
+      "#,
+    )
+    .bind(email.id)
+    .bind(status)
+    .bind(error)
+    .bind(retry_in as f64)
+    .execute(db)
+    .await?;
+
+    Ok(())
+  }
+
+  /// Wait before the next try, after `attempts` failed ones.
+  fn backoff_secs(attempts: i32) -> i64 {
+    let doublings = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    OUTBOX_BASE_BACKOFF_SECS
+      .saturating_mul(1 << doublings)
+      .min(OUTBOX_MAX_BACKOFF_SECS)
+  }
+}
@@ -0,0 +1,195 @@
+/// Plain-text version of a rendered email, for the `text/plain` part and for clients that don't
+/// show HTML.
+///
+/// Good enough for our own templates, not a general HTML parser: markup that never shows
+/// (`head`, `style`, `svg`, comments) is dropped, blocks become line breaks, list items get a
+/// dash, and links keep their target next to the label.
+pub fn from_html(html: &str) -> String {
+  let mut out = String::new();
+  let mut hidden: Option<String> = None;
+  let mut link: Option<(String, usize)> = None;
+  let mut rest = html;
+
+  while let Some(start) = rest.find('<') {
+    if hidden.is_none() {
+      push_text(&mut out, &rest[..start]);
+    }
+    rest = &rest[start..];
+
+    if let Some(comment) = rest.strip_prefix("<!--") {
+      rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+      continue;
+    }
+
+    let Some(end) = rest.find('>') else {
+      break;
+    };
+    let tag = &rest[1..end];
+    rest = &rest[end + 1..];
+
+    let closing = tag.starts_with('/');
+    let name = tag
+      .trim_start_matches('/')
+      .split(|c: char| c.is_whitespace() || c == '/')
+      .next()
+      .unwrap_or_default()
+      .to_ascii_lowercase();
+
+    if let Some(hidden_name) = &hidden {
+      if closing && *hidden_name == name {
+        hidden = None;
+      }
+      continue;
+    }
+
+    match name.as_str() {
+      "head" | "style" | "script" | "svg" | "title" if !closing => hidden = Some(name),
+      "p" | "div" | "table" | "ul" | "ol" | "hr" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+        out.push_str("\n\n")
+      },
+      "br" | "tr" => out.push('\n'),
+      "li" if !closing => out.push_str("\n- "),
+      "td" | "th" if !closing => out.push(' '),
+      "a" if !closing => link = attribute(tag, "href").map(|href| (href, out.len())),
+      "a" => {
+        if let Some((href, at)) = link.take() {
+          let label = out[at..].trim();
+          let target = href.strip_prefix("mailto:").unwrap_or(&href);
+          if !target.is_empty() && !target.starts_with('#') && label != target {
+            out.push_str(&format!(" ({target})"));
+          }
+        }
+      },
+      _ => {},
+    }
+  }
+  if hidden.is_none() {
+    push_text(&mut out, rest);
+  }
+
+  tidy(&out)
+}
+
+/// Appends text content with entities decoded and whitespace runs folded into one space.
+fn push_text(out: &mut String, text: &str) {
+  let decoded = decode_entities(text);
+  let mut words = decoded.split_whitespace().peekable();
+  if words.peek().is_none() {
+    if !decoded.is_empty() && !out.ends_with(char::is_whitespace) {
+      out.push(' ');
+    }
+    return;
+  }
+
+  if decoded.starts_with(char::is_whitespace) && !out.ends_with(char::is_whitespace) {
+    out.push(' ');
+  }
+  let joined: Vec<&str> = words.collect();
+  out.push_str(&joined.join(" "));
+  if decoded.ends_with(char::is_whitespace) {
+    out.push(' ');
+  }
+}
+
+fn decode_entities(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  let mut rest = text;
+
+  while let Some(start) = rest.find('&') {
+    out.push_str(&rest[..start]);
+    rest = &rest[start..];
+
+    let decoded = rest.find(';').filter(|end| *end <= 10).and_then(|end| {
+      let entity = &rest[1..end];
+      let c = match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        "copy" => Some('©'),
+        _ => match entity.strip_prefix('#') {
+          Some(hex) if hex.starts_with(['x', 'X']) => u32::from_str_radix(&hex[1..], 16)
+            .ok()
+            .and_then(char::from_u32),
+          Some(dec) => dec.parse().ok().and_then(char::from_u32),
+          None => None,
+        },
+      };
+      c.map(|c| (c, end))
+    });
+
+    match decoded {
+      Some((c, end)) => {
+        out.push(c);
+        rest = &rest[end + 1..];
+      },
+      None => {
+        out.push('&');
+        rest = &rest[1..];
+      },
+    }
+  }
+  out.push_str(rest);
+
+  out
+}
+
+/// Value of `name="..."` in the inside of a tag.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+  let at = tag.find(&format!("{name}="))? + name.len() + 1;
+  let value = &tag[at..];
+  let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+  let value = &value[1..];
+
+  Some(decode_entities(&value[..value.find(quote)?]))
+}
+
+/// Trims every line and keeps at most one blank line between paragraphs.
+fn tidy(text: &str) -> String {
+  let mut lines: Vec<&str> = Vec::new();
+  for line in text.lines().map(str::trim) {
+    if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+      continue;
+    }
+    lines.push(line);
+  }
+  while lines.last().is_some_and(|last| last.is_empty()) {
+    lines.pop();
+  }
+
+  lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keeps_the_words_and_drops_the_markup() {
+    let html = r#"<html><head><style>p { color: red }</style></head>
+      <body><!-- logo --><svg><path d="M0"/></svg>
+        <h1>Reset   Your Password</h1>
+        <p>Hi <!-- -->duck<!-- -->,</p>
+        <p>Tom &amp; Jerry&#39;s code:<br/><strong>123456</strong></p>
+        <ul><li>One</li><li>Two</li></ul>
+      </body></html>"#;
+
+    assert_eq!(
+      from_html(html),
+      "Reset Your Password\n\nHi duck,\n\nTom & Jerry's code:\n123456\n\n- One\n- Two"
+    );
+  }
+
+  #[test]
+  fn links_keep_their_target() {
+    let html = r#"<p><a href="https://example.com/reset?a=1&amp;b=2">Reset password</a>
+      or write to <a href="mailto:support@example.com">support@example.com</a></p>"#;
+
+    assert_eq!(
+      from_html(html),
+      "Reset password (https://example.com/reset?a=1&b=2) or write to support@example.com"
+    );
+  }
+}
@@ -1,63 +1,93 @@
-use std::env;
+use std::sync::Arc;
 
+use ::redis::aio::ConnectionManager;
 use ::sqlx::PgPool;
 use actix_web::cookie::{time::Duration, Key};
+use base64::Engine;
 use dotenv::dotenv;
-use lettre::SmtpTransport;
 
 use actix_cors::Cors;
 use actix_identity::IdentityMiddleware;
 use actix_session::config::PersistentSession;
-use actix_session::{storage::RedisSessionStore, SessionMiddleware};
-use actix_web::{http::header, web, App, HttpServer};
+use actix_session::SessionMiddleware;
+use actix_web::{http::header, middleware::from_fn, web, App, HttpServer};
 
-use crate::email::connect_to_smtp;
+use crate::common::extractors::json_error_handler;
+use crate::common::functionalities::{geoip, password, signing};
+use crate::common::middlewares::{catch_panic, negotiate_locale};
+use crate::config::Config;
+use crate::email::mailer::{self, Mailer};
 
+mod admin;
 mod auth;
 mod common;
+mod config;
+mod dev;
 mod email;
 mod otp_code;
 mod redis;
 mod sqlx;
+mod subscribers;
 mod user;
 mod words;
 
 struct AppState {
   db: PgPool,
-  redis: RedisSessionStore,
-  mailer: SmtpTransport,
+  redis: ConnectionManager,
+  mailer: Arc<dyn Mailer>,
+  config: Config,
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
   dotenv().ok();
 
-  let secret_key = get_session_key();
+  let config = Config::load().unwrap_or_else(|e| {
+    eprintln!("{e}");
+    std::process::exit(1);
+  });
+  println!("🦆 Loaded the `{}` configuration.", config.env);
 
-  // Connect to the SMTP service
-  let mailer = connect_to_smtp().await;
+  let secret_key = session_key(&config.session.secret);
+  signing::init(&config.session.secret);
+  password::init(&config.password);
+  geoip::init(&config.geoip);
+
+  // Pick the mail backend
+  let mailer = mailer::from_config(&config.mail);
   // Connect to the database
-  let pool = sqlx::connect_sqlx().await;
+  let pool = sqlx::connect_sqlx(&config.database).await;
   // Create a Redis client
-  let redis_client = redis::connect_redis().await;
+  let redis_client = redis::connect_redis(&config.redis).await;
+  let redis_manager = redis::connect_redis_manager(&config.redis).await;
+
+  let state = web::Data::new(AppState {
+    db: pool.clone(),
+    redis: redis_manager,
+    mailer,
+    config: config.clone(),
+  });
+
+  // Soft-delete expired otp codes in the background
+  actix_rt::spawn(otp_code::service::OtpCodeService::run_cleanup(pool));
+  // Deliver queued emails in the background
+  actix_rt::spawn(email::outbox::EmailOutbox::run_worker(state.clone()));
 
-  let address: u16 = env::var("PORT")
-    .unwrap_or("8080".to_string())
-    .parse()
-    .expect("Invalid port");
+  let address = config.server.port;
+  if config.server.dev_routes {
+    println!("🦆 Dev routes are mounted under /v1/dev");
+  }
   println!("🦆 Starting server on http://localhost:{address}");
 
   HttpServer::new(move || {
+    let allowed = config.server.cors_origins.clone();
     let cors = Cors::default()
       .allowed_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"])
       .allowed_headers(vec![header::AUTHORIZATION, header::CONTENT_TYPE])
-      .allowed_origin_fn(|origin, _req_head| {
-        let allowed = [
-          "http://localhost:3001",
-          "http://localhost:3000", // Add if needed
-          "https://blog.gentleduck.com",
-        ];
-        allowed.contains(&origin.to_str().unwrap())
+      .allowed_origin_fn(move |origin, _req_head| {
+        origin
+          .to_str()
+          .is_ok_and(|origin| allowed.iter().any(|allowed| allowed == origin))
       })
       .supports_credentials()
       .max_age(3600);
@@ -66,35 +96,45 @@ async fn main() -> std::io::Result<()> {
       .wrap(cors)
       .wrap(
         SessionMiddleware::builder(redis_client.clone(), secret_key.clone())
-          .cookie_name("acme-session".to_string())
-          .cookie_domain(Some("localhost".to_string()))
-          .cookie_secure(false) // 👈 required for localhost without HTTPS
-          .session_lifecycle(PersistentSession::default().session_ttl(Duration::days(7)))
+          .cookie_name(config.session.cookie_name.clone())
+          .cookie_domain(config.session.cookie_domain.clone())
+          .cookie_secure(config.session.cookie_secure)
+          .session_lifecycle(
+            PersistentSession::default().session_ttl(Duration::days(config.session.ttl_days)),
+          )
           .build(),
         // .cookie_http_only(false)
       )
       .wrap(IdentityMiddleware::default())
+      .app_data(web::JsonConfig::default().error_handler(json_error_handler))
       // Add the logger middleware to the service
-      .app_data(web::Data::new(AppState {
-        db: pool.clone(),
-        redis: redis_client.clone(),
-        mailer: mailer.clone(),
-      }))
+      .app_data(state.clone())
       .service(
         web::scope("/v1")
           .configure(auth::config)
+          .configure(admin::config)
           .configure(user::config)
           .configure(otp_code::config)
-          .configure(words::config),
+          .configure(subscribers::config)
+          .configure(words::config)
+          .configure(|cfg| {
+            if config.server.dev_routes {
+              dev::config(cfg)
+            }
+          }),
       )
+      .wrap(from_fn(negotiate_locale))
+      .wrap(from_fn(catch_panic))
   })
   .bind(("0.0.0.0", address))?
   .run()
   .await
 }
 
-fn get_session_key() -> Key {
-  let secret = env::var("SESSION_SECRET").expect("SESSION_SECRET must be set");
-  let decoded = base64::decode(&secret).expect("Invalid base64 in SESSION_SECRET");
+/// Cookie key from the session secret, which `Config::load` has already checked.
+fn session_key(secret: &str) -> Key {
+  let decoded = base64::engine::general_purpose::STANDARD
+    .decode(secret)
+    .expect("Invalid base64 in SESSION_SECRET");
   Key::from(&decoded)
 }
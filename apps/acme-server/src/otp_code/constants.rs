@@ -1,16 +1,48 @@
 use std::{error::Error, fmt};
 
+use actix_web::http::StatusCode;
 use serde::{Deserialize, Serialize};
 
+use crate::common::i18n::Translate;
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Serialize, Deserialize)]
 pub enum OtpCodeMessage {
   // Success
   OtpCodeCreateSuccess,
+  OtpCodeVerifySuccess,
+  OtpCodeSigninRequestSuccess,
+  OtpCodeResendSuccess,
+  OtpCodeSigninSuccess,
+  OtpCodeTwoFactorRequired,
 
   // Error
   OtpCodeCreateFailed,
+  OtpCodeVerifyFailed,
+  OtpCodeNotFound,
+  OtpCodeInvalid,
+  OtpCodeExpired,
+  OtpCodeSendFailed,
+  OtpCodeSigninFailed,
+  OtpCodeMagicLinkInvalid,
+  OtpCodeTooManyAttempts,
+  OtpCodeResendCooldown,
+  OtpCodePurposeNotResendable,
+  OtpCodeCleanupFailed,
 }
 
+/// Wrong guesses allowed against a single code before it is burned.
+pub const MAX_OTP_VERIFY_ATTEMPTS: i32 = 5;
+/// Minimum time (seconds) between two codes for the same user and purpose.
+pub const OTP_RESEND_COOLDOWN_SECS: i64 = 60;
+/// How often (seconds) expired codes get soft-deleted.
+pub const OTP_CLEANUP_INTERVAL_SECS: u64 = 10 * 60;
+/// Signing purpose of code hashes, so they are useless without the server key.
+pub const OTP_HASH_PURPOSE: &str = "otp-code";
+
+/// Signing purpose of magic-link tokens.
+pub const MAGIC_LINK_PURPOSE: &str = "otp-magic-link";
+
 impl fmt::Display for OtpCodeMessage {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     write!(f, "{:?}", self)
@@ -18,3 +50,29 @@ impl fmt::Display for OtpCodeMessage {
 }
 
 impl Error for OtpCodeMessage {}
+
+// Only the code for now
+impl Translate for OtpCodeMessage {}
+
+impl OtpCodeMessage {
+  /// Status the message is answered with when it is an error.
+  pub fn status_code(&self) -> StatusCode {
+    use OtpCodeMessage::*;
+
+    match self {
+      OtpCodeNotFound => StatusCode::NOT_FOUND,
+      OtpCodeInvalid | OtpCodeExpired | OtpCodeMagicLinkInvalid | OtpCodeTwoFactorRequired => {
+        StatusCode::UNAUTHORIZED
+      },
+      OtpCodeTooManyAttempts | OtpCodeResendCooldown => StatusCode::TOO_MANY_REQUESTS,
+      OtpCodePurposeNotResendable => StatusCode::BAD_REQUEST,
+      OtpCodeCreateFailed | OtpCodeVerifyFailed | OtpCodeSendFailed | OtpCodeSigninFailed
+      | OtpCodeCleanupFailed => StatusCode::INTERNAL_SERVER_ERROR,
+      OtpCodeCreateSuccess
+      | OtpCodeVerifySuccess
+      | OtpCodeSigninRequestSuccess
+      | OtpCodeResendSuccess
+      | OtpCodeSigninSuccess => StatusCode::OK,
+    }
+  }
+}
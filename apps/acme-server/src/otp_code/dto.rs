@@ -0,0 +1,42 @@
+use serde::Deserialize;
+use validator::Validate;
+
+use crate::otp_code::types::OtpPurpose;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct OtpSigninRequestDto {
+  #[validate(
+    length(min = 1, max = 255, message = "ValidateEmailLength"),
+    email(message = "ValidateEmailShape")
+  )]
+  pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct OtpSigninDto {
+  #[validate(
+    length(min = 1, max = 255, message = "ValidateEmailLength"),
+    email(message = "ValidateEmailShape")
+  )]
+  pub email: String,
+
+  #[validate(length(min = 1, max = 6, message = "ValidateOtpCodeLength"))]
+  pub code: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct MagicLinkSigninDto {
+  #[validate(length(min = 1, max = 255, message = "ValidateMagicLinkTokenLength"))]
+  pub token: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct OtpResendDto {
+  #[validate(
+    length(min = 1, max = 255, message = "ValidateEmailLength"),
+    email(message = "ValidateEmailShape")
+  )]
+  pub email: String,
+
+  pub purpose: OtpPurpose,
+}
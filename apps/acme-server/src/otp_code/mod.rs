@@ -1,14 +1,304 @@
-use crate::common::generators;
 use actix_session::Session;
-use actix_web::{delete, get, patch, post, web, HttpResponse, Responder};
-use askama::Template;
-use chrono::{Datelike, Local};
-use lettre::{message::header::ContentType, Message, Transport};
+use actix_web::{http::StatusCode, post, web, HttpRequest, HttpResponse};
+use uuid::Uuid;
+
+use crate::{
+  auth::{
+    alerts::SignInAlertService, client_ip, sessions::SessionRegistry, throttle::LoginThrottle,
+  },
+  common::{
+    error::AppError,
+    extractors::ValidatedJson,
+    functionalities::{api_res::api_success, signing},
+  },
+  email::{
+    emails::{AccountVerificationEmail, ResetPasswordEmail, SignInCodeEmail},
+    outbox::EmailOutbox,
+  },
+  otp_code::{
+    constants::{OtpCodeMessage, MAGIC_LINK_PURPOSE},
+    dto::{MagicLinkSigninDto, OtpResendDto, OtpSigninDto, OtpSigninRequestDto},
+    service::OtpCodeService,
+    types::OtpPurpose,
+  },
+  user::{service::UserService, types::User},
+  AppState,
+};
 
 pub mod constants;
+mod dto;
 pub mod service;
 pub mod types;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
-  cfg.service(web::scope("/otp"));
+  cfg.service(
+    web::scope("/otp")
+      .service(otp_signin_request)
+      .service(otp_signin)
+      .service(otp_signin_magic_link)
+      .service(otp_resend),
+  );
+}
+
+#[post("/signin/request")]
+async fn otp_signin_request(
+  data: web::Data<AppState>,
+  credentials: ValidatedJson<OtpSigninRequestDto>,
+) -> Result<HttpResponse, AppError> {
+  let user = UserService::get_by_email(&data, &credentials.email)
+    .await
+    .map_err(|_| OtpCodeMessage::OtpCodeCreateFailed)?;
+
+  // Answer the same way whether or not the address exists, so it can't be probed
+  if let Some(user) = user
+    && let Err(e) = send_code(&data, &user, OtpPurpose::Login).await
+  {
+    eprintln!("Sign-in code failed: {:?}", e);
+  }
+
+  Ok(api_success::<(), OtpCodeMessage>(
+    StatusCode::OK,
+    (),
+    OtpCodeMessage::OtpCodeSigninRequestSuccess,
+  ))
+}
+
+#[post("/signin")]
+async fn otp_signin(
+  req: HttpRequest,
+  data: web::Data<AppState>,
+  session: Session,
+  credentials: ValidatedJson<OtpSigninDto>,
+) -> Result<HttpResponse, AppError> {
+  let ip = client_ip(&req);
+
+  LoginThrottle::check(&data, &credentials.email, &ip).await?;
+
+  let user = UserService::get_by_email(&data, &credentials.email)
+    .await
+    .map_err(|_| OtpCodeMessage::OtpCodeSigninFailed)?;
+
+  // An unknown address and an account without a live code answer the same, so the address can't
+  // be probed, and both count as a failed guess
+  let Some(user) = user else {
+    LoginThrottle::record_failure(&data, &credentials.email, &ip).await?;
+    return Err(OtpCodeMessage::OtpCodeInvalid.into());
+  };
+
+  match OtpCodeService::consume(&data, user.id, OtpPurpose::Login, &credentials.code).await {
+    Ok(_) => {},
+    Err(OtpCodeMessage::OtpCodeInvalid | OtpCodeMessage::OtpCodeNotFound) => {
+      LoginThrottle::record_failure(&data, &credentials.email, &ip).await?;
+      return Err(OtpCodeMessage::OtpCodeInvalid.into());
+    },
+    Err(e) => return Err(e.into()),
+  }
+
+  complete_signin(&data, &req, &session, user).await
+}
+
+#[post("/signin/magic-link")]
+async fn otp_signin_magic_link(
+  req: HttpRequest,
+  data: web::Data<AppState>,
+  session: Session,
+  credentials: ValidatedJson<MagicLinkSigninDto>,
+) -> Result<HttpResponse, AppError> {
+  let otp_id = match credentials.token.split_once('.') {
+    Some((otp_id, signature)) if signing::verify(MAGIC_LINK_PURPOSE, otp_id, signature) => {
+      Uuid::parse_str(otp_id).ok()
+    },
+    _ => None,
+  };
+
+  let otp_id = otp_id.ok_or(OtpCodeMessage::OtpCodeMagicLinkInvalid)?;
+  let otp = OtpCodeService::consume_by_id(&data, otp_id, OtpPurpose::Login).await?;
+
+  let user = UserService::me(&data, otp.user_id)
+    .await
+    .map_err(|_| OtpCodeMessage::OtpCodeSigninFailed)?;
+
+  complete_signin(&data, &req, &session, user).await
+}
+
+#[post("/resend")]
+async fn otp_resend(
+  data: web::Data<AppState>,
+  credentials: ValidatedJson<OtpResendDto>,
+) -> Result<HttpResponse, AppError> {
+  // Email verification is resent from the signed-in `/user/verify-email/resend` route
+  if !matches!(
+    credentials.purpose,
+    OtpPurpose::Login | OtpPurpose::ResetPassword
+  ) {
+    return Err(OtpCodeMessage::OtpCodePurposeNotResendable.into());
+  }
+
+  let user = UserService::get_by_email(&data, &credentials.email)
+    .await
+    .map_err(|_| OtpCodeMessage::OtpCodeCreateFailed)?;
+
+  // Same answer for unknown addresses and for a resend inside the cooldown, which only
+  // existing accounts can hit, so neither tells whether the address has an account
+  if let Some(user) = user {
+    match send_code(&data, &user, credentials.purpose).await {
+      Ok(_) | Err(OtpCodeMessage::OtpCodeResendCooldown) => {},
+      Err(e) => eprintln!("Otp resend failed: {:?}", e),
+    }
+  }
+
+  Ok(api_success::<(), OtpCodeMessage>(
+    StatusCode::OK,
+    (),
+    OtpCodeMessage::OtpCodeResendSuccess,
+  ))
+}
+
+/// Signs the user in, or parks them on the second factor when 2FA is on.
+async fn complete_signin(
+  data: &web::Data<AppState>,
+  req: &HttpRequest,
+  session: &Session,
+  user: User,
+) -> Result<HttpResponse, AppError> {
+  if user.totp_enabled_at.is_some() {
+    SessionRegistry::start_pending_two_factor(session, user.id)
+      .map_err(|_| OtpCodeMessage::OtpCodeSigninFailed)?;
+
+    return Ok(api_success::<(), OtpCodeMessage>(
+      StatusCode::OK,
+      (),
+      OtpCodeMessage::OtpCodeTwoFactorRequired,
+    ));
+  }
+
+  let sid = SessionRegistry::start(data, req, session, user.id)
+    .await
+    .map_err(|_| OtpCodeMessage::OtpCodeSigninFailed)?;
+  LoginThrottle::clear(data, &user.email).await;
+
+  SignInAlertService::record(data, req, &user, Some(&sid)).await;
+
+  Ok(api_success::<User, OtpCodeMessage>(
+    StatusCode::OK,
+    user,
+    OtpCodeMessage::OtpCodeSigninSuccess,
+  ))
+}
+
+/// Issues a code for `purpose` and emails it to the user with the matching template.
+pub async fn send_code(
+  data: &web::Data<AppState>,
+  user: &User,
+  purpose: OtpPurpose,
+) -> Result<(), OtpCodeMessage> {
+  let mut tx = data.db.begin().await.map_err(|e| {
+    println!("{:?}", e);
+    OtpCodeMessage::OtpCodeSendFailed
+  })?;
+
+  let (otp, code) = OtpCodeService::create(&mut tx, user.id, purpose).await?;
+
+  let (mail, recipient, locale) = (&data.config.mail, user.email.as_str(), user.locale());
+  let queued = match purpose {
+    OtpPurpose::Login => {
+      let otp_id = otp.id.to_string();
+      let token = format!("{otp_id}.{}", signing::sign(MAGIC_LINK_PURPOSE, &otp_id));
+      let email = SignInCodeEmail {
+        username: &user.username,
+        sign_in_code: &code,
+        sign_in_link: &data
+          .config
+          .links
+          .url(&format!("/signin/magic-link?token={token}")),
+      };
+      EmailOutbox::enqueue(&mut *tx, mail, recipient, locale, &email).await
+    },
+    OtpPurpose::ResetPassword => {
+      let email = ResetPasswordEmail {
+        username: &user.username,
+        reset_code: &code,
+        reset_link: &data.config.links.url(&format!(
+          "/reset-password?user_id={}&code={}",
+          user.id, code
+        )),
+      };
+      EmailOutbox::enqueue(&mut *tx, mail, recipient, locale, &email).await
+    },
+    OtpPurpose::VerifyEmail => {
+      let email = AccountVerificationEmail {
+        username: &user.username,
+        verification_code: &code,
+        verification_link: &data
+          .config
+          .links
+          .url(&format!("/verify-email?user_id={}&code={}", user.id, code)),
+      };
+      EmailOutbox::enqueue(&mut *tx, mail, recipient, locale, &email).await
+    },
+    OtpPurpose::DeleteAccount => return Err(OtpCodeMessage::OtpCodePurposeNotResendable),
+  };
+
+  queued.map_err(|e| {
+    println!("{:?}", e);
+    OtpCodeMessage::OtpCodeSendFailed
+  })?;
+
+  tx.commit().await.map_err(|e| {
+    println!("{:?}", e);
+    OtpCodeMessage::OtpCodeSendFailed
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::{test, App};
+  use serde_json::{json, Value};
+
+  use super::*;
+  use crate::common::{functionalities::password, testing};
+
+  #[actix_web::test]
+  #[ignore = "needs a migrated Postgres (TEST_DATABASE_URL) and Redis (TEST_REDIS_URL)"]
+  async fn unknown_address_answers_like_a_missing_code() {
+    let state = testing::state().await;
+    let app = test::init_service(
+      App::new()
+        .app_data(state.data.clone())
+        .service(web::scope("/v1").configure(config)),
+    )
+    .await;
+
+    let username = format!("duck-{}", Uuid::new_v4().simple());
+    let known = format!("{username}@example.com");
+    sqlx::query(
+      r#"
+        INSERT INTO users (username, email, first_name, last_name, password_hash)
+        VALUES ($1, $2, 'Duck', 'Tester', $3)
+      "#,
+    )
+    .bind(&username)
+    .bind(&known)
+    .bind(password::hash("correct horse battery").await.unwrap())
+    .execute(&state.data.db)
+    .await
+    .unwrap();
+
+    let peer = testing::peer_addr();
+    let mut answers = Vec::new();
+    for email in [known, format!("nobody-{}@example.com", Uuid::new_v4())] {
+      let req = test::TestRequest::post()
+        .uri("/v1/otp/signin")
+        .peer_addr(peer)
+        .set_json(json!({ "email": email, "code": "123456" }))
+        .to_request();
+      let res = test::call_service(&app, req).await;
+      let status = res.status();
+      let body: Value = test::read_body_json(res).await;
+      answers.push((status, body));
+    }
+
+    assert_eq!(answers[0].0, StatusCode::UNAUTHORIZED);
+    assert_eq!(answers[0], answers[1]);
+  }
 }
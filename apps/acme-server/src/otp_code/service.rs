@@ -1,34 +1,276 @@
+use std::time::Duration;
+
 use actix_web::web;
+use chrono::Utc;
+use sqlx::{PgConnection, PgPool};
 
 use crate::{
-  otp_code::{constants::OtpCodeMessage, types::OtpCode},
+  common::{functionalities::signing, generators},
+  otp_code::{
+    constants::{
+      OtpCodeMessage, MAX_OTP_VERIFY_ATTEMPTS, OTP_CLEANUP_INTERVAL_SECS, OTP_HASH_PURPOSE,
+      OTP_RESEND_COOLDOWN_SECS,
+    },
+    types::{OtpCode, OtpPurpose},
+  },
   AppState,
 };
 
 pub struct OtpCodeService;
 
 impl OtpCodeService {
+  fn hash(user_id: uuid::Uuid, code: &str) -> String {
+    signing::sign(OTP_HASH_PURPOSE, &format!("{user_id}:{code}"))
+  }
+
+  /// Issues a new code for `purpose` and returns it with the plain code, which is not stored.
+  ///
+  /// Refused while the previous code for the same purpose is younger than the resend cooldown;
+  /// otherwise the user's older codes for that purpose are deactivated. Runs on the caller's
+  /// transaction, so the email carrying the code can be queued with it.
   pub async fn create(
-    data: &web::Data<AppState>,
-    code: String,
+    tx: &mut PgConnection,
     user_id: uuid::Uuid,
-  ) -> Result<OtpCode, OtpCodeMessage> {
+    purpose: OtpPurpose,
+  ) -> Result<(OtpCode, String), OtpCodeMessage> {
+    let in_cooldown = sqlx::query_scalar::<_, bool>(
+      r#"
+        SELECT EXISTS (
+          SELECT 1
+          FROM otp_codes
+          WHERE user_id = $1::uuid
+            AND purpose = $2
+            AND deleted_at IS NULL
+            AND created_at > NOW() - make_interval(secs => $3)
+        )
+      "#,
+    )
+    .bind(user_id)
+    .bind(purpose)
+    .bind(OTP_RESEND_COOLDOWN_SECS as f64)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      OtpCodeMessage::OtpCodeCreateFailed
+    })?;
+
+    if in_cooldown {
+      return Err(OtpCodeMessage::OtpCodeResendCooldown);
+    }
+
+    sqlx::query(
+      r#"
+        UPDATE otp_codes
+        SET is_active = false, updated_at = NOW()
+        WHERE user_id = $1::uuid
+          AND purpose = $2
+          AND is_active = true
+      "#,
+    )
+    .bind(user_id)
+    .bind(purpose)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      OtpCodeMessage::OtpCodeCreateFailed
+    })?;
+
+    let code = generators::code::generate_code(6);
+
     let otp = sqlx::query_as::<_, OtpCode>(
       r#"
-        INSERT INTO otp_codes (code, user_id, expires_at)
-        VALUES ($1, $2::uuid, NOW() + INTERVAL '5 minutes')
+        INSERT INTO otp_codes (code_hash, user_id, purpose, expires_at)
+        VALUES ($1, $2::uuid, $3, NOW() + make_interval(secs => $4))
         RETURNING *; 
       "#,
     )
-    .bind(&code)
-    .bind(&user_id)
-    .fetch_one(&data.db)
+    .bind(Self::hash(user_id, &code))
+    .bind(user_id)
+    .bind(purpose)
+    .bind(purpose.ttl_secs() as f64)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|e| {
       println!("{:?}", e);
       OtpCodeMessage::OtpCodeCreateFailed
     })?;
 
+    Ok((otp, code))
+  }
+
+  /// Checks `code` against the user's latest active code for `purpose` and marks it consumed.
+  pub async fn consume(
+    data: &web::Data<AppState>,
+    user_id: uuid::Uuid,
+    purpose: OtpPurpose,
+    code: &str,
+  ) -> Result<OtpCode, OtpCodeMessage> {
+    let otp = Self::verify(data, user_id, purpose, code).await?;
+
+    Self::deactivate(data, otp.id, purpose)
+      .await?
+      .ok_or(OtpCodeMessage::OtpCodeInvalid)
+  }
+
+  /// Checks `code` against the user's latest active code for `purpose`, leaving it active so the
+  /// caller can consume it with `consume_by_id` once the rest of the request holds up.
+  ///
+  /// Every check counts against the code, which is burned after too many wrong guesses.
+  pub async fn verify(
+    data: &web::Data<AppState>,
+    user_id: uuid::Uuid,
+    purpose: OtpPurpose,
+    code: &str,
+  ) -> Result<OtpCode, OtpCodeMessage> {
+    let otp = sqlx::query_as::<_, OtpCode>(
+      r#"
+        SELECT *
+        FROM otp_codes
+        WHERE user_id = $1::uuid
+          AND purpose = $2
+          AND is_active = true
+          AND deleted_at IS NULL
+        ORDER BY created_at DESC
+        LIMIT 1;
+      "#,
+    )
+    .bind(user_id)
+    .bind(purpose)
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      OtpCodeMessage::OtpCodeVerifyFailed
+    })?
+    .ok_or(OtpCodeMessage::OtpCodeNotFound)?;
+
+    if otp.expires_at <= Utc::now() {
+      return Err(OtpCodeMessage::OtpCodeExpired);
+    }
+
+    // The attempt is counted before the code is compared, so concurrent guesses can't all slip in
+    // under the limit
+    let Some(attempts) = Self::reserve_attempt(data, otp.id).await? else {
+      Self::deactivate(data, otp.id, purpose).await?;
+      return Err(OtpCodeMessage::OtpCodeTooManyAttempts);
+    };
+
+    let expected = format!("{user_id}:{code}");
+    if !signing::verify(OTP_HASH_PURPOSE, &expected, &otp.code_hash) {
+      if attempts >= MAX_OTP_VERIFY_ATTEMPTS {
+        Self::deactivate(data, otp.id, purpose).await?;
+        return Err(OtpCodeMessage::OtpCodeTooManyAttempts);
+      }
+      return Err(OtpCodeMessage::OtpCodeInvalid);
+    }
+
+    Ok(otp)
+  }
+
+  /// Consumes the code with the given id, as referenced by a signed magic link or returned by
+  /// `verify`.
+  pub async fn consume_by_id(
+    data: &web::Data<AppState>,
+    otp_id: uuid::Uuid,
+    purpose: OtpPurpose,
+  ) -> Result<OtpCode, OtpCodeMessage> {
+    let otp = Self::deactivate(data, otp_id, purpose)
+      .await?
+      .ok_or(OtpCodeMessage::OtpCodeInvalid)?;
+
+    if otp.expires_at <= Utc::now() {
+      return Err(OtpCodeMessage::OtpCodeExpired);
+    }
+
     Ok(otp)
   }
+
+  /// Soft-deletes every expired code.
+  pub async fn cleanup_expired(db: &PgPool) -> Result<u64, OtpCodeMessage> {
+    let result = sqlx::query(
+      r#"
+        UPDATE otp_codes
+        SET is_active = false, deleted_at = NOW(), updated_at = NOW()
+        WHERE expires_at <= NOW()
+          AND deleted_at IS NULL
+      "#,
+    )
+    .execute(db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      OtpCodeMessage::OtpCodeCleanupFailed
+    })?;
+
+    Ok(result.rows_affected())
+  }
+
+  /// Runs `cleanup_expired` forever, meant to be spawned once at startup.
+  pub async fn run_cleanup(db: PgPool) {
+    let mut interval = tokio::time::interval(Duration::from_secs(OTP_CLEANUP_INTERVAL_SECS));
+
+    loop {
+      interval.tick().await;
+
+      match Self::cleanup_expired(&db).await {
+        Ok(0) => {},
+        Ok(count) => println!("🦆 Soft-deleted {count} expired otp codes."),
+        Err(e) => eprintln!("Otp cleanup failed: {:?}", e),
+      }
+    }
+  }
+
+  /// Takes one of the code's attempts; `None` once they are used up or the code is gone.
+  async fn reserve_attempt(
+    data: &web::Data<AppState>,
+    otp_id: uuid::Uuid,
+  ) -> Result<Option<i32>, OtpCodeMessage> {
+    sqlx::query_scalar::<_, i32>(
+      r#"
+        UPDATE otp_codes
+        SET attempts = attempts + 1, updated_at = NOW()
+        WHERE id = $1::uuid
+          AND attempts < $2
+          AND is_active = true
+        RETURNING attempts;
+      "#,
+    )
+    .bind(otp_id)
+    .bind(MAX_OTP_VERIFY_ATTEMPTS)
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      OtpCodeMessage::OtpCodeVerifyFailed
+    })
+  }
+
+  // Guarded on `is_active` so two concurrent requests can't both use the same code
+  async fn deactivate(
+    data: &web::Data<AppState>,
+    otp_id: uuid::Uuid,
+    purpose: OtpPurpose,
+  ) -> Result<Option<OtpCode>, OtpCodeMessage> {
+    sqlx::query_as::<_, OtpCode>(
+      r#"
+        UPDATE otp_codes
+        SET is_active = false, updated_at = NOW()
+        WHERE id = $1::uuid
+          AND purpose = $2
+          AND is_active = true
+          AND deleted_at IS NULL
+        RETURNING *;
+      "#,
+    )
+    .bind(otp_id)
+    .bind(purpose)
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      OtpCodeMessage::OtpCodeVerifyFailed
+    })
+  }
 }
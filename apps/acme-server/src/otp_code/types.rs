@@ -7,11 +7,37 @@ use uuid::Uuid;
 pub struct OtpCode {
   #[serde(skip_serializing)]
   pub id: Uuid,
-  pub code: String,
+  #[serde(skip_serializing)]
+  pub code_hash: String,
   pub user_id: Uuid,
+  pub purpose: OtpPurpose,
+  pub attempts: i32,
   pub is_active: bool,
   pub expires_at: DateTime<Utc>,
   pub created_at: DateTime<Utc>,
   pub updated_at: DateTime<Utc>,
   pub deleted_at: Option<DateTime<Utc>>,
 }
+
+/// What a code was issued for; a code only works for its own purpose.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "otp_purpose", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum OtpPurpose {
+  ResetPassword,
+  VerifyEmail,
+  Login,
+  DeleteAccount,
+}
+
+impl OtpPurpose {
+  /// How long (seconds) a code issued for this purpose stays valid.
+  pub fn ttl_secs(&self) -> i64 {
+    match self {
+      OtpPurpose::ResetPassword => 15 * 60,
+      OtpPurpose::VerifyEmail => 24 * 60 * 60,
+      OtpPurpose::Login => 5 * 60,
+      OtpPurpose::DeleteAccount => 15 * 60,
+    }
+  }
+}
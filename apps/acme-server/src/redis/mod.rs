@@ -1,14 +1,26 @@
-use std::env;
-
 use actix_session::storage::RedisSessionStore;
+use redis::aio::ConnectionManager;
+
+use crate::config::RedisConfig;
 
-pub async fn connect_redis() -> RedisSessionStore {
-  let redis_client =
-    RedisSessionStore::new(env::var("REDIS_URL").unwrap_or("redis://localhost:6380".to_string()))
-      .await
-      .unwrap();
+pub async fn connect_redis(config: &RedisConfig) -> RedisSessionStore {
+  let redis_client = RedisSessionStore::new(config.url.as_str())
+    .await
+    .expect("Could not connect to redis");
 
   println!("🦆 Redis client created and ready.");
 
   redis_client
 }
+
+/// Plain Redis connection used for counters and indexes that live outside the session store.
+pub async fn connect_redis_manager(config: &RedisConfig) -> ConnectionManager {
+  let client = redis::Client::open(config.url.as_str()).expect("Invalid REDIS_URL");
+  let manager = ConnectionManager::new(client)
+    .await
+    .expect("Could not connect to redis");
+
+  println!("🦆 Redis connection manager created and ready.");
+
+  manager
+}
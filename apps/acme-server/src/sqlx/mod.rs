@@ -3,17 +3,14 @@
  *
  */
 
-use std::env;
-
 use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 
-pub async fn connect_sqlx() -> Pool<Postgres> {
-  let database_url =
-    env::var("DATABASE_URL").expect("DATABASE_URL must be set in .env or environment");
+use crate::config::DatabaseConfig;
 
+pub async fn connect_sqlx(config: &DatabaseConfig) -> Pool<Postgres> {
   let pool = PgPoolOptions::new()
-    .max_connections(5)
-    .connect(&database_url)
+    .max_connections(config.max_connections)
+    .connect(&config.url)
     .await
     .expect("Could not connect to the database");
   println!("🦆 Database pool created and ready.");
@@ -0,0 +1,109 @@
+use std::{error::Error, fmt};
+
+use actix_web::http::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::common::i18n::{Locale, Translate};
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Serialize, Deserialize)]
+pub enum SubscriberMessage {
+  // Success
+  SubscriberSubscribeSuccess,
+  SubscriberConfirmSuccess,
+  SubscriberUnsubscribeSuccess,
+  SubscriberGetSuccess,
+  SubscriberNewsletterQueueSuccess,
+
+  // Error
+  SubscriberNotFound,
+  SubscriberTokenInvalid,
+  SubscriberSubscribeFailed,
+  SubscriberConfirmFailed,
+  SubscriberUnsubscribeFailed,
+  SubscriberGetFailed,
+  SubscriberNewsletterQueueFailed,
+}
+
+/// Signing purpose of the double opt-in links.
+pub const SUBSCRIBER_CONFIRM_PURPOSE: &str = "subscriber-confirm";
+/// Signing purpose of unsubscribe links; they never expire.
+pub const SUBSCRIBER_UNSUBSCRIBE_PURPOSE: &str = "subscriber-unsubscribe";
+/// How long a confirmation link works.
+pub const SUBSCRIBER_CONFIRM_TTL_SECS: i64 = 7 * 24 * 60 * 60;
+/// Minimum time (seconds) between two confirmation emails to the same address, so the public
+/// endpoint can't be used to flood someone's inbox.
+pub const SUBSCRIBER_CONFIRM_COOLDOWN_SECS: i64 = 10 * 60;
+
+impl fmt::Display for SubscriberMessage {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?}", self)
+  }
+}
+
+impl Error for SubscriberMessage {}
+
+impl SubscriberMessage {
+  /// Status the message is answered with when it is an error.
+  pub fn status_code(&self) -> StatusCode {
+    use SubscriberMessage::*;
+
+    match self {
+      SubscriberNotFound => StatusCode::NOT_FOUND,
+      SubscriberTokenInvalid => StatusCode::BAD_REQUEST,
+      SubscriberSubscribeFailed
+      | SubscriberConfirmFailed
+      | SubscriberUnsubscribeFailed
+      | SubscriberGetFailed
+      | SubscriberNewsletterQueueFailed => StatusCode::INTERNAL_SERVER_ERROR,
+      SubscriberSubscribeSuccess
+      | SubscriberConfirmSuccess
+      | SubscriberUnsubscribeSuccess
+      | SubscriberGetSuccess
+      | SubscriberNewsletterQueueSuccess => StatusCode::OK,
+    }
+  }
+}
+
+impl Translate for SubscriberMessage {
+  fn translate(&self, locale: Locale) -> Option<&'static str> {
+    use SubscriberMessage::*;
+
+    Some(match (self, locale) {
+      (SubscriberSubscribeSuccess, Locale::En) => {
+        "Check your inbox and confirm your email address to subscribe."
+      },
+      (SubscriberSubscribeSuccess, Locale::Es) => {
+        "Revisa tu bandeja de entrada y confirma tu dirección de correo para suscribirte."
+      },
+      (SubscriberConfirmSuccess, Locale::En) => "You're subscribed to the newsletter.",
+      (SubscriberConfirmSuccess, Locale::Es) => "Ya estás suscrito a la newsletter.",
+      (SubscriberUnsubscribeSuccess, Locale::En) => "You won't get the newsletter anymore.",
+      (SubscriberUnsubscribeSuccess, Locale::Es) => "Ya no recibirás la newsletter.",
+      (SubscriberGetSuccess, Locale::En) => "Here is your newsletter subscription.",
+      (SubscriberGetSuccess, Locale::Es) => "Esta es tu suscripción a la newsletter.",
+      (SubscriberNewsletterQueueSuccess, Locale::En) => "The newsletter is on its way.",
+      (SubscriberNewsletterQueueSuccess, Locale::Es) => "La newsletter está en camino.",
+      (SubscriberNotFound, Locale::En) => "You are not subscribed to the newsletter.",
+      (SubscriberNotFound, Locale::Es) => "No estás suscrito a la newsletter.",
+      (SubscriberTokenInvalid, Locale::En) => "This link is not valid or has expired.",
+      (SubscriberTokenInvalid, Locale::Es) => "Este enlace no es válido o ha caducado.",
+      (SubscriberSubscribeFailed, Locale::En) => "We couldn't subscribe you. Please try again.",
+      (SubscriberSubscribeFailed, Locale::Es) => "No hemos podido suscribirte. Inténtalo de nuevo.",
+      (SubscriberConfirmFailed, Locale::En) => {
+        "We couldn't confirm your subscription. Please try again."
+      },
+      (SubscriberConfirmFailed, Locale::Es) => {
+        "No hemos podido confirmar tu suscripción. Inténtalo de nuevo."
+      },
+      (SubscriberUnsubscribeFailed, Locale::En) => "We couldn't unsubscribe you. Please try again.",
+      (SubscriberUnsubscribeFailed, Locale::Es) => {
+        "No hemos podido darte de baja. Inténtalo de nuevo."
+      },
+      (SubscriberGetFailed, Locale::En) => "We couldn't load your subscription.",
+      (SubscriberGetFailed, Locale::Es) => "No hemos podido cargar tu suscripción.",
+      (SubscriberNewsletterQueueFailed, Locale::En) => "The newsletter couldn't be queued.",
+      (SubscriberNewsletterQueueFailed, Locale::Es) => "No se ha podido encolar la newsletter.",
+    })
+  }
+}
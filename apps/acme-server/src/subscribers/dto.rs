@@ -0,0 +1,23 @@
+use serde::Deserialize;
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SubscribeDto {
+  #[validate(
+    length(min = 1, max = 255, message = "ValidateEmailLength"),
+    email(message = "ValidateEmailShape")
+  )]
+  pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ConfirmSubscriptionDto {
+  #[validate(length(min = 1, max = 512, message = "ValidateSubscriberTokenLength"))]
+  pub token: String,
+}
+
+/// The token rides in the query string: one-click clients POST a fixed form body (RFC 8058).
+#[derive(Debug, Deserialize)]
+pub struct UnsubscribeQuery {
+  pub token: String,
+}
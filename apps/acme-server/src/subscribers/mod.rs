@@ -0,0 +1,140 @@
+use actix_web::{delete, get, http::StatusCode, middleware::from_fn, post, web, HttpResponse};
+
+use crate::{
+  auth::{
+    guard::{auth_middleware, require_permission, AuthUser, RequireScope},
+    types::{Permission, TokenScope},
+  },
+  common::{error::AppError, extractors::ValidatedJson, functionalities::api_res::api_success},
+  subscribers::{
+    constants::SubscriberMessage,
+    dto::{ConfirmSubscriptionDto, SubscribeDto, UnsubscribeQuery},
+    service::SubscriberService,
+    types::{Subscriber, SubscriberStatus},
+  },
+  user::service::UserService,
+  AppState,
+};
+
+pub mod constants;
+mod dto;
+pub mod service;
+pub mod types;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+  cfg.service(
+    web::scope("/subscribers")
+      .service(subscriber_subscribe)
+      .service(subscriber_confirm)
+      .service(subscriber_unsubscribe)
+      .service(
+        web::scope("/me")
+          .wrap(require_permission(Permission::ProfileRead))
+          .wrap(from_fn(auth_middleware))
+          .service(subscriber_me)
+          .service(subscriber_me_subscribe)
+          .service(subscriber_me_unsubscribe),
+      ),
+  );
+}
+
+/// Public sign-up form. Answers the same whatever the address, so it can't be used to find out
+/// who is subscribed.
+#[post("/subscribe")]
+async fn subscriber_subscribe(
+  data: web::Data<AppState>,
+  credentials: ValidatedJson<SubscribeDto>,
+) -> Result<HttpResponse, AppError> {
+  SubscriberService::subscribe(&data, &credentials.email, None).await?;
+
+  Ok(api_success::<(), SubscriberMessage>(
+    StatusCode::OK,
+    (),
+    SubscriberMessage::SubscriberSubscribeSuccess,
+  ))
+}
+
+#[post("/confirm")]
+async fn subscriber_confirm(
+  data: web::Data<AppState>,
+  credentials: ValidatedJson<ConfirmSubscriptionDto>,
+) -> Result<HttpResponse, AppError> {
+  SubscriberService::confirm(&data, &credentials.token).await?;
+
+  Ok(api_success::<(), SubscriberMessage>(
+    StatusCode::OK,
+    (),
+    SubscriberMessage::SubscriberConfirmSuccess,
+  ))
+}
+
+/// Both the `List-Unsubscribe` target mail clients POST to and what the unsubscribe page calls.
+#[post("/unsubscribe")]
+async fn subscriber_unsubscribe(
+  data: web::Data<AppState>,
+  query: web::Query<UnsubscribeQuery>,
+) -> Result<HttpResponse, AppError> {
+  SubscriberService::unsubscribe(&data, &query.token).await?;
+
+  Ok(api_success::<(), SubscriberMessage>(
+    StatusCode::OK,
+    (),
+    SubscriberMessage::SubscriberUnsubscribeSuccess,
+  ))
+}
+
+#[get("", wrap = "RequireScope(TokenScope::UserRead)")]
+async fn subscriber_me(
+  auth: AuthUser,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let subscriber = SubscriberService::for_user(&data, auth.id).await?;
+
+  Ok(api_success::<Subscriber, SubscriberMessage>(
+    StatusCode::OK,
+    subscriber,
+    SubscriberMessage::SubscriberGetSuccess,
+  ))
+}
+
+/// Subscribes the user's own address; no confirmation needed once it is verified.
+#[post(
+  "",
+  wrap = "RequireScope(TokenScope::UserWrite)",
+  wrap = "require_permission(Permission::ProfileWrite)"
+)]
+async fn subscriber_me_subscribe(
+  auth: AuthUser,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let user = UserService::me(&data, auth.id).await?;
+  let subscriber = SubscriberService::subscribe(&data, &user.email, Some(&user)).await?;
+
+  let message = match subscriber.status {
+    SubscriberStatus::Active => SubscriberMessage::SubscriberConfirmSuccess,
+    _ => SubscriberMessage::SubscriberSubscribeSuccess,
+  };
+  Ok(api_success::<Subscriber, SubscriberMessage>(
+    StatusCode::OK,
+    subscriber,
+    message,
+  ))
+}
+
+#[delete(
+  "",
+  wrap = "RequireScope(TokenScope::UserWrite)",
+  wrap = "require_permission(Permission::ProfileWrite)"
+)]
+async fn subscriber_me_unsubscribe(
+  auth: AuthUser,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let subscriber = SubscriberService::unsubscribe_user(&data, auth.id).await?;
+
+  Ok(api_success::<Subscriber, SubscriberMessage>(
+    StatusCode::OK,
+    subscriber,
+    SubscriberMessage::SubscriberUnsubscribeSuccess,
+  ))
+}
@@ -0,0 +1,377 @@
+use actix_web::web;
+use chrono::{Duration, Utc};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+  common::{functionalities::signing, i18n::Locale},
+  email::{
+    emails::{NewsletterConfirmationEmail, NewsletterEmail},
+    outbox::EmailOutbox,
+  },
+  subscribers::{
+    constants::{
+      SubscriberMessage, SUBSCRIBER_CONFIRM_COOLDOWN_SECS, SUBSCRIBER_CONFIRM_PURPOSE,
+      SUBSCRIBER_CONFIRM_TTL_SECS, SUBSCRIBER_UNSUBSCRIBE_PURPOSE,
+    },
+    types::{Subscriber, SubscriberStatus},
+  },
+  user::types::User,
+  AppState,
+};
+
+/// Newsletter subscriptions with double opt-in.
+///
+/// Links in the emails carry signed tokens instead of anything stored: `{id}.{expires_at}` to
+/// confirm, and `{id}` alone to unsubscribe, which has to keep working for as long as the
+/// subscriber has old issues in their inbox.
+pub struct SubscriberService;
+
+impl SubscriberService {
+  /// Subscribes `email`. A signed-in user's own verified address is active right away; any other
+  /// address stays pending until the link in the confirmation email is followed.
+  pub async fn subscribe(
+    data: &web::Data<AppState>,
+    email: &str,
+    user: Option<&User>,
+  ) -> Result<Subscriber, SubscriberMessage> {
+    let email = email.trim().to_lowercase();
+    let locale = user.map_or_else(Locale::current, User::locale);
+    let verified = user.is_some_and(|user| {
+      user.email_verified_at.is_some() && user.email.eq_ignore_ascii_case(&email)
+    });
+    let status = match verified {
+      true => SubscriberStatus::Active,
+      false => SubscriberStatus::Pending,
+    };
+
+    let mut tx = data.db.begin().await.map_err(|e| {
+      println!("{:?}", e);
+      SubscriberMessage::SubscriberSubscribeFailed
+    })?;
+
+    // A user has one subscription, the one for their current address
+    if let Some(user) = user {
+      sqlx::query(
+        r#"
+          UPDATE subscribers
+          SET user_id = NULL, updated_at = NOW()
+          WHERE user_id = $1::uuid AND email <> $2
+        "#,
+      )
+      .bind(user.id)
+      .bind(&email)
+      .execute(&mut *tx)
+      .await
+      .map_err(|e| {
+        println!("{:?}", e);
+        SubscriberMessage::SubscriberSubscribeFailed
+      })?;
+    }
+
+    // Subscribing again never downgrades an active subscription, so anyone can ask for an
+    // address without being able to pause it
+    let subscriber = sqlx::query_as::<_, Subscriber>(
+      r#"
+        INSERT INTO subscribers (email, user_id, locale, status, confirmed_at)
+        VALUES ($1, $2::uuid, $3, $4, CASE WHEN $4 = 'active'::subscriber_status THEN NOW() END)
+        ON CONFLICT (email) DO UPDATE
+        SET user_id = COALESCE(EXCLUDED.user_id, subscribers.user_id),
+            locale = CASE
+              WHEN subscribers.status = 'active' THEN subscribers.locale
+              ELSE EXCLUDED.locale
+            END,
+            status = CASE
+              WHEN subscribers.status = 'active' THEN subscribers.status
+              ELSE EXCLUDED.status
+            END,
+            confirmed_at = CASE
+              WHEN subscribers.status <> 'active' AND EXCLUDED.status = 'active' THEN NOW()
+              ELSE subscribers.confirmed_at
+            END,
+            unsubscribed_at = CASE
+              WHEN subscribers.status = 'unsubscribed' THEN NULL
+              ELSE subscribers.unsubscribed_at
+            END,
+            updated_at = NOW()
+        RETURNING *
+      "#,
+    )
+    .bind(&email)
+    .bind(user.map(|user| user.id))
+    .bind(locale.tag())
+    .bind(status)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      SubscriberMessage::SubscriberSubscribeFailed
+    })?;
+
+    let cooled_down = subscriber.confirmation_sent_at.is_none_or(|sent_at| {
+      Utc::now() - sent_at >= Duration::seconds(SUBSCRIBER_CONFIRM_COOLDOWN_SECS)
+    });
+    if subscriber.status == SubscriberStatus::Pending && cooled_down {
+      Self::send_confirmation(&mut tx, data, &subscriber).await?;
+    }
+
+    tx.commit().await.map_err(|e| {
+      println!("{:?}", e);
+      SubscriberMessage::SubscriberSubscribeFailed
+    })?;
+
+    Ok(subscriber)
+  }
+
+  async fn send_confirmation(
+    tx: &mut PgConnection,
+    data: &web::Data<AppState>,
+    subscriber: &Subscriber,
+  ) -> Result<(), SubscriberMessage> {
+    let expires_at = Utc::now().timestamp() + SUBSCRIBER_CONFIRM_TTL_SECS;
+    let token = Self::token(
+      SUBSCRIBER_CONFIRM_PURPOSE,
+      format!("{}.{expires_at}", subscriber.id),
+    );
+    let email = NewsletterConfirmationEmail {
+      confirm_link: &data
+        .config
+        .links
+        .url(&format!("/newsletter/confirm?token={token}")),
+    };
+
+    EmailOutbox::enqueue(
+      &mut *tx,
+      &data.config.mail,
+      &subscriber.email,
+      subscriber.locale(),
+      &email,
+    )
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      SubscriberMessage::SubscriberSubscribeFailed
+    })?;
+
+    sqlx::query(
+      r#"
+        UPDATE subscribers
+        SET confirmation_sent_at = NOW()
+        WHERE id = $1::uuid
+      "#,
+    )
+    .bind(subscriber.id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      SubscriberMessage::SubscriberSubscribeFailed
+    })?;
+
+    Ok(())
+  }
+
+  /// Target of the link in the confirmation email.
+  pub async fn confirm(data: &web::Data<AppState>, token: &str) -> Result<(), SubscriberMessage> {
+    let payload = Self::verify(SUBSCRIBER_CONFIRM_PURPOSE, token)?;
+    let (id, expires_at) = payload
+      .split_once('.')
+      .ok_or(SubscriberMessage::SubscriberTokenInvalid)?;
+    let id = Uuid::parse_str(id).map_err(|_| SubscriberMessage::SubscriberTokenInvalid)?;
+    if expires_at.parse::<i64>().unwrap_or_default() < Utc::now().timestamp() {
+      return Err(SubscriberMessage::SubscriberTokenInvalid);
+    }
+
+    // An old link must not undo an unsubscribe
+    sqlx::query_scalar::<_, Uuid>(
+      r#"
+        UPDATE subscribers
+        SET status = 'active',
+            confirmed_at = CASE WHEN status = 'pending' THEN NOW() ELSE confirmed_at END,
+            updated_at = NOW()
+        WHERE id = $1::uuid AND status <> 'unsubscribed'
+        RETURNING id
+      "#,
+    )
+    .bind(id)
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      SubscriberMessage::SubscriberConfirmFailed
+    })?
+    .ok_or(SubscriberMessage::SubscriberTokenInvalid)?;
+
+    Ok(())
+  }
+
+  /// Target of the unsubscribe link and of one-click unsubscribe; asking twice is fine.
+  pub async fn unsubscribe(
+    data: &web::Data<AppState>,
+    token: &str,
+  ) -> Result<(), SubscriberMessage> {
+    let id = Self::verify(SUBSCRIBER_UNSUBSCRIBE_PURPOSE, token)?;
+    let id = Uuid::parse_str(id).map_err(|_| SubscriberMessage::SubscriberTokenInvalid)?;
+
+    sqlx::query(
+      r#"
+        UPDATE subscribers
+        SET status = 'unsubscribed',
+            unsubscribed_at = COALESCE(unsubscribed_at, NOW()),
+            updated_at = NOW()
+        WHERE id = $1::uuid
+      "#,
+    )
+    .bind(id)
+    .execute(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      SubscriberMessage::SubscriberUnsubscribeFailed
+    })?;
+
+    Ok(())
+  }
+
+  pub async fn for_user(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+  ) -> Result<Subscriber, SubscriberMessage> {
+    sqlx::query_as::<_, Subscriber>(
+      r#"
+        SELECT *
+        FROM subscribers
+        WHERE user_id = $1::uuid
+      "#,
+    )
+    .bind(user_id)
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      SubscriberMessage::SubscriberGetFailed
+    })?
+    .ok_or(SubscriberMessage::SubscriberNotFound)
+  }
+
+  pub async fn unsubscribe_user(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+  ) -> Result<Subscriber, SubscriberMessage> {
+    sqlx::query_as::<_, Subscriber>(
+      r#"
+        UPDATE subscribers
+        SET status = 'unsubscribed',
+            unsubscribed_at = COALESCE(unsubscribed_at, NOW()),
+            updated_at = NOW()
+        WHERE user_id = $1::uuid
+        RETURNING *
+      "#,
+    )
+    .bind(user_id)
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      SubscriberMessage::SubscriberUnsubscribeFailed
+    })?
+    .ok_or(SubscriberMessage::SubscriberNotFound)
+  }
+
+  /// Queues the newsletter for every active subscriber, each with their own unsubscribe links;
+  /// returns how many were queued.
+  pub async fn queue_newsletter(data: &web::Data<AppState>) -> Result<usize, SubscriberMessage> {
+    let mut tx = data.db.begin().await.map_err(|e| {
+      println!("{:?}", e);
+      SubscriberMessage::SubscriberNewsletterQueueFailed
+    })?;
+
+    let subscribers = sqlx::query_as::<_, Subscriber>(
+      r#"
+        SELECT *
+        FROM subscribers
+        WHERE status = 'active'
+        ORDER BY created_at
+      "#,
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      SubscriberMessage::SubscriberNewsletterQueueFailed
+    })?;
+
+    for subscriber in &subscribers {
+      let token = Self::token(SUBSCRIBER_UNSUBSCRIBE_PURPOSE, subscriber.id.to_string());
+      let email = NewsletterEmail {
+        unsubscribe_link: &data
+          .config
+          .links
+          .url(&format!("/newsletter/unsubscribe?token={token}")),
+        one_click_unsubscribe: &data
+          .config
+          .links
+          .api(&format!("/v1/subscribers/unsubscribe?token={token}")),
+      };
+
+      EmailOutbox::enqueue(
+        &mut *tx,
+        &data.config.mail,
+        &subscriber.email,
+        subscriber.locale(),
+        &email,
+      )
+      .await
+      .map_err(|e| {
+        println!("{:?}", e);
+        SubscriberMessage::SubscriberNewsletterQueueFailed
+      })?;
+    }
+
+    tx.commit().await.map_err(|e| {
+      println!("{:?}", e);
+      SubscriberMessage::SubscriberNewsletterQueueFailed
+    })?;
+
+    Ok(subscribers.len())
+  }
+
+  fn token(purpose: &str, payload: String) -> String {
+    let signature = signing::sign(purpose, &payload);
+    format!("{payload}.{signature}")
+  }
+
+  /// The payload of a token made by `token` for `purpose`.
+  fn verify<'t>(purpose: &str, token: &'t str) -> Result<&'t str, SubscriberMessage> {
+    let (payload, signature) = token
+      .rsplit_once('.')
+      .ok_or(SubscriberMessage::SubscriberTokenInvalid)?;
+    if !signing::verify(purpose, payload, signature) {
+      return Err(SubscriberMessage::SubscriberTokenInvalid);
+    }
+
+    Ok(payload)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn tokens_only_verify_for_their_purpose() {
+    signing::init("test-secret");
+    let id = Uuid::new_v4().to_string();
+
+    let token = SubscriberService::token(SUBSCRIBER_UNSUBSCRIBE_PURPOSE, id.clone());
+
+    assert_eq!(
+      SubscriberService::verify(SUBSCRIBER_UNSUBSCRIBE_PURPOSE, &token).ok(),
+      Some(id.as_str())
+    );
+    assert!(SubscriberService::verify(SUBSCRIBER_CONFIRM_PURPOSE, &token).is_err());
+    assert!(
+      SubscriberService::verify(SUBSCRIBER_UNSUBSCRIBE_PURPOSE, &token.replace('-', "")).is_err()
+    );
+  }
+}
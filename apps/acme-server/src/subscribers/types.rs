@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::common::i18n::Locale;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "subscriber_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriberStatus {
+  /// Waiting for the address to be confirmed; gets nothing but the confirmation email.
+  Pending,
+  Active,
+  Unsubscribed,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Subscriber {
+  pub id: Uuid,
+  pub email: String,
+  pub user_id: Option<Uuid>,
+  pub status: SubscriberStatus,
+  pub locale: String,
+  pub confirmation_sent_at: Option<DateTime<Utc>>,
+  pub confirmed_at: Option<DateTime<Utc>>,
+  pub unsubscribed_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+impl Subscriber {
+  /// Language of the emails to this subscriber, English for anything we don't speak.
+  pub fn locale(&self) -> Locale {
+    Locale::from_tag(&self.locale).unwrap_or_default()
+  }
+}
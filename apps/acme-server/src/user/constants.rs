@@ -1,7 +1,13 @@
 use std::{error::Error, fmt};
 
+use actix_web::http::StatusCode;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+  common::i18n::{Locale, Translate},
+  user::password_policy::PasswordPolicyViolation,
+};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum UserMessage {
   // Success
@@ -10,12 +16,17 @@ pub enum UserMessage {
   UserDeleteSuccess,
   UserGetProfileSuccess,
   UserResetPasswordSuccess,
+  UserResetPasswordConfirmSuccess,
   UserGetSuccess,
+  UserVerifyEmailSuccess,
+  UserVerifyEmailResendSuccess,
 
   // Error
   AuthInsertUserIdSessionFailed,
   AuthGetSessionUserIdSessionFailed,
   UserCreateFailed,
+  UserUsernameTaken,
+  UserEmailTaken,
   UserNotFound,
   UserGetProfileFailed,
   UserDeleteFailed,
@@ -27,6 +38,11 @@ pub enum UserMessage {
   UserResetPasswordEmailFailed,
   UserUpdateProfileEmailFailed,
   UserCreateEmailFailed,
+  UserVerifyEmailFailed,
+  UserEmailAlreadyVerified,
+  UserPasswordPolicyViolated(Vec<PasswordPolicyViolation>),
+  UserPasswordPolicyCheckFailed,
+  UserRevokeSessionsFailed,
 }
 
 impl fmt::Display for UserMessage {
@@ -36,3 +52,151 @@ impl fmt::Display for UserMessage {
 }
 
 impl Error for UserMessage {}
+
+impl UserMessage {
+  /// Status the message is answered with when it is an error.
+  pub fn status_code(&self) -> StatusCode {
+    use UserMessage::*;
+
+    match self {
+      UserNotFound => StatusCode::NOT_FOUND,
+      UserUsernameTaken | UserEmailTaken | UserEmailAlreadyVerified => StatusCode::CONFLICT,
+      InvalidUuid | NothingToUpdate => StatusCode::BAD_REQUEST,
+      UserPasswordPolicyViolated(_) => StatusCode::UNPROCESSABLE_ENTITY,
+      AuthInsertUserIdSessionFailed
+      | AuthGetSessionUserIdSessionFailed
+      | UserCreateFailed
+      | UserGetProfileFailed
+      | UserDeleteFailed
+      | UserUpdateFailed
+      | UserResetPasswordFailed
+      | UserGetFailed
+      | UserResetPasswordEmailFailed
+      | UserUpdateProfileEmailFailed
+      | UserCreateEmailFailed
+      | UserVerifyEmailFailed
+      | UserPasswordPolicyCheckFailed
+      | UserRevokeSessionsFailed => StatusCode::INTERNAL_SERVER_ERROR,
+      UserCreateSuccess
+      | UserUpdateSuccess
+      | UserDeleteSuccess
+      | UserGetProfileSuccess
+      | UserResetPasswordSuccess
+      | UserResetPasswordConfirmSuccess
+      | UserGetSuccess
+      | UserVerifyEmailSuccess
+      | UserVerifyEmailResendSuccess => StatusCode::OK,
+    }
+  }
+}
+
+impl Translate for UserMessage {
+  fn translate(&self, locale: Locale) -> Option<&'static str> {
+    use UserMessage::*;
+
+    Some(match (self, locale) {
+      (UserCreateSuccess, Locale::En) => "Your account was created.",
+      (UserCreateSuccess, Locale::Es) => "Tu cuenta se ha creado.",
+      (UserUpdateSuccess, Locale::En) => "Your profile was updated.",
+      (UserUpdateSuccess, Locale::Es) => "Tu perfil se ha actualizado.",
+      (UserDeleteSuccess, Locale::En) => "Your account was deleted.",
+      (UserDeleteSuccess, Locale::Es) => "Tu cuenta se ha eliminado.",
+      (UserGetProfileSuccess, Locale::En) => "Here is your profile.",
+      (UserGetProfileSuccess, Locale::Es) => "Este es tu perfil.",
+      (UserResetPasswordSuccess, Locale::En) => {
+        "If the account exists, a reset code is on its way."
+      },
+      (UserResetPasswordSuccess, Locale::Es) => {
+        "Si la cuenta existe, te hemos enviado un código para restablecer la contraseña."
+      },
+      (UserResetPasswordConfirmSuccess, Locale::En) => "Your password was reset.",
+      (UserResetPasswordConfirmSuccess, Locale::Es) => "Tu contraseña se ha restablecido.",
+      (UserGetSuccess, Locale::En) => "Here is the user.",
+      (UserGetSuccess, Locale::Es) => "Este es el usuario.",
+      (UserVerifyEmailSuccess, Locale::En) => "Your email address is verified.",
+      (UserVerifyEmailSuccess, Locale::Es) => "Tu correo electrónico está verificado.",
+      (UserVerifyEmailResendSuccess, Locale::En) => "A new verification code is on its way.",
+      (UserVerifyEmailResendSuccess, Locale::Es) => {
+        "Te hemos enviado un nuevo código de verificación."
+      },
+      (AuthInsertUserIdSessionFailed, Locale::En) => "We couldn't sign you in. Please try again.",
+      (AuthInsertUserIdSessionFailed, Locale::Es) => {
+        "No hemos podido iniciar tu sesión. Inténtalo de nuevo."
+      },
+      (AuthGetSessionUserIdSessionFailed, Locale::En) => {
+        "We couldn't read your session. Please sign in again."
+      },
+      (AuthGetSessionUserIdSessionFailed, Locale::Es) => {
+        "No hemos podido leer tu sesión. Vuelve a iniciar sesión."
+      },
+      (UserCreateFailed, Locale::En) => "We couldn't create your account. Please try again.",
+      (UserCreateFailed, Locale::Es) => "No hemos podido crear tu cuenta. Inténtalo de nuevo.",
+      (UserUsernameTaken, Locale::En) => "That username is already taken.",
+      (UserUsernameTaken, Locale::Es) => "Ese nombre de usuario ya está en uso.",
+      (UserEmailTaken, Locale::En) => "An account with that email address already exists.",
+      (UserEmailTaken, Locale::Es) => "Ya existe una cuenta con ese correo electrónico.",
+      (UserNotFound, Locale::En) => "We couldn't find that user.",
+      (UserNotFound, Locale::Es) => "No hemos encontrado ese usuario.",
+      (UserGetProfileFailed, Locale::En) => "We couldn't load your profile. Please try again.",
+      (UserGetProfileFailed, Locale::Es) => "No hemos podido cargar tu perfil. Inténtalo de nuevo.",
+      (UserDeleteFailed, Locale::En) => "We couldn't delete your account. Please try again.",
+      (UserDeleteFailed, Locale::Es) => "No hemos podido eliminar tu cuenta. Inténtalo de nuevo.",
+      (InvalidUuid, Locale::En) => "That id is not valid.",
+      (InvalidUuid, Locale::Es) => "Ese identificador no es válido.",
+      (UserUpdateFailed, Locale::En) => "We couldn't update your profile. Please try again.",
+      (UserUpdateFailed, Locale::Es) => "No hemos podido actualizar tu perfil. Inténtalo de nuevo.",
+      (NothingToUpdate, Locale::En) => "There is nothing to update.",
+      (NothingToUpdate, Locale::Es) => "No hay nada que actualizar.",
+      (UserResetPasswordFailed, Locale::En) => "We couldn't reset your password. Please try again.",
+      (UserResetPasswordFailed, Locale::Es) => {
+        "No hemos podido restablecer tu contraseña. Inténtalo de nuevo."
+      },
+      (UserGetFailed, Locale::En) => "We couldn't load that user. Please try again.",
+      (UserGetFailed, Locale::Es) => "No hemos podido cargar ese usuario. Inténtalo de nuevo.",
+      (UserResetPasswordEmailFailed, Locale::En) => {
+        "We couldn't send the reset email. Please try again."
+      },
+      (UserResetPasswordEmailFailed, Locale::Es) => {
+        "No hemos podido enviar el correo para restablecer la contraseña. Inténtalo de nuevo."
+      },
+      (UserUpdateProfileEmailFailed, Locale::En) => {
+        "We couldn't send the profile update email. Please try again."
+      },
+      (UserUpdateProfileEmailFailed, Locale::Es) => {
+        "No hemos podido enviar el correo de actualización del perfil. Inténtalo de nuevo."
+      },
+      (UserCreateEmailFailed, Locale::En) => {
+        "We couldn't send the welcome email. Please try again."
+      },
+      (UserCreateEmailFailed, Locale::Es) => {
+        "No hemos podido enviar el correo de bienvenida. Inténtalo de nuevo."
+      },
+      (UserVerifyEmailFailed, Locale::En) => {
+        "We couldn't verify your email address. Please try again."
+      },
+      (UserVerifyEmailFailed, Locale::Es) => {
+        "No hemos podido verificar tu correo electrónico. Inténtalo de nuevo."
+      },
+      (UserRevokeSessionsFailed, Locale::En) => {
+        "Your password was changed, but we couldn't sign out your other sessions. Please try again."
+      },
+      (UserRevokeSessionsFailed, Locale::Es) => {
+        "Tu contraseña se ha cambiado, pero no hemos podido cerrar tus otras sesiones. Inténtalo de nuevo."
+      },
+      (UserEmailAlreadyVerified, Locale::En) => "Your email address is already verified.",
+      (UserEmailAlreadyVerified, Locale::Es) => "Tu correo electrónico ya está verificado.",
+      (UserPasswordPolicyViolated(_), Locale::En) => {
+        "That password doesn't meet the password rules."
+      },
+      (UserPasswordPolicyViolated(_), Locale::Es) => {
+        "Esa contraseña no cumple las reglas de contraseñas."
+      },
+      (UserPasswordPolicyCheckFailed, Locale::En) => {
+        "We couldn't check your password. Please try again."
+      },
+      (UserPasswordPolicyCheckFailed, Locale::Es) => {
+        "No hemos podido comprobar tu contraseña. Inténtalo de nuevo."
+      },
+    })
+  }
+}
@@ -6,7 +6,7 @@ pub struct CreateUserDto {
   #[validate(length(min = 1, max = 255, message = "ValidateUsernameLength"))]
   pub username: String,
 
-  #[validate(length(min = 8, message = "ValidatePasswordMinLength"))]
+  #[validate(length(min = 1, message = "ValidatePasswordLength"))]
   pub password: String,
 
   #[validate(
@@ -24,12 +24,10 @@ pub struct CreateUserDto {
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct UpdateUserDto {
-  pub user_id: uuid::Uuid,
-
   #[validate(length(min = 1, max = 255, message = "ValidateUsernameLength"))]
   pub username: Option<String>,
 
-  #[validate(length(min = 8, message = "ValidatePasswordMinLength"))]
+  #[validate(length(min = 1, message = "ValidatePasswordLength"))]
   pub password: Option<String>,
 
   #[validate(
@@ -43,11 +41,36 @@ pub struct UpdateUserDto {
 
   #[validate(length(min = 1, max = 255, message = "ValidateLastNameLength"))]
   pub last_name: Option<String>,
+
+  /// Language for emails, e.g. `es`; kept in `settings`.
+  #[validate(custom = "crate::common::validators::validate_locale")]
+  pub locale: Option<String>,
+
+  /// IANA zone dates in emails are shown in, e.g. `Europe/Madrid`; kept in `settings`.
+  #[validate(custom = "crate::common::validators::validate_timezone")]
+  pub timezone: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
-pub struct DeleteUserDto {
+pub struct ResetPasswordDto {
   pub user_id: uuid::Uuid,
 }
 
-pub type ResetPasswordDto = DeleteUserDto;
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordConfirmDto {
+  pub user_id: uuid::Uuid,
+
+  #[validate(length(min = 1, max = 6, message = "ValidateOtpCodeLength"))]
+  pub code: String,
+
+  #[validate(length(min = 1, message = "ValidatePasswordLength"))]
+  pub password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct VerifyEmailDto {
+  pub user_id: uuid::Uuid,
+
+  #[validate(length(min = 1, max = 6, message = "ValidateOtpCodeLength"))]
+  pub code: String,
+}
@@ -1,29 +1,33 @@
-use crate::common::functionalities::api_res::{api_error, api_success};
-use crate::common::generators;
-use crate::email::send_reset_email;
+use crate::common::error::AppError;
+use crate::common::extractors::ValidatedJson;
+use crate::common::functionalities::api_res::api_success;
 use crate::otp_code;
 use crate::otp_code::constants::OtpCodeMessage;
+use crate::otp_code::types::OtpPurpose;
 use actix_session::Session;
 use actix_web::http::StatusCode;
-use actix_web::{delete, get, patch, post, web, Responder};
-use askama::Template;
-use chrono::{Datelike, Local};
+use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
 
-mod constants;
+pub mod constants;
 mod dto;
-mod service;
+pub mod password_policy;
+pub mod service;
 pub mod types;
 
 use actix_web::middleware::from_fn;
 
-pub fn config(cfg: &mut web::ServiceConfig) -> () {
+pub fn config(cfg: &mut web::ServiceConfig) {
   cfg.service(
     web::scope("/user")
       .service(user_create)
       .service(user_reset_password)
+      .service(user_reset_password_confirm)
+      .service(user_verify_email)
       .service(
         web::scope("")
+          .wrap(require_permission(Permission::ProfileRead))
           .wrap(from_fn(auth_middleware))
+          .service(user_verify_email_resend)
           .service(user_update)
           .service(user_me)
           .service(user_delete),
@@ -32,11 +36,17 @@ pub fn config(cfg: &mut web::ServiceConfig) -> () {
 }
 
 use crate::{
-  auth::guard::auth_middleware,
-  email::emails::{ResetPasswordEmail, UpdateUserEmail, WelcomeEmail},
+  auth::{
+    guard::{auth_middleware, require_permission, AuthUser, RequireScope},
+    sessions::SessionRegistry,
+    tokens::TokenService,
+    types::{Permission, TokenScope},
+  },
   user::{
     constants::UserMessage,
-    dto::{CreateUserDto, DeleteUserDto, ResetPasswordDto, UpdateUserDto},
+    dto::{
+      CreateUserDto, ResetPasswordConfirmDto, ResetPasswordDto, UpdateUserDto, VerifyEmailDto,
+    },
     service::UserService,
     types::User,
   },
@@ -45,134 +55,198 @@ use crate::{
 
 #[post("/create")]
 async fn user_create(
-  creditials: web::Json<CreateUserDto>,
+  req: HttpRequest,
+  creditials: ValidatedJson<CreateUserDto>,
   session: Session,
   data: web::Data<AppState>,
-) -> impl Responder {
-  let user = match UserService::create(&data, creditials.into_inner()).await {
-    Ok(user) => user,
-    Err(e) => return api_error::<User, UserMessage>(StatusCode::BAD_REQUEST, e),
-  };
-
-  session
-    .insert("user_id", user.id.to_string())
-    .expect(&UserMessage::AuthInsertUserIdSessionFailed.to_string());
-
-  let email = WelcomeEmail {
-    username: &user.username,
-    dashboard_url: "http://localhost:3000/dashboard/",
-    current_year: chrono::Utc::now().year(),
-  };
-
-  let html = email.render().unwrap();
-
-  if let Err(e) = send_reset_email(&data, &user.email, &html).await {
-    eprintln!("Email send failed: {:?}", e);
-    return api_error::<User, UserMessage>(
-      StatusCode::BAD_REQUEST,
-      UserMessage::UserCreateEmailFailed,
-    );
+) -> Result<HttpResponse, AppError> {
+  let user = UserService::create(&data, creditials.into_inner()).await?;
+
+  SessionRegistry::start(&data, &req, &session, user.id)
+    .await
+    .map_err(|_| UserMessage::AuthInsertUserIdSessionFailed)?;
+
+  // The account works without it, the user can ask for another code later
+  if let Err(e) = otp_code::send_code(&data, &user, OtpPurpose::VerifyEmail).await {
+    eprintln!("Verification email failed: {:?}", e);
+  }
+
+  Ok(api_success::<User, UserMessage>(
+    StatusCode::OK,
+    user,
+    UserMessage::UserCreateSuccess,
+  ))
+}
+
+#[post("/verify-email")]
+async fn user_verify_email(
+  data: web::Data<AppState>,
+  credentials: ValidatedJson<VerifyEmailDto>,
+) -> Result<HttpResponse, AppError> {
+  otp_code::service::OtpCodeService::consume(
+    &data,
+    credentials.user_id,
+    OtpPurpose::VerifyEmail,
+    &credentials.code,
+  )
+  .await?;
+
+  let user = UserService::mark_email_verified(&data, credentials.user_id).await?;
+
+  Ok(api_success::<User, UserMessage>(
+    StatusCode::OK,
+    user,
+    UserMessage::UserVerifyEmailSuccess,
+  ))
+}
+
+#[post(
+  "/verify-email/resend",
+  wrap = "RequireScope(TokenScope::UserWrite)",
+  wrap = "require_permission(Permission::ProfileWrite)"
+)]
+async fn user_verify_email_resend(
+  auth: AuthUser,
+  data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let user = UserService::me(&data, auth.id).await?;
+
+  if user.email_verified_at.is_some() {
+    return Err(UserMessage::UserEmailAlreadyVerified.into());
   }
 
-  api_success::<User, UserMessage>(StatusCode::OK, user, UserMessage::UserCreateSuccess)
+  otp_code::send_code(&data, &user, OtpPurpose::VerifyEmail).await?;
+
+  Ok(api_success::<(), UserMessage>(
+    StatusCode::OK,
+    (),
+    UserMessage::UserVerifyEmailResendSuccess,
+  ))
 }
 
-#[patch("/update")]
+#[patch(
+  "/update",
+  wrap = "RequireScope(TokenScope::UserWrite)",
+  wrap = "require_permission(Permission::ProfileWrite)"
+)]
 async fn user_update(
+  auth: AuthUser,
   data: web::Data<AppState>,
-  credentials: web::Json<UpdateUserDto>,
-) -> impl Responder {
-  let (user, updated_fields) = match UserService::update(&data, credentials.into_inner()).await {
-    Ok(user) => user,
-    Err(e) => return api_error::<User, UserMessage>(StatusCode::BAD_REQUEST, e),
-  };
-
-  let date = Local::now().format("%B %-d, %Y at %-I:%M %p").to_string();
-  let email = UpdateUserEmail {
-    username: &user.username,
-    view_profile_link: "https://example.com/profile",
-    updated_fields,
-    last_update: &date,
-  };
-
-  let html = email.render().unwrap();
-
-  if let Err(e) = send_reset_email(&data, &user.email, &html).await {
-    eprintln!("Email send failed: {:?}", e);
-    return api_error::<User, UserMessage>(
-      StatusCode::BAD_REQUEST,
-      UserMessage::UserUpdateProfileEmailFailed,
-    );
+  credentials: ValidatedJson<UpdateUserDto>,
+) -> Result<HttpResponse, AppError> {
+  let (user, updated_fields) = UserService::update(
+    &data,
+    auth.id,
+    auth.sid.as_deref(),
+    credentials.into_inner(),
+  )
+  .await?;
+
+  let email_changed = updated_fields.iter().any(|field| field.label == "email");
+
+  if email_changed && let Err(e) = otp_code::send_code(&data, &user, OtpPurpose::VerifyEmail).await
+  {
+    eprintln!("Verification email failed: {:?}", e);
   }
 
-  api_success::<User, UserMessage>(StatusCode::OK, user, UserMessage::UserUpdateSuccess)
+  Ok(api_success::<User, UserMessage>(
+    StatusCode::OK,
+    user,
+    UserMessage::UserUpdateSuccess,
+  ))
 }
 
 #[post("/reset-password")]
 async fn user_reset_password(
   data: web::Data<AppState>,
-  credentials: web::Json<ResetPasswordDto>,
-) -> impl Responder {
-  let user = match UserService::get(&data, credentials.into_inner()).await {
-    Ok(user) => user,
-    Err(e) => return api_error::<User, UserMessage>(StatusCode::BAD_REQUEST, e),
-  };
-
-  let code = generators::code::generate_code(6);
-  let otp = match otp_code::service::OtpCodeService::create(&data, code.clone(), user.id).await {
-    Ok(otp) => otp,
-    Err(e) => return api_error::<User, OtpCodeMessage>(StatusCode::BAD_REQUEST, e),
-  };
-
-  let email = ResetPasswordEmail {
-    username: &user.username,
-    reset_code: &otp.code,
-    reset_link: &format!("https://example.com/reset-password?code={}", otp.code),
-  };
-
-  let html = match email.render() {
-    Ok(html) => html,
-    Err(_) => {
-      return api_error::<User, UserMessage>(
-        StatusCode::BAD_REQUEST,
-        UserMessage::UserResetPasswordFailed,
-      );
+  credentials: ValidatedJson<ResetPasswordDto>,
+) -> Result<HttpResponse, AppError> {
+  let user = UserService::get(&data, credentials.into_inner()).await?;
+
+  match otp_code::send_code(&data, &user, OtpPurpose::ResetPassword).await {
+    Ok(_) => {},
+    Err(OtpCodeMessage::OtpCodeSendFailed) => {
+      return Err(UserMessage::UserResetPasswordEmailFailed.into());
     },
-  };
-
-  if let Err(e) = send_reset_email(&data, &user.email, &html).await {
-    eprintln!("Email send failed: {:?}", e);
-    return api_error::<User, UserMessage>(
-      StatusCode::BAD_REQUEST,
-      UserMessage::UserResetPasswordEmailFailed,
-    );
+    Err(e) => return Err(e.into()),
   }
 
-  api_success::<User, UserMessage>(StatusCode::OK, user, UserMessage::UserResetPasswordSuccess)
+  Ok(api_success::<User, UserMessage>(
+    StatusCode::OK,
+    user,
+    UserMessage::UserResetPasswordSuccess,
+  ))
 }
 
-#[delete("/delete")]
-async fn user_delete(
+#[post("/reset-password/confirm")]
+async fn user_reset_password_confirm(
   data: web::Data<AppState>,
-  credentials: web::Json<DeleteUserDto>,
-) -> impl Responder {
-  match UserService::delete(data, credentials.into_inner()).await {
-    Ok(_) => api_success::<(), UserMessage>(StatusCode::OK, (), UserMessage::UserDeleteSuccess),
-    Err(e) => api_error::<User, UserMessage>(StatusCode::BAD_REQUEST, e),
-  }
+  credentials: ValidatedJson<ResetPasswordConfirmDto>,
+) -> Result<HttpResponse, AppError> {
+  let credentials = credentials.into_inner();
+
+  // The code is checked before the password so the policy's answers (reused, breached...) are
+  // only given to whoever holds the code, and it is only consumed once the password is accepted
+  let otp = otp_code::service::OtpCodeService::verify(
+    &data,
+    credentials.user_id,
+    OtpPurpose::ResetPassword,
+    &credentials.code,
+  )
+  .await?;
+
+  UserService::check_password(&data, credentials.user_id, &credentials.password).await?;
+
+  otp_code::service::OtpCodeService::consume_by_id(&data, otp.id, OtpPurpose::ResetPassword)
+    .await?;
+
+  let user = UserService::set_password(&data, credentials.user_id, &credentials.password).await?;
+
+  // Whoever knew the old password must not stay signed in, so the reset isn't reported as done
+  // until they are out
+  SessionRegistry::revoke_all(&data, user.id)
+    .await
+    .map_err(|_| UserMessage::UserRevokeSessionsFailed)?;
+  TokenService::revoke_all(&data, user.id)
+    .await
+    .map_err(|_| UserMessage::UserRevokeSessionsFailed)?;
+
+  Ok(api_success::<User, UserMessage>(
+    StatusCode::OK,
+    user,
+    UserMessage::UserResetPasswordConfirmSuccess,
+  ))
 }
 
-#[get("/me")]
-async fn user_me(data: web::Data<AppState>, session: Session) -> impl Responder {
-  let user_id = session
-    .get::<String>("user_id")
-    .expect(&UserMessage::AuthGetSessionUserIdSessionFailed.to_string())
-    .unwrap();
+#[delete(
+  "/delete",
+  wrap = "RequireScope(TokenScope::UserWrite)",
+  wrap = "require_permission(Permission::ProfileWrite)"
+)]
+async fn user_delete(
+  auth: AuthUser,
+  data: web::Data<AppState>,
+  session: Session,
+) -> Result<HttpResponse, AppError> {
+  UserService::delete(&data, auth.id).await?;
+
+  let _ = SessionRegistry::revoke_all(&data, auth.id).await;
+  session.purge();
+
+  Ok(api_success::<(), UserMessage>(
+    StatusCode::OK,
+    (),
+    UserMessage::UserDeleteSuccess,
+  ))
+}
 
-  let user = match UserService::me(data, user_id).await {
-    Ok(user) => user,
-    Err(e) => return api_error::<User, UserMessage>(StatusCode::BAD_REQUEST, e),
-  };
+#[get("/me", wrap = "RequireScope(TokenScope::UserRead)")]
+async fn user_me(auth: AuthUser, data: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+  let user = UserService::me(&data, auth.id).await?;
 
-  api_success::<User, UserMessage>(StatusCode::OK, user, UserMessage::UserGetProfileSuccess)
+  Ok(api_success::<User, UserMessage>(
+    StatusCode::OK,
+    user,
+    UserMessage::UserGetProfileSuccess,
+  ))
 }
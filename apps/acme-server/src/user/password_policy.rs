@@ -0,0 +1,209 @@
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::{
+  common::functionalities::password, config::PasswordConfig, user::constants::UserMessage, AppState,
+};
+
+/// One reason a password was refused, for the client to show next to the field.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "reason")]
+pub enum PasswordPolicyViolation {
+  TooShort {
+    min: usize,
+  },
+  TooLong {
+    max: usize,
+  },
+  TooWeak {
+    score: u8,
+    min_score: u8,
+    warning: Option<String>,
+    suggestions: Vec<String>,
+  },
+  ContainsUsername,
+  ContainsEmail,
+  RecentlyUsed {
+    history: i64,
+  },
+  Breached {
+    count: u64,
+  },
+}
+
+pub struct PasswordPolicyService;
+
+impl PasswordPolicyService {
+  /// Checks a new password for the account named `username`/`email`; `user_id` is `None` while
+  /// the account is being created and has no history yet.
+  pub async fn check(
+    data: &web::Data<AppState>,
+    plain: &str,
+    username: &str,
+    email: &str,
+    user_id: Option<Uuid>,
+  ) -> Result<(), UserMessage> {
+    let policy = &data.config.password;
+    let mut violations = Vec::new();
+    let length = plain.chars().count();
+
+    if length < policy.min_length {
+      violations.push(PasswordPolicyViolation::TooShort {
+        min: policy.min_length,
+      });
+    }
+    if length > policy.max_length {
+      // Not worth scoring or hashing
+      violations.push(PasswordPolicyViolation::TooLong {
+        max: policy.max_length,
+      });
+      return Err(UserMessage::UserPasswordPolicyViolated(violations));
+    }
+
+    let lowered = plain.to_lowercase();
+    let mailbox = email.split('@').next().unwrap_or_default();
+    if !username.is_empty() && lowered.contains(&username.to_lowercase()) {
+      violations.push(PasswordPolicyViolation::ContainsUsername);
+    }
+    if !mailbox.is_empty() && lowered.contains(&mailbox.to_lowercase()) {
+      violations.push(PasswordPolicyViolation::ContainsEmail);
+    }
+
+    let entropy = zxcvbn::zxcvbn(plain, &[username, email, mailbox]);
+    let score = u8::from(entropy.score());
+    if score < policy.min_score {
+      let feedback = entropy.feedback();
+      violations.push(PasswordPolicyViolation::TooWeak {
+        score,
+        min_score: policy.min_score,
+        warning: feedback.and_then(|f| f.warning()).map(|w| w.to_string()),
+        suggestions: feedback
+          .map(|f| f.suggestions().iter().map(|s| s.to_string()).collect())
+          .unwrap_or_default(),
+      });
+    }
+
+    if let Some(count) = Self::breach_count(policy, plain).await {
+      violations.push(PasswordPolicyViolation::Breached { count });
+    }
+
+    if let Some(user_id) = user_id
+      && Self::recently_used(data, user_id, plain).await?
+    {
+      violations.push(PasswordPolicyViolation::RecentlyUsed {
+        history: policy.history_size,
+      });
+    }
+
+    if violations.is_empty() {
+      Ok(())
+    } else {
+      Err(UserMessage::UserPasswordPolicyViolated(violations))
+    }
+  }
+
+  /// Remembers a hash the user just set, keeping only the last `history_size`.
+  pub async fn remember<'e, E>(
+    executor: E,
+    policy: &PasswordConfig,
+    user_id: Uuid,
+    password_hash: &str,
+  ) where
+    E: Executor<'e, Database = Postgres>,
+  {
+    if let Err(e) = sqlx::query(
+      r#"
+        WITH inserted AS (
+          INSERT INTO password_history (user_id, password_hash)
+          VALUES ($1::uuid, $2)
+        )
+        DELETE FROM password_history
+        WHERE user_id = $1::uuid
+          AND id NOT IN (
+            SELECT id
+            FROM password_history
+            WHERE user_id = $1::uuid
+            ORDER BY created_at DESC
+            LIMIT GREATEST($3 - 1, 0)
+          )
+      "#,
+    )
+    .bind(user_id)
+    .bind(password_hash)
+    .bind(policy.history_size)
+    .execute(executor)
+    .await
+    {
+      println!("{:?}", e);
+    }
+  }
+
+  /// Whether `plain` matches the current password or one of the remembered ones.
+  async fn recently_used(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+    plain: &str,
+  ) -> Result<bool, UserMessage> {
+    let history_size = data.config.password.history_size;
+    if history_size <= 0 {
+      return Ok(false);
+    }
+
+    // The current hash covers accounts from before the history was kept
+    let hashes = sqlx::query_scalar::<_, String>(
+      r#"
+        SELECT password_hash
+        FROM users
+        WHERE id = $1::uuid
+        UNION
+        (
+          SELECT password_hash
+          FROM password_history
+          WHERE user_id = $1::uuid
+          ORDER BY created_at DESC
+          LIMIT $2
+        )
+      "#,
+    )
+    .bind(user_id)
+    .bind(history_size)
+    .fetch_all(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      UserMessage::UserPasswordPolicyCheckFailed
+    })?;
+
+    for hash in &hashes {
+      if password::verify(plain, hash).await.unwrap_or(false) {
+        return Ok(true);
+      }
+    }
+
+    Ok(false)
+  }
+
+  /// How often `plain` shows up in the local breach corpus, looked up by SHA-1 prefix.
+  async fn breach_count(policy: &PasswordConfig, plain: &str) -> Option<u64> {
+    let digest = hex::encode_upper(Sha1::digest(plain.as_bytes()));
+    let (prefix, suffix) = digest.split_at(5);
+
+    let range = tokio::fs::read_to_string(format!("{}/{prefix}.txt", policy.breached_dir))
+      .await
+      .ok()?;
+
+    range
+      .lines()
+      .find_map(|line| {
+        let (candidate, count) = line.trim().split_once(':')?;
+        candidate
+          .eq_ignore_ascii_case(suffix)
+          .then(|| count.parse().unwrap_or(1))
+      })
+      // Padded range files list made-up suffixes with a zero count
+      .filter(|count| *count > 0)
+  }
+}
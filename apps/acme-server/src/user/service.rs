@@ -1,16 +1,37 @@
 use actix_web::web;
+use chrono::{Datelike, Utc};
 
 use crate::{
-  email::emails::UpdatedField,
+  auth::{roles::RoleService, sessions::SessionRegistry, tokens::TokenService, types::Role},
+  common::{error::unique_violation, functionalities::password},
+  email::{
+    emails::{UpdateUserEmail, UpdatedField, WelcomeEmail},
+    outbox::EmailOutbox,
+  },
   user::{
     constants::UserMessage,
-    dto::{CreateUserDto, DeleteUserDto, ResetPasswordDto, UpdateUserDto},
+    dto::{CreateUserDto, ResetPasswordDto, UpdateUserDto},
+    password_policy::PasswordPolicyService,
     types::User,
   },
   AppState,
 };
 use sqlx::Postgres;
 use sqlx::QueryBuilder;
+use uuid::Uuid;
+
+/// Turns a failed insert or update into a conflict when it hit the username or email
+/// uniqueness, and into `fallback` otherwise.
+fn write_error(e: sqlx::Error, fallback: UserMessage) -> UserMessage {
+  match unique_violation(&e) {
+    Some(constraint) if constraint.contains("username") => UserMessage::UserUsernameTaken,
+    Some(constraint) if constraint.contains("email") => UserMessage::UserEmailTaken,
+    _ => {
+      println!("{:?}", e);
+      fallback
+    },
+  }
+}
 
 pub struct UserService;
 
@@ -24,23 +45,17 @@ impl UserService {
       r#"
         SELECT *
         FROM users u
-        WHERE u.id = $1::uuid
-          AND NOT EXISTS (
-            SELECT 1
-            FROM otp_codes oc
-            WHERE oc.user_id = u.id
-              AND oc.expires_at > NOW()
-              AND oc.is_active = true
-          );
+        WHERE u.id = $1::uuid;
     "#,
     )
-    .bind(&credentials.user_id)
-    .fetch_one(&data.db)
+    .bind(credentials.user_id)
+    .fetch_optional(&data.db)
     .await
     .map_err(|e| {
       println!("{}", e);
       UserMessage::UserGetFailed
-    })?;
+    })?
+    .ok_or(UserMessage::UserNotFound)?;
 
     Ok(user)
   }
@@ -49,9 +64,26 @@ impl UserService {
     data: &web::Data<AppState>,
     credentials: CreateUserDto,
   ) -> Result<User, UserMessage> {
-    let hashed_password = bcrypt::hash(&credentials.password, 10).unwrap();
+    PasswordPolicyService::check(
+      data,
+      &credentials.password,
+      &credentials.username,
+      &credentials.email,
+      None,
+    )
+    .await?;
 
-    let user = sqlx::query_as::<_, User>(
+    let hashed_password = password::hash(&credentials.password).await.map_err(|e| {
+      println!("{:?}", e);
+      UserMessage::UserCreateFailed
+    })?;
+
+    let mut tx = data.db.begin().await.map_err(|e| {
+      println!("{:?}", e);
+      UserMessage::UserCreateFailed
+    })?;
+
+    let mut user = sqlx::query_as::<_, User>(
       r#"
         INSERT INTO users (username, email, first_name, last_name, password_hash)
         VALUES ($1, $2, $3, $4, $5)
@@ -63,35 +95,86 @@ impl UserService {
     .bind(&credentials.first_name)
     .bind(&credentials.last_name)
     .bind(&hashed_password)
-    .fetch_one(&data.db)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| write_error(e, UserMessage::UserCreateFailed))?;
+
+    PasswordPolicyService::remember(&mut *tx, &data.config.password, user.id, &hashed_password)
+      .await;
+
+    // Every account starts out as a member
+    RoleService::grant(&mut *tx, user.id, Role::Member, None)
+      .await
+      .map_err(|_| UserMessage::UserCreateFailed)?;
+
+    let email = WelcomeEmail {
+      username: &user.username,
+      dashboard_url: &data.config.links.url("/dashboard/"),
+      current_year: Utc::now().year(),
+    };
+
+    EmailOutbox::enqueue(
+      &mut *tx,
+      &data.config.mail,
+      &user.email,
+      user.locale(),
+      &email,
+    )
     .await
     .map_err(|e| {
-      println!("{}", e);
+      println!("{:?}", e);
+      UserMessage::UserCreateEmailFailed
+    })?;
+
+    tx.commit().await.map_err(|e| {
+      println!("{:?}", e);
       UserMessage::UserCreateFailed
     })?;
 
+    if let Ok(grants) = RoleService::grants_of(data, user.id).await {
+      user.roles = grants.roles;
+      user.permissions = grants.permissions;
+    }
+
     Ok(user)
   }
 
   pub async fn update<'a>(
     data: &web::Data<AppState>,
+    user_id: Uuid,
+    current_sid: Option<&str>,
     credentials: UpdateUserDto,
   ) -> Result<(User, Vec<UpdatedField<'a>>), UserMessage> {
+    if let Some(password) = &credentials.password {
+      let current = Self::me(data, user_id).await?;
+      PasswordPolicyService::check(
+        data,
+        password,
+        credentials.username.as_deref().unwrap_or(&current.username),
+        credentials.email.as_deref().unwrap_or(&current.email),
+        Some(user_id),
+      )
+      .await?;
+    }
+
     let mut fields_updated = Vec::<UpdatedField<'a>>::new();
     let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("UPDATE users SET ");
 
+    let password_hash = match credentials.password {
+      Some(plain) => Some(password::hash(&plain).await.map_err(|e| {
+        println!("{:?}", e);
+        UserMessage::UserUpdateFailed
+      })?),
+      None => None,
+    };
+
     // Create an iterator of field name + Option<value>
     let fields = [
       ("username", credentials.username),
       ("email", credentials.email),
       ("first_name", credentials.first_name),
       ("last_name", credentials.last_name),
-      (
-        "password_hash",
-        credentials
-          .password
-          .map(|v| bcrypt::hash(v, 10).expect("UserFailedToHashPassword")),
-      ),
+      ("password_hash", password_hash),
     ];
 
     let mut first = true;
@@ -104,43 +187,118 @@ impl UserService {
         qb.push(format!("{name} = ").as_str()).push_bind(value);
         fields_updated.push(UpdatedField {
           label: name,
-          value: value_opt.clone().unwrap(),
+          value: value.clone(),
         });
         first = false;
       }
     }
 
+    // Preferences live in the settings JSON, next to whatever the client keeps there
+    let preferences = [
+      ("locale", credentials.locale),
+      ("timezone", credentials.timezone),
+    ];
+    let mut settings = serde_json::Map::new();
+    for (name, value) in preferences {
+      if let Some(value) = value {
+        fields_updated.push(UpdatedField {
+          label: name,
+          value: value.clone(),
+        });
+        settings.insert(name.to_string(), value.into());
+      }
+    }
+    if !settings.is_empty() {
+      if !first {
+        qb.push(", ");
+      }
+      qb.push("settings = COALESCE(settings, '{}'::jsonb) || ")
+        .push_bind(serde_json::Value::Object(settings));
+      first = false;
+    }
+
     // If no fields to update
     if first {
       return Err(UserMessage::NothingToUpdate);
     }
 
-    qb.push(" WHERE id = ").push_bind(credentials.user_id);
+    // A new address has to be confirmed again
+    if fields
+      .iter()
+      .any(|(name, value)| *name == "email" && value.is_some())
+    {
+      qb.push(", email_verified_at = NULL");
+    }
+
+    qb.push(" WHERE id = ").push_bind(user_id);
     qb.push(" RETURNING *");
 
+    let mut tx = data.db.begin().await.map_err(|e| {
+      println!("{:?}", e);
+      UserMessage::UserUpdateFailed
+    })?;
+
     let updated_user = qb
       .build_query_as::<User>()
-      .fetch_one(&data.db)
+      .fetch_optional(&mut *tx)
       .await
-      .map_err(|e| {
-        println!("{:?}", e);
-        UserMessage::UserUpdateFailed
-      })?;
+      .map_err(|e| write_error(e, UserMessage::UserUpdateFailed))?
+      .ok_or(UserMessage::UserNotFound)?;
+
+    let password_field = fields_updated
+      .iter()
+      .find(|field| field.label == "password_hash");
+    if let Some(field) = password_field {
+      PasswordPolicyService::remember(&mut *tx, &data.config.password, user_id, &field.value).await;
+    }
+
+    let locale = updated_user.locale();
+    let email = UpdateUserEmail {
+      username: &updated_user.username,
+      view_profile_link: &data.config.links.url("/profile"),
+      updated_fields: fields_updated.clone(),
+      last_update: &locale.format_datetime(Utc::now(), updated_user.timezone()),
+    };
+
+    EmailOutbox::enqueue(
+      &mut *tx,
+      &data.config.mail,
+      &updated_user.email,
+      locale,
+      &email,
+    )
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      UserMessage::UserUpdateProfileEmailFailed
+    })?;
+
+    tx.commit().await.map_err(|e| {
+      println!("{:?}", e);
+      UserMessage::UserUpdateFailed
+    })?;
+
+    // Anyone holding another session or a refresh token may know the old password
+    if password_field.is_some() {
+      SessionRegistry::revoke_others(data, user_id, current_sid)
+        .await
+        .map_err(|_| UserMessage::UserRevokeSessionsFailed)?;
+      TokenService::revoke_all(data, user_id)
+        .await
+        .map_err(|_| UserMessage::UserRevokeSessionsFailed)?;
+    }
 
     Ok((updated_user, fields_updated))
   }
 
-  pub async fn delete(
-    data: web::Data<AppState>,
-    credentials: DeleteUserDto,
-  ) -> Result<(), UserMessage> {
+  pub async fn delete(data: &web::Data<AppState>, user_id: Uuid) -> Result<(), UserMessage> {
     let result = sqlx::query(
       r#"
         DELETE FROM users
         WHERE id = $1::uuid
       "#,
     )
-    .bind(&credentials.user_id)
+    .bind(user_id)
     .execute(&data.db)
     .await
     .map_err(|e| {
@@ -155,8 +313,8 @@ impl UserService {
     Ok(())
   }
 
-  pub async fn me(data: web::Data<AppState>, user_id: String) -> Result<User, UserMessage> {
-    let user = sqlx::query_as::<_, User>(
+  pub async fn me(data: &web::Data<AppState>, user_id: Uuid) -> Result<User, UserMessage> {
+    let mut user = sqlx::query_as::<_, User>(
       r#"
       SELECT * 
       FROM users
@@ -164,13 +322,193 @@ impl UserService {
       "#,
     )
     .bind(user_id)
-    .fetch_one(&data.db)
+    .fetch_optional(&data.db)
     .await
     .map_err(|e| {
       println!("{:?}", e);
       UserMessage::UserGetProfileFailed
+    })?
+    .ok_or(UserMessage::UserNotFound)?;
+
+    let grants = RoleService::grants_of(data, user_id)
+      .await
+      .map_err(|_| UserMessage::UserGetProfileFailed)?;
+    user.roles = grants.roles;
+    user.permissions = grants.permissions;
+
+    Ok(user)
+  }
+
+  /// Runs the password policy for an existing account, before anything is spent on the change.
+  pub async fn check_password(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+    password: &str,
+  ) -> Result<(), UserMessage> {
+    let user = Self::me(data, user_id).await?;
+
+    PasswordPolicyService::check(data, password, &user.username, &user.email, Some(user_id)).await
+  }
+
+  /// Sets a password that already went through `check_password`.
+  pub async fn set_password(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+    password: &str,
+  ) -> Result<User, UserMessage> {
+    let hashed_password = password::hash(password).await.map_err(|e| {
+      println!("{:?}", e);
+      UserMessage::UserResetPasswordFailed
     })?;
 
+    let user = sqlx::query_as::<_, User>(
+      r#"
+        UPDATE users
+        SET password_hash = $1, updated_at = NOW()
+        WHERE id = $2::uuid
+        RETURNING *
+      "#,
+    )
+    .bind(&hashed_password)
+    .bind(user_id)
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      UserMessage::UserResetPasswordFailed
+    })?
+    .ok_or(UserMessage::UserNotFound)?;
+
+    PasswordPolicyService::remember(&data.db, &data.config.password, user_id, &hashed_password)
+      .await;
+
     Ok(user)
   }
+
+  pub async fn mark_email_verified(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+  ) -> Result<User, UserMessage> {
+    let user = sqlx::query_as::<_, User>(
+      r#"
+        UPDATE users
+        SET email_verified_at = COALESCE(email_verified_at, NOW()), updated_at = NOW()
+        WHERE id = $1::uuid
+        RETURNING *
+      "#,
+    )
+    .bind(user_id)
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      UserMessage::UserVerifyEmailFailed
+    })?
+    .ok_or(UserMessage::UserNotFound)?;
+
+    Ok(user)
+  }
+
+  pub async fn get_by_email(
+    data: &web::Data<AppState>,
+    email: &str,
+  ) -> Result<Option<User>, UserMessage> {
+    let user = sqlx::query_as::<_, User>(
+      r#"
+        SELECT *
+        FROM users
+        WHERE LOWER(email) = LOWER($1)
+          AND deleted_at IS NULL
+      "#,
+    )
+    .bind(email)
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      UserMessage::UserGetFailed
+    })?;
+
+    Ok(user)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{borrow::Cow, error::Error, fmt};
+
+  use sqlx::error::{DatabaseError, ErrorKind};
+
+  use super::*;
+
+  /// A unique violation on `constraint`, as Postgres would report it.
+  #[derive(Debug)]
+  struct UniqueViolation(&'static str);
+
+  impl fmt::Display for UniqueViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      write!(
+        f,
+        "duplicate key value violates unique constraint \"{}\"",
+        self.0
+      )
+    }
+  }
+
+  impl Error for UniqueViolation {}
+
+  impl DatabaseError for UniqueViolation {
+    fn message(&self) -> &str {
+      "duplicate key value violates unique constraint"
+    }
+
+    fn code(&self) -> Option<Cow<'_, str>> {
+      Some(Cow::Borrowed("23505"))
+    }
+
+    fn constraint(&self) -> Option<&str> {
+      Some(self.0)
+    }
+
+    fn as_error(&self) -> &(dyn Error + Send + Sync + 'static) {
+      self
+    }
+
+    fn as_error_mut(&mut self) -> &mut (dyn Error + Send + Sync + 'static) {
+      self
+    }
+
+    fn into_error(self: Box<Self>) -> Box<dyn Error + Send + Sync + 'static> {
+      self
+    }
+
+    fn kind(&self) -> ErrorKind {
+      ErrorKind::UniqueViolation
+    }
+  }
+
+  fn violation(constraint: &'static str) -> sqlx::Error {
+    sqlx::Error::Database(Box::new(UniqueViolation(constraint)))
+  }
+
+  #[test]
+  fn duplicate_username_is_a_conflict() {
+    let e = write_error(
+      violation("users_username_unique"),
+      UserMessage::UserCreateFailed,
+    );
+    assert!(matches!(e, UserMessage::UserUsernameTaken));
+  }
+
+  #[test]
+  fn duplicate_email_is_a_conflict() {
+    let e = write_error(violation("user_email_idx"), UserMessage::UserUpdateFailed);
+    assert!(matches!(e, UserMessage::UserEmailTaken));
+  }
+
+  #[test]
+  fn other_failures_fall_back() {
+    let e = write_error(sqlx::Error::PoolTimedOut, UserMessage::UserCreateFailed);
+    assert!(matches!(e, UserMessage::UserCreateFailed));
+  }
 }
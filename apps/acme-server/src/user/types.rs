@@ -1,14 +1,21 @@
 use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::FromRow;
 use uuid::Uuid;
 
+use crate::{
+  auth::types::{Permission, Role},
+  common::i18n::Locale,
+};
+
 #[derive(Debug, Serialize, Deserialize, FromRow, Clone)]
 pub struct User {
   pub id: Uuid,
   pub username: String,
   pub email: String,
+  pub email_verified_at: Option<DateTime<Utc>>,
   pub first_name: String,
   pub last_name: String,
 
@@ -18,10 +25,18 @@ pub struct User {
   pub is_active: bool,
   pub version: i32,
   pub settings: Value, // JSON column
+  #[serde(skip_serializing)]
+  pub totp_secret: Option<String>,
+  pub totp_enabled_at: Option<DateTime<Utc>>,
   pub created_at: DateTime<Utc>,
   pub updated_at: DateTime<Utc>,
   pub deleted_at: Option<DateTime<Utc>>,
   pub last_login_at: Option<DateTime<Utc>>,
+  /// Filled in from `user_roles` where needed, not a column.
+  #[sqlx(skip)]
+  pub roles: Vec<Role>,
+  #[sqlx(skip)]
+  pub permissions: Vec<Permission>,
 }
 
 impl Default for User {
@@ -30,6 +45,7 @@ impl Default for User {
       id: Uuid::default(),
       username: String::default(),
       email: String::default(),
+      email_verified_at: None,
       first_name: String::default(),
       last_name: String::default(),
       password_hash: String::default(),
@@ -37,10 +53,36 @@ impl Default for User {
       is_active: false,
       version: 0,
       settings: Value::default(),
+      totp_secret: None,
+      totp_enabled_at: None,
       created_at: Utc::now(),
       updated_at: Utc::now(),
       deleted_at: None,
       last_login_at: None,
+      roles: Vec::new(),
+      permissions: Vec::new(),
     }
   }
 }
+
+impl User {
+  /// Language emails to this user are written in: `settings.locale`, or the request's.
+  pub fn locale(&self) -> Locale {
+    self
+      .settings
+      .get("locale")
+      .and_then(Value::as_str)
+      .and_then(Locale::from_tag)
+      .unwrap_or_else(Locale::current)
+  }
+
+  /// Zone dates in emails to this user are shown in: `settings.timezone`, or UTC.
+  pub fn timezone(&self) -> Tz {
+    self
+      .settings
+      .get("timezone")
+      .and_then(Value::as_str)
+      .and_then(|name| name.parse().ok())
+      .unwrap_or(Tz::UTC)
+  }
+}
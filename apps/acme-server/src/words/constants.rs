@@ -1,7 +1,10 @@
 use std::{error::Error, fmt};
 
+use actix_web::http::StatusCode;
 use serde::{Deserialize, Serialize};
 
+use crate::common::i18n::{Locale, Translate};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum WordsMessage {
   WordGetSuccess,
@@ -11,9 +14,11 @@ pub enum WordsMessage {
 
   WordGetFailed,
   WordCreateFailed,
+  WordTranslateFailed,
   WordUpdateFailed,
   WordDeleteFailed,
   WordNotFound,
+  WordForbidden,
   NothingToUpdate,
 }
 
@@ -24,3 +29,60 @@ impl fmt::Display for WordsMessage {
 }
 
 impl Error for WordsMessage {}
+
+impl WordsMessage {
+  /// Status the message is answered with when it is an error.
+  pub fn status_code(&self) -> StatusCode {
+    use WordsMessage::*;
+
+    match self {
+      WordNotFound => StatusCode::NOT_FOUND,
+      WordForbidden => StatusCode::FORBIDDEN,
+      NothingToUpdate => StatusCode::BAD_REQUEST,
+      WordTranslateFailed => StatusCode::BAD_GATEWAY,
+      WordGetFailed | WordCreateFailed | WordUpdateFailed | WordDeleteFailed => {
+        StatusCode::INTERNAL_SERVER_ERROR
+      },
+      WordGetSuccess | WordCreateSuccess | WordUpdateSuccess | WordDeleteSuccess => StatusCode::OK,
+    }
+  }
+}
+
+impl Translate for WordsMessage {
+  fn translate(&self, locale: Locale) -> Option<&'static str> {
+    use WordsMessage::*;
+
+    Some(match (self, locale) {
+      (WordGetSuccess, Locale::En) => "Here are the words.",
+      (WordGetSuccess, Locale::Es) => "Estas son las palabras.",
+      (WordCreateSuccess, Locale::En) => "The word was added.",
+      (WordCreateSuccess, Locale::Es) => "La palabra se ha añadido.",
+      (WordUpdateSuccess, Locale::En) => "The word was updated.",
+      (WordUpdateSuccess, Locale::Es) => "La palabra se ha actualizado.",
+      (WordDeleteSuccess, Locale::En) => "The word was deleted.",
+      (WordDeleteSuccess, Locale::Es) => "La palabra se ha eliminado.",
+      (WordGetFailed, Locale::En) => "We couldn't load the words. Please try again.",
+      (WordGetFailed, Locale::Es) => "No hemos podido cargar las palabras. Inténtalo de nuevo.",
+      (WordCreateFailed, Locale::En) => "We couldn't add the word. Please try again.",
+      (WordCreateFailed, Locale::Es) => "No hemos podido añadir la palabra. Inténtalo de nuevo.",
+      (WordTranslateFailed, Locale::En) => {
+        "The translation service is not answering. Please try again later."
+      },
+      (WordTranslateFailed, Locale::Es) => {
+        "El servicio de traducción no responde. Inténtalo más tarde."
+      },
+      (WordUpdateFailed, Locale::En) => "We couldn't update the word. Please try again.",
+      (WordUpdateFailed, Locale::Es) => {
+        "No hemos podido actualizar la palabra. Inténtalo de nuevo."
+      },
+      (WordDeleteFailed, Locale::En) => "We couldn't delete the word. Please try again.",
+      (WordDeleteFailed, Locale::Es) => "No hemos podido eliminar la palabra. Inténtalo de nuevo.",
+      (WordNotFound, Locale::En) => "We couldn't find that word.",
+      (WordNotFound, Locale::Es) => "No hemos encontrado esa palabra.",
+      (WordForbidden, Locale::En) => "That word belongs to someone else.",
+      (WordForbidden, Locale::Es) => "Esa palabra pertenece a otra persona.",
+      (NothingToUpdate, Locale::En) => "There is nothing to update.",
+      (NothingToUpdate, Locale::Es) => "No hay nada que actualizar.",
+    })
+  }
+}
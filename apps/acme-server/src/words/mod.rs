@@ -1,6 +1,9 @@
 use crate::{
-  auth::guard::auth_middleware,
-  common::functionalities::api_res::{api_error, api_success},
+  auth::{
+    guard::{auth_middleware, require_permission, AuthUser, RequireScope, VerifiedUser},
+    types::{Permission, TokenScope},
+  },
+  common::{error::AppError, extractors::ValidatedJson, functionalities::api_res::api_success},
   words::{
     constants::WordsMessage,
     dto::{WordsCreateDto, WordsDeleteDto, WordsUpdateDto},
@@ -10,19 +13,20 @@ use crate::{
   AppState,
 };
 use actix_web::{
-  delete, get, http::StatusCode, middleware::from_fn, patch, post, web, HttpResponse, Responder,
+  delete, get, http::StatusCode, middleware::from_fn, patch, post, web, HttpResponse,
 };
 
-mod constants;
+pub mod constants;
 mod dto;
 mod libs;
 mod service;
 mod types;
 
-pub fn config(cfg: &mut web::ServiceConfig) -> () {
+pub fn config(cfg: &mut web::ServiceConfig) {
   cfg.service(
     web::scope("/words").service(
       web::scope("")
+        .wrap(require_permission(Permission::WordsRead))
         .wrap(from_fn(auth_middleware))
         .service(word_create)
         .service(word_delete)
@@ -32,65 +36,77 @@ pub fn config(cfg: &mut web::ServiceConfig) -> () {
   );
 }
 
-#[get("/get-all")]
-async fn word_get(data: web::Data<AppState>) -> impl Responder {
-  let words = match WordsService::get_all(&data).await {
-    Ok(words) => words,
-    Err(e) => return api_error::<Vec<Word>, WordsMessage>(StatusCode::BAD_REQUEST, e),
-  };
+#[get("/get-all", wrap = "RequireScope(TokenScope::WordsRead)")]
+async fn word_get(auth: AuthUser, data: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+  let words = WordsService::get_all(&data, auth.id).await?;
 
-  api_success::<Vec<Word>, WordsMessage>(StatusCode::OK, words, WordsMessage::WordGetSuccess)
+  Ok(api_success::<Vec<Word>, WordsMessage>(
+    StatusCode::OK,
+    words,
+    WordsMessage::WordGetSuccess,
+  ))
 }
 
-#[post("/create")]
+#[post(
+  "/create",
+  wrap = "RequireScope(TokenScope::WordsWrite)",
+  wrap = "require_permission(Permission::WordsWrite)"
+)]
 async fn word_create(
+  auth: VerifiedUser,
   data: web::Data<AppState>,
-  credentials: web::Json<WordsCreateDto>,
-) -> impl Responder {
-  let translated =
-    match libs::translate_text(&credentials.literal, &credentials.language, None).await {
-      Ok(translated) => translated,
-      Err(_) => {
-        return api_error::<Word, WordsMessage>(
-          StatusCode::BAD_REQUEST,
-          WordsMessage::WordCreateFailed,
-        );
-      },
-    };
+  credentials: ValidatedJson<WordsCreateDto>,
+) -> Result<HttpResponse, AppError> {
+  let translated = libs::translate_text(&credentials.literal, &credentials.language, None)
+    .await
+    .map_err(|_| WordsMessage::WordTranslateFailed)?;
 
   let mut dto = credentials.into_inner();
   dto.translated = translated;
 
-  let word = match WordsService::create(&data, dto).await {
-    Ok(word) => word,
-    Err(e) => return api_error::<Word, WordsMessage>(StatusCode::BAD_REQUEST, e),
-  };
+  let word = WordsService::create(&data, auth.id, dto).await?;
 
-  api_success::<Word, WordsMessage>(StatusCode::OK, word, WordsMessage::WordGetSuccess)
+  Ok(api_success::<Word, WordsMessage>(
+    StatusCode::OK,
+    word,
+    WordsMessage::WordGetSuccess,
+  ))
 }
 
-#[patch("/update")]
+#[patch(
+  "/update",
+  wrap = "RequireScope(TokenScope::WordsWrite)",
+  wrap = "require_permission(Permission::WordsWrite)"
+)]
 async fn word_update(
+  auth: AuthUser,
   data: web::Data<AppState>,
-  credentials: web::Json<WordsUpdateDto>,
-) -> impl Responder {
-  let (word, _) = match WordsService::update(&data, credentials.into_inner()).await {
-    Ok(word) => word,
-    Err(e) => return api_error::<Word, WordsMessage>(StatusCode::BAD_REQUEST, e),
-  };
+  credentials: ValidatedJson<WordsUpdateDto>,
+) -> Result<HttpResponse, AppError> {
+  let (word, _) = WordsService::update(&data, auth.id, credentials.into_inner()).await?;
 
-  api_success::<Word, WordsMessage>(StatusCode::OK, word, WordsMessage::WordUpdateSuccess)
+  Ok(api_success::<Word, WordsMessage>(
+    StatusCode::OK,
+    word,
+    WordsMessage::WordUpdateSuccess,
+  ))
 }
 
-#[delete("/delete")]
+#[delete(
+  "/delete",
+  wrap = "RequireScope(TokenScope::WordsWrite)",
+  wrap = "require_permission(Permission::WordsWrite)"
+)]
 async fn word_delete(
+  auth: AuthUser,
   data: web::Data<AppState>,
-  credentials: web::Json<WordsDeleteDto>,
-) -> impl Responder {
-  match WordsService::delete(&data, credentials.into_inner()).await {
-    Ok(word) => word,
-    Err(e) => return api_error::<Word, WordsMessage>(StatusCode::BAD_REQUEST, e),
-  };
+  credentials: ValidatedJson<WordsDeleteDto>,
+) -> Result<HttpResponse, AppError> {
+  WordsService::delete(&data, auth.id, credentials.into_inner()).await?;
 
-  api_success::<(), WordsMessage>(StatusCode::OK, (), WordsMessage::WordDeleteSuccess)
+  Ok(api_success::<(), WordsMessage>(
+    StatusCode::OK,
+    (),
+    WordsMessage::WordDeleteSuccess,
+  ))
 }
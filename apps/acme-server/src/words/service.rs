@@ -1,5 +1,6 @@
 use actix_web::web;
 use sqlx::{Postgres, QueryBuilder};
+use uuid::Uuid;
 
 use crate::{
   email::emails::UpdatedField,
@@ -14,12 +15,17 @@ use crate::{
 pub struct WordsService;
 
 impl WordsService {
-  pub async fn get_all(data: &web::Data<AppState>) -> Result<Vec<Word>, WordsMessage> {
+  pub async fn get_all(
+    data: &web::Data<AppState>,
+    user_id: Uuid,
+  ) -> Result<Vec<Word>, WordsMessage> {
     let words = sqlx::query_as::<_, Word>(
       r#"
         SELECT * FROM words
+        WHERE user_id = $1::uuid
       "#,
     )
+    .bind(user_id)
     .fetch_all(&data.db)
     .await
     .map_err(|e| {
@@ -32,6 +38,7 @@ impl WordsService {
 
   pub async fn create(
     data: &web::Data<AppState>,
+    user_id: Uuid,
     credentials: WordsCreateDto,
   ) -> Result<Word, WordsMessage> {
     let word = sqlx::query_as::<_, Word>(
@@ -45,7 +52,7 @@ impl WordsService {
     .bind(&credentials.literal)
     .bind(&credentials.language)
     .bind(&credentials.translated)
-    .bind(&credentials.user_id)
+    .bind(user_id)
     .fetch_one(&data.db)
     .await
     .map_err(|e| {
@@ -58,12 +65,15 @@ impl WordsService {
 
   pub async fn update<'a>(
     data: &web::Data<AppState>,
+    user_id: Uuid,
     credentials: WordsUpdateDto,
   ) -> Result<(Word, Vec<UpdatedField<'a>>), WordsMessage> {
+    Self::ensure_owner(data, credentials.word_id, user_id).await?;
+
     let mut fields_updated = Vec::<UpdatedField<'a>>::new();
     let mut qb: QueryBuilder<Postgres> = QueryBuilder::new("UPDATE words SET ");
 
-    let fields = vec![
+    let fields = [
       ("category", credentials.category),
       ("literal", credentials.literal),
       ("language", credentials.language),
@@ -80,7 +90,7 @@ impl WordsService {
         qb.push(format!("{name} = ").as_str()).push_bind(value);
         fields_updated.push(UpdatedField {
           label: name,
-          value: value_opt.clone().unwrap(),
+          value: value.clone(),
         });
         first = false;
       }
@@ -92,6 +102,7 @@ impl WordsService {
     }
 
     qb.push(" WHERE id = ").push_bind(credentials.word_id);
+    qb.push(" AND user_id = ").push_bind(user_id);
     qb.push(" RETURNING *");
 
     let updated_user = qb
@@ -108,16 +119,19 @@ impl WordsService {
 
   pub async fn delete(
     data: &web::Data<AppState>,
+    user_id: Uuid,
     credentials: WordsDeleteDto,
   ) -> Result<(), WordsMessage> {
+    Self::ensure_owner(data, credentials.id, user_id).await?;
+
     let result = sqlx::query(
       r#"
         DELETE FROM words
         WHERE id = $1::uuid AND user_id = $2::uuid
       "#,
     )
-    .bind(&credentials.id)
-    .bind(&credentials.user_id)
+    .bind(credentials.id)
+    .bind(user_id)
     .execute(&data.db)
     .await
     .map_err(|e| {
@@ -131,4 +145,31 @@ impl WordsService {
 
     Ok(())
   }
+
+  /// Makes sure the word exists and belongs to `user_id`.
+  async fn ensure_owner(
+    data: &web::Data<AppState>,
+    word_id: Uuid,
+    user_id: Uuid,
+  ) -> Result<(), WordsMessage> {
+    let owner = sqlx::query_scalar::<_, Uuid>(
+      r#"
+        SELECT user_id FROM words
+        WHERE id = $1::uuid
+      "#,
+    )
+    .bind(word_id)
+    .fetch_optional(&data.db)
+    .await
+    .map_err(|e| {
+      println!("{:?}", e);
+      WordsMessage::WordGetFailed
+    })?;
+
+    match owner {
+      None => Err(WordsMessage::WordNotFound),
+      Some(owner) if owner != user_id => Err(WordsMessage::WordForbidden),
+      Some(_) => Ok(()),
+    }
+  }
 }
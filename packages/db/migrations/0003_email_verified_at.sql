@@ -0,0 +1,3 @@
+ALTER TABLE "users" ADD COLUMN "email_verified_at" timestamp with time zone;--> statement-breakpoint
+-- Accounts created before verification existed keep working as verified
+UPDATE "users" SET "email_verified_at" = NOW() WHERE "email_verified_at" IS NULL;
\ No newline at end of file
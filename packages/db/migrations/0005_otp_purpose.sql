@@ -0,0 +1,3 @@
+CREATE TYPE "public"."otp_purpose" AS ENUM('reset_password', 'login');--> statement-breakpoint
+ALTER TABLE "otp_codes" ADD COLUMN "purpose" "otp_purpose" DEFAULT 'reset_password' NOT NULL;--> statement-breakpoint
+CREATE INDEX "user_purpose_codes_idx" ON "otp_codes" USING btree ("user_id","purpose");
\ No newline at end of file
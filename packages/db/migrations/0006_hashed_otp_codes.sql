@@ -0,0 +1,8 @@
+ALTER TYPE "public"."otp_purpose" ADD VALUE 'verify_email' BEFORE 'login';--> statement-breakpoint
+ALTER TYPE "public"."otp_purpose" ADD VALUE 'delete_account';--> statement-breakpoint
+ALTER TABLE "otp_codes" RENAME COLUMN "code" TO "code_hash";--> statement-breakpoint
+ALTER TABLE "otp_codes" ALTER COLUMN "code_hash" SET DATA TYPE varchar(255);--> statement-breakpoint
+ALTER TABLE "otp_codes" ADD COLUMN "attempts" integer DEFAULT 0 NOT NULL;--> statement-breakpoint
+-- Codes issued before hashing are stored in the clear and can never verify, so retire them
+UPDATE "otp_codes"
+SET "code_hash" = '', "is_active" = false, "deleted_at" = COALESCE("deleted_at", NOW()), "updated_at" = NOW();
\ No newline at end of file
@@ -0,0 +1,15 @@
+CREATE TABLE "refresh_tokens" (
+	"created_at" timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	"expires_at" timestamp with time zone NOT NULL,
+	"family_id" uuid NOT NULL,
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"status" "token_status" DEFAULT 'active' NOT NULL,
+	"token_hash" varchar(255) NOT NULL,
+	"used_at" timestamp with time zone,
+	"user_id" uuid NOT NULL,
+	CONSTRAINT "refresh_tokens_token_hash_unique" UNIQUE("token_hash")
+);
+--> statement-breakpoint
+ALTER TABLE "refresh_tokens" ADD CONSTRAINT "refresh_tokens_user_id_users_id_fk" FOREIGN KEY ("user_id") REFERENCES "public"."users"("id") ON DELETE cascade ON UPDATE no action;--> statement-breakpoint
+CREATE INDEX "user_refresh_tokens_idx" ON "refresh_tokens" USING btree ("user_id");--> statement-breakpoint
+CREATE INDEX "family_refresh_tokens_idx" ON "refresh_tokens" USING btree ("family_id");
\ No newline at end of file
@@ -0,0 +1,15 @@
+CREATE TABLE "personal_access_tokens" (
+	"created_at" timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	"expires_at" timestamp with time zone NOT NULL,
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"last_used_at" timestamp with time zone,
+	"name" varchar(255) NOT NULL,
+	"scopes" text[] NOT NULL,
+	"status" "token_status" DEFAULT 'active' NOT NULL,
+	"token_hash" varchar(255) NOT NULL,
+	"user_id" uuid NOT NULL,
+	CONSTRAINT "personal_access_tokens_token_hash_unique" UNIQUE("token_hash")
+);
+--> statement-breakpoint
+ALTER TABLE "personal_access_tokens" ADD CONSTRAINT "personal_access_tokens_user_id_users_id_fk" FOREIGN KEY ("user_id") REFERENCES "public"."users"("id") ON DELETE cascade ON UPDATE no action;--> statement-breakpoint
+CREATE INDEX "user_personal_access_tokens_idx" ON "personal_access_tokens" USING btree ("user_id");
\ No newline at end of file
@@ -0,0 +1,51 @@
+CREATE TABLE "permissions" (
+	"created_at" timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	"description" text,
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"name" varchar(255) NOT NULL,
+	CONSTRAINT "permissions_name_unique" UNIQUE("name")
+);
+--> statement-breakpoint
+CREATE TABLE "role_permissions" (
+	"permission_id" uuid NOT NULL,
+	"role_id" uuid NOT NULL,
+	CONSTRAINT "role_permissions_role_id_permission_id_pk" PRIMARY KEY("role_id","permission_id")
+);
+--> statement-breakpoint
+CREATE TABLE "roles" (
+	"created_at" timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	"description" text,
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"name" varchar(255) NOT NULL,
+	CONSTRAINT "roles_name_unique" UNIQUE("name")
+);
+--> statement-breakpoint
+CREATE TABLE "user_roles" (
+	"created_at" timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	"granted_by" uuid,
+	"role_id" uuid NOT NULL,
+	"user_id" uuid NOT NULL,
+	CONSTRAINT "user_roles_user_id_role_id_pk" PRIMARY KEY("user_id","role_id")
+);
+--> statement-breakpoint
+ALTER TABLE "role_permissions" ADD CONSTRAINT "role_permissions_permission_id_permissions_id_fk" FOREIGN KEY ("permission_id") REFERENCES "public"."permissions"("id") ON DELETE cascade ON UPDATE no action;--> statement-breakpoint
+ALTER TABLE "role_permissions" ADD CONSTRAINT "role_permissions_role_id_roles_id_fk" FOREIGN KEY ("role_id") REFERENCES "public"."roles"("id") ON DELETE cascade ON UPDATE no action;--> statement-breakpoint
+ALTER TABLE "user_roles" ADD CONSTRAINT "user_roles_granted_by_users_id_fk" FOREIGN KEY ("granted_by") REFERENCES "public"."users"("id") ON DELETE set null ON UPDATE no action;--> statement-breakpoint
+ALTER TABLE "user_roles" ADD CONSTRAINT "user_roles_role_id_roles_id_fk" FOREIGN KEY ("role_id") REFERENCES "public"."roles"("id") ON DELETE cascade ON UPDATE no action;--> statement-breakpoint
+ALTER TABLE "user_roles" ADD CONSTRAINT "user_roles_user_id_users_id_fk" FOREIGN KEY ("user_id") REFERENCES "public"."users"("id") ON DELETE cascade ON UPDATE no action;--> statement-breakpoint
+INSERT INTO "roles" ("name") VALUES ('admin'), ('editor'), ('member') ON CONFLICT ("name") DO NOTHING;--> statement-breakpoint
+INSERT INTO "permissions" ("name") VALUES ('words:read'), ('words:write'), ('profile:read'), ('profile:write'), ('users:read') ON CONFLICT ("name") DO NOTHING;--> statement-breakpoint
+INSERT INTO "role_permissions" ("role_id", "permission_id")
+SELECT r."id", p."id"
+FROM (VALUES
+	('admin', 'words:read'), ('admin', 'words:write'), ('admin', 'profile:read'), ('admin', 'profile:write'), ('admin', 'users:read'),
+	('editor', 'words:read'), ('editor', 'words:write'), ('editor', 'profile:read'), ('editor', 'profile:write'), ('editor', 'users:read'),
+	('member', 'words:read'), ('member', 'words:write'), ('member', 'profile:read'), ('member', 'profile:write')
+) AS grants ("role", "permission")
+JOIN "roles" r ON r."name" = grants."role"
+JOIN "permissions" p ON p."name" = grants."permission"
+ON CONFLICT DO NOTHING;--> statement-breakpoint
+-- Accounts created before roles existed become members, as every new signup does
+INSERT INTO "user_roles" ("user_id", "role_id")
+SELECT u."id", r."id" FROM "users" u, "roles" r WHERE r."name" = 'member'
+ON CONFLICT DO NOTHING;
\ No newline at end of file
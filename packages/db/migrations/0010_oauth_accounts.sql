@@ -0,0 +1,12 @@
+CREATE TABLE "oauth_accounts" (
+	"created_at" timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	"email" varchar(255) NOT NULL,
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"provider" varchar(50) NOT NULL,
+	"provider_user_id" varchar(255) NOT NULL,
+	"user_id" uuid NOT NULL
+);
+--> statement-breakpoint
+ALTER TABLE "oauth_accounts" ADD CONSTRAINT "oauth_accounts_user_id_users_id_fk" FOREIGN KEY ("user_id") REFERENCES "public"."users"("id") ON DELETE cascade ON UPDATE no action;--> statement-breakpoint
+CREATE UNIQUE INDEX "provider_account_idx" ON "oauth_accounts" USING btree ("provider","provider_user_id");--> statement-breakpoint
+CREATE INDEX "user_oauth_accounts_idx" ON "oauth_accounts" USING btree ("user_id");
\ No newline at end of file
@@ -0,0 +1,13 @@
+CREATE TABLE "sign_in_devices" (
+	"fingerprint" varchar(255) NOT NULL,
+	"first_seen_at" timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"ip" varchar(255) NOT NULL,
+	"last_seen_at" timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	"location" varchar(255),
+	"user_agent" text NOT NULL,
+	"user_id" uuid NOT NULL
+);
+--> statement-breakpoint
+ALTER TABLE "sign_in_devices" ADD CONSTRAINT "sign_in_devices_user_id_users_id_fk" FOREIGN KEY ("user_id") REFERENCES "public"."users"("id") ON DELETE cascade ON UPDATE no action;--> statement-breakpoint
+CREATE UNIQUE INDEX "user_device_fingerprint_idx" ON "sign_in_devices" USING btree ("user_id","fingerprint");
\ No newline at end of file
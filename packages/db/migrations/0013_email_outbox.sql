@@ -0,0 +1,16 @@
+CREATE TYPE "public"."email_outbox_status" AS ENUM('pending', 'sending', 'sent', 'dead');--> statement-breakpoint
+CREATE TABLE "email_outbox" (
+	"attempts" integer DEFAULT 0 NOT NULL,
+	"created_at" timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	"html" text NOT NULL,
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"last_error" text,
+	"next_attempt_at" timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	"recipient" varchar(255) NOT NULL,
+	"sent_at" timestamp with time zone,
+	"status" "email_outbox_status" DEFAULT 'pending' NOT NULL,
+	"subject" varchar(255) NOT NULL,
+	"updated_at" timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+--> statement-breakpoint
+CREATE INDEX "email_outbox_due_idx" ON "email_outbox" USING btree ("status","next_attempt_at");
\ No newline at end of file
@@ -0,0 +1,10 @@
+ALTER TABLE "email_outbox" ADD COLUMN "reply_to" varchar(255);--> statement-breakpoint
+ALTER TABLE "email_outbox" ADD COLUMN "sender" varchar(255);--> statement-breakpoint
+ALTER TABLE "email_outbox" ADD COLUMN "text" text;--> statement-breakpoint
+-- Emails queued before senders were stored went out from the default `mail.from`, with a text
+-- part stripped from the html
+UPDATE "email_outbox"
+SET "sender" = 'Acme App <admin@example.com>',
+	"text" = regexp_replace("html", '<[^>]*>', '', 'g');--> statement-breakpoint
+ALTER TABLE "email_outbox" ALTER COLUMN "sender" SET NOT NULL;--> statement-breakpoint
+ALTER TABLE "email_outbox" ALTER COLUMN "text" SET NOT NULL;
\ No newline at end of file
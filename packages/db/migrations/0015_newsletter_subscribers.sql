@@ -0,0 +1,25 @@
+CREATE TYPE "public"."subscriber_status" AS ENUM('pending', 'active', 'unsubscribed');--> statement-breakpoint
+CREATE TABLE "subscribers" (
+	"confirmation_sent_at" timestamp with time zone,
+	"confirmed_at" timestamp with time zone,
+	"created_at" timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	"email" varchar(255) NOT NULL,
+	"id" uuid PRIMARY KEY DEFAULT gen_random_uuid() NOT NULL,
+	"locale" varchar(16) DEFAULT 'en' NOT NULL,
+	"status" "subscriber_status" DEFAULT 'pending' NOT NULL,
+	"unsubscribed_at" timestamp with time zone,
+	"updated_at" timestamp with time zone DEFAULT CURRENT_TIMESTAMP NOT NULL,
+	"user_id" uuid,
+	CONSTRAINT "subscribers_email_unique" UNIQUE("email"),
+	CONSTRAINT "subscribers_user_id_unique" UNIQUE("user_id")
+);
+--> statement-breakpoint
+ALTER TABLE "email_outbox" ADD COLUMN "list_unsubscribe" text;--> statement-breakpoint
+ALTER TABLE "subscribers" ADD CONSTRAINT "subscribers_user_id_users_id_fk" FOREIGN KEY ("user_id") REFERENCES "public"."users"("id") ON DELETE set null ON UPDATE no action;--> statement-breakpoint
+CREATE INDEX "subscribers_status_idx" ON "subscribers" USING btree ("status");--> statement-breakpoint
+INSERT INTO "permissions" ("name") VALUES ('newsletter:send') ON CONFLICT ("name") DO NOTHING;--> statement-breakpoint
+INSERT INTO "role_permissions" ("role_id", "permission_id")
+SELECT r."id", p."id"
+FROM "roles" r, "permissions" p
+WHERE r."name" IN ('admin', 'editor') AND p."name" = 'newsletter:send'
+ON CONFLICT DO NOTHING;
\ No newline at end of file
@@ -1 +1,19 @@
 export const ACCESS_TOKENS_STATUSES = ['active', 'expired', 'revoked'] as const
+export const TOKEN_SCOPES = ['words:read', 'words:write', 'user:read', 'user:write'] as const
+export const ROLES = ['admin', 'editor', 'member'] as const
+export const PERMISSIONS = [
+  'words:read',
+  'words:write',
+  'profile:read',
+  'profile:write',
+  'users:read',
+  'newsletter:send',
+] as const
+export const ROLE_PERMISSIONS: Record<(typeof ROLES)[number], readonly (typeof PERMISSIONS)[number][]> = {
+  admin: PERMISSIONS,
+  editor: ['words:read', 'words:write', 'profile:read', 'profile:write', 'users:read', 'newsletter:send'],
+  member: ['words:read', 'words:write', 'profile:read', 'profile:write'],
+}
+export const OTP_PURPOSES = ['reset_password', 'verify_email', 'login', 'delete_account'] as const
+export const EMAIL_OUTBOX_STATUSES = ['pending', 'sending', 'sent', 'dead'] as const
+export const SUBSCRIBER_STATUSES = ['pending', 'active', 'unsubscribed'] as const
@@ -1,11 +1,34 @@
 import { relations } from 'drizzle-orm'
-import { otpCodes, users, words } from './tables'
+import {
+  oauthAccounts,
+  otpCodes,
+  passwordHistory,
+  permissions,
+  personalAccessTokens,
+  recoveryCodes,
+  refreshTokens,
+  rolePermissions,
+  roles,
+  signInDevices,
+  subscribers,
+  userRoles,
+  users,
+  words,
+} from './tables'
 
 /**
  * USERS RELATIONS
  */
-export const usersRelations = relations(users, ({ many }) => ({
+export const usersRelations = relations(users, ({ many, one }) => ({
+  oauthAccounts: many(oauthAccounts),
   otpCodes: many(otpCodes),
+  passwordHistory: many(passwordHistory),
+  personalAccessTokens: many(personalAccessTokens),
+  recoveryCodes: many(recoveryCodes),
+  refreshTokens: many(refreshTokens),
+  signInDevices: many(signInDevices),
+  subscriber: one(subscribers),
+  userRoles: many(userRoles),
   words: many(words),
 }))
 
@@ -19,6 +42,119 @@ export const otpCodesRelations = relations(otpCodes, ({ one }) => ({
   }),
 }))
 
+/**
+ * RECOVERY CODES RELATIONS
+ */
+export const recoveryCodesRelations = relations(recoveryCodes, ({ one }) => ({
+  user: one(users, {
+    fields: [recoveryCodes.user_id],
+    references: [users.id],
+  }),
+}))
+
+/**
+ * OAUTH ACCOUNTS RELATIONS
+ */
+export const oauthAccountsRelations = relations(oauthAccounts, ({ one }) => ({
+  user: one(users, {
+    fields: [oauthAccounts.user_id],
+    references: [users.id],
+  }),
+}))
+
+/**
+ * SIGN IN DEVICES RELATIONS
+ */
+export const signInDevicesRelations = relations(signInDevices, ({ one }) => ({
+  user: one(users, {
+    fields: [signInDevices.user_id],
+    references: [users.id],
+  }),
+}))
+
+/**
+ * SUBSCRIBERS RELATIONS
+ */
+export const subscribersRelations = relations(subscribers, ({ one }) => ({
+  user: one(users, {
+    fields: [subscribers.user_id],
+    references: [users.id],
+  }),
+}))
+
+/**
+ * PASSWORD HISTORY RELATIONS
+ */
+export const passwordHistoryRelations = relations(passwordHistory, ({ one }) => ({
+  user: one(users, {
+    fields: [passwordHistory.user_id],
+    references: [users.id],
+  }),
+}))
+
+/**
+ * ROLES RELATIONS
+ */
+export const rolesRelations = relations(roles, ({ many }) => ({
+  rolePermissions: many(rolePermissions),
+  userRoles: many(userRoles),
+}))
+
+/**
+ * PERMISSIONS RELATIONS
+ */
+export const permissionsRelations = relations(permissions, ({ many }) => ({
+  rolePermissions: many(rolePermissions),
+}))
+
+/**
+ * ROLE PERMISSIONS RELATIONS
+ */
+export const rolePermissionsRelations = relations(rolePermissions, ({ one }) => ({
+  permission: one(permissions, {
+    fields: [rolePermissions.permission_id],
+    references: [permissions.id],
+  }),
+  role: one(roles, {
+    fields: [rolePermissions.role_id],
+    references: [roles.id],
+  }),
+}))
+
+/**
+ * USER ROLES RELATIONS
+ */
+export const userRolesRelations = relations(userRoles, ({ one }) => ({
+  role: one(roles, {
+    fields: [userRoles.role_id],
+    references: [roles.id],
+  }),
+  user: one(users, {
+    fields: [userRoles.user_id],
+    references: [users.id],
+  }),
+}))
+
+/**
+ * PERSONAL ACCESS TOKENS RELATIONS
+ */
+export const personalAccessTokensRelations = relations(personalAccessTokens, ({ one }) => ({
+  user: one(users, {
+    fields: [personalAccessTokens.user_id],
+    references: [users.id],
+  }),
+}))
+
+/**
+ * REFRESH TOKENS RELATIONS
+ */
+export const refreshTokensRelations = relations(refreshTokens, ({ one }) => ({
+  user: one(users, {
+    fields: [refreshTokens.user_id],
+    references: [users.id],
+  }),
+}))
+
 /**
  * Words RELATIONS
  */
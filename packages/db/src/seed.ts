@@ -1,7 +1,8 @@
 import * as crypto from 'crypto'
 import { eq } from 'drizzle-orm'
 import { db } from './db'
-import { otpCodes, users, words } from './tables'
+import { PERMISSIONS, ROLE_PERMISSIONS, ROLES } from './constants'
+import { otpCodes, permissions, rolePermissions, roles, userRoles, users, words } from './tables'
 
 /**
  * Helper function to hash passwords.
@@ -11,13 +12,6 @@ function hashPassword(password: string): string {
   return crypto.createHash('sha256').update(password).digest('hex')
 }
 
-/**
- * Helper function to generate a random 6-digit OTP code.
- */
-function generateOTP(): string {
-  return Math.floor(100000 + Math.random() * 900000).toString()
-}
-
 /**
  * Helper function to get a random date within a given range.
  */
@@ -37,6 +31,39 @@ async function seed() {
     await db.delete(words)
     await db.delete(otpCodes)
     await db.delete(users)
+    await db.delete(rolePermissions)
+    await db.delete(roles)
+    await db.delete(permissions)
+
+    // Seed roles and their permissions
+    console.log('🛡 Seeding roles and permissions...')
+    const insertedRoles = await db
+      .insert(roles)
+      .values(ROLES.map((name) => ({ name })))
+      .returning()
+    const insertedPermissions = await db
+      .insert(permissions)
+      .values(PERMISSIONS.map((name) => ({ name })))
+      .returning()
+
+    const roleMap: Record<string, string> = {}
+    insertedRoles.forEach((r) => {
+      roleMap[r.name] = r.id
+    })
+    const permissionMap: Record<string, string> = {}
+    insertedPermissions.forEach((p) => {
+      permissionMap[p.name] = p.id
+    })
+
+    await db.insert(rolePermissions).values(
+      ROLES.flatMap((role) =>
+        ROLE_PERMISSIONS[role].map((permission) => ({
+          permission_id: permissionMap[permission],
+          role_id: roleMap[role],
+        })),
+      ),
+    )
+    console.log(`   Created ${insertedRoles.length} roles and ${insertedPermissions.length} permissions.`)
 
     // Create Users
     console.log('👤 Seeding users...')
@@ -46,6 +73,7 @@ async function seed() {
         avatar_url: 'https://api.dicebear.com/7.x/avataaars/svg?seed=john',
         created_at: new Date(Date.now() - 85 * 24 * 60 * 60 * 1000),
         email: 'john.doe@example.com',
+        email_verified_at: now,
         first_name: 'John',
         last_name: 'Doe',
         username: 'johndoe',
@@ -58,18 +86,20 @@ async function seed() {
         avatar_url: 'https://api.dicebear.com/7.x/avataaars/svg?seed=jane',
         created_at: new Date(Date.now() - 70 * 24 * 60 * 60 * 1000),
         email: 'jane.smith@example.com',
+        email_verified_at: now,
         first_name: 'Jane',
         last_name: 'Smith',
         username: 'janesmith',
         password_hash: hashPassword('password123'),
         is_active: true,
-        settings: { notifications: false, theme: 'light' },
+        settings: { locale: 'es', notifications: false, theme: 'light', timezone: 'Europe/Madrid' },
         last_login_at: new Date(Date.now() - 86400000),
       },
       {
         avatar_url: 'https://api.dicebear.com/7.x/avataaars/svg?seed=bob',
         created_at: new Date(Date.now() - 60 * 24 * 60 * 60 * 1000),
         email: 'bob.johnson@example.com',
+        email_verified_at: now,
         first_name: 'Bob',
         last_name: 'Johnson',
         username: 'bobjohnson',
@@ -82,6 +112,7 @@ async function seed() {
         avatar_url: 'https://api.dicebear.com/7.x/avataaars/svg?seed=alice',
         created_at: new Date(Date.now() - 45 * 24 * 60 * 60 * 1000),
         email: 'alice.williams@example.com',
+        email_verified_at: now,
         first_name: 'Alice',
         last_name: 'Williams',
         username: 'alicewilliams',
@@ -101,6 +132,12 @@ async function seed() {
       userMap[u.username] = u.id
     })
 
+    // Everyone is a member; John administers the seeded data
+    await db.insert(userRoles).values([
+      ...insertedUsers.map((u) => ({ role_id: roleMap['member'], user_id: u.id })),
+      { role_id: roleMap['admin'], user_id: userMap['johndoe'] },
+    ])
+
     // Seed Words
     console.log('🗣 Seeding words...')
     const wordsData = [
@@ -150,31 +187,8 @@ async function seed() {
     const insertedWords = await db.insert(words).values(wordsData).returning()
     console.log(`   Created ${insertedWords.length} words.`)
 
-    // Seed OTP Codes
-    console.log('🔐 Seeding OTP codes...')
-    const otpCodesData = [
-      {
-        code: generateOTP(),
-        expires_at: new Date(Date.now() + 10 * 60 * 1000),
-        is_active: true,
-        user_id: userMap['johndoe'],
-      },
-      {
-        code: generateOTP(),
-        expires_at: new Date(Date.now() + 15 * 60 * 1000),
-        is_active: true,
-        user_id: userMap['janesmith'],
-      },
-      {
-        code: generateOTP(),
-        expires_at: new Date(Date.now() - 5 * 60 * 1000),
-        is_active: false,
-        user_id: userMap['johndoe'],
-      },
-    ]
-
-    const insertedOtpCodes = await db.insert(otpCodes).values(otpCodesData).returning()
-    console.log(`   Created ${insertedOtpCodes.length} OTP codes.`)
+    // No OTP codes: they are HMACed with the server's SESSION_SECRET, so only the API can issue
+    // them (ask for one and read it from the mail catcher)
 
     // Update login timestamps for active users
     console.log('📊 Updating user login history...')
@@ -187,7 +201,6 @@ async function seed() {
     console.log('\n📊 Summary:')
     console.log(`   - Users: ${insertedUsers.length}`)
     console.log(`   - Words: ${insertedWords.length}`)
-    console.log(`   - OTP Codes: ${insertedOtpCodes.length}`)
 
     console.log('\n🔒 Test Credentials:')
     console.log('   - Email: john.doe@example.com')
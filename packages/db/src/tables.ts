@@ -6,15 +6,19 @@ import {
   jsonb,
   pgEnum,
   pgTable,
+  primaryKey,
   text,
   timestamp,
   uniqueIndex,
   uuid,
   varchar,
 } from 'drizzle-orm/pg-core'
-import { ACCESS_TOKENS_STATUSES } from './constants'
+import { ACCESS_TOKENS_STATUSES, EMAIL_OUTBOX_STATUSES, OTP_PURPOSES, SUBSCRIBER_STATUSES } from './constants'
 
 export const tokenStatus = pgEnum('token_status', ACCESS_TOKENS_STATUSES)
+export const otpPurpose = pgEnum('otp_purpose', OTP_PURPOSES)
+export const emailOutboxStatus = pgEnum('email_outbox_status', EMAIL_OUTBOX_STATUSES)
+export const subscriberStatus = pgEnum('subscriber_status', SUBSCRIBER_STATUSES)
 
 /**
  * @name USERS
@@ -27,6 +31,7 @@ export const users = pgTable(
     created_at: timestamp('created_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
     deleted_at: timestamp('deleted_at', { withTimezone: true }),
     email: varchar('email', { length: 255 }).notNull().unique(),
+    email_verified_at: timestamp('email_verified_at', { withTimezone: true }),
     first_name: varchar('first_name', { length: 100 }).notNull(),
     id: uuid('id').primaryKey().default(sql`gen_random_uuid()`),
     is_active: boolean('is_active').default(true).notNull(),
@@ -34,6 +39,8 @@ export const users = pgTable(
     last_name: varchar('last_name', { length: 100 }).notNull(),
     password_hash: varchar('password_hash', { length: 255 }).notNull(),
     settings: jsonb('settings').default(sql`'{}'::jsonb`),
+    totp_enabled_at: timestamp('totp_enabled_at', { withTimezone: true }),
+    totp_secret: varchar('totp_secret', { length: 255 }),
     updated_at: timestamp('updated_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
     username: varchar('username', { length: 100 }).notNull().unique(),
     version: integer('version').default(1).notNull(),
@@ -52,12 +59,14 @@ export const users = pgTable(
 export const otpCodes = pgTable(
   'otp_codes',
   {
-    code: varchar('code', { length: 6 }).notNull(),
+    attempts: integer('attempts').default(0).notNull(),
+    code_hash: varchar('code_hash', { length: 255 }).notNull(),
     created_at: timestamp('created_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
     deleted_at: timestamp('deleted_at', { withTimezone: true }),
     expires_at: timestamp('expires_at', { withTimezone: true }).notNull(),
     id: uuid('id').primaryKey().default(sql`gen_random_uuid()`),
     is_active: boolean('is_active').default(true).notNull(),
+    purpose: otpPurpose('purpose').default('reset_password').notNull(),
     updated_at: timestamp('updated_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
     user_id: uuid('user_id')
       .notNull()
@@ -66,6 +75,238 @@ export const otpCodes = pgTable(
   (table) => [
     index('active_codes_idx').on(table.is_active, table.expires_at).where(sql`deleted_at IS NULL`),
     index('user_codes_idx').on(table.user_id, table.created_at),
+    index('user_purpose_codes_idx').on(table.user_id, table.purpose),
+  ],
+)
+
+/**
+ * @name RECOVERY CODES
+ * @description One-time codes that replace the TOTP code when the authenticator is lost
+ */
+export const recoveryCodes = pgTable(
+  'recovery_codes',
+  {
+    code_hash: varchar('code_hash', { length: 255 }).notNull(),
+    created_at: timestamp('created_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+    id: uuid('id').primaryKey().default(sql`gen_random_uuid()`),
+    used_at: timestamp('used_at', { withTimezone: true }),
+    user_id: uuid('user_id')
+      .notNull()
+      .references(() => users.id, { onDelete: 'cascade' }),
+  },
+  (table) => [index('user_recovery_codes_idx').on(table.user_id)],
+)
+
+/**
+ * @name OAUTH ACCOUNTS
+ * @description External identities (GitHub, Google, OIDC) linked to a user
+ */
+export const oauthAccounts = pgTable(
+  'oauth_accounts',
+  {
+    created_at: timestamp('created_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+    email: varchar('email', { length: 255 }).notNull(),
+    id: uuid('id').primaryKey().default(sql`gen_random_uuid()`),
+    provider: varchar('provider', { length: 50 }).notNull(),
+    provider_user_id: varchar('provider_user_id', { length: 255 }).notNull(),
+    user_id: uuid('user_id')
+      .notNull()
+      .references(() => users.id, { onDelete: 'cascade' }),
+  },
+  (table) => [
+    uniqueIndex('provider_account_idx').on(table.provider, table.provider_user_id),
+    index('user_oauth_accounts_idx').on(table.user_id),
+  ],
+)
+
+/**
+ * @name SIGN IN DEVICES
+ * @description Devices users signed in from, to spot sign-ins from somewhere new
+ */
+export const signInDevices = pgTable(
+  'sign_in_devices',
+  {
+    fingerprint: varchar('fingerprint', { length: 255 }).notNull(),
+    first_seen_at: timestamp('first_seen_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+    id: uuid('id').primaryKey().default(sql`gen_random_uuid()`),
+    ip: varchar('ip', { length: 255 }).notNull(),
+    last_seen_at: timestamp('last_seen_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+    location: varchar('location', { length: 255 }),
+    user_agent: text('user_agent').notNull(),
+    user_id: uuid('user_id')
+      .notNull()
+      .references(() => users.id, { onDelete: 'cascade' }),
+  },
+  (table) => [uniqueIndex('user_device_fingerprint_idx').on(table.user_id, table.fingerprint)],
+)
+
+/**
+ * @name PASSWORD HISTORY
+ * @description Hashes of the last passwords each user set, so they can't be reused
+ */
+export const passwordHistory = pgTable(
+  'password_history',
+  {
+    created_at: timestamp('created_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+    id: uuid('id').primaryKey().default(sql`gen_random_uuid()`),
+    password_hash: varchar('password_hash', { length: 255 }).notNull(),
+    user_id: uuid('user_id')
+      .notNull()
+      .references(() => users.id, { onDelete: 'cascade' }),
+  },
+  (table) => [index('user_password_history_idx').on(table.user_id, table.created_at)],
+)
+
+/**
+ * @name EMAIL OUTBOX
+ * @description Emails waiting to be delivered, written with the change that triggered them
+ */
+export const emailOutbox = pgTable(
+  'email_outbox',
+  {
+    attempts: integer('attempts').default(0).notNull(),
+    created_at: timestamp('created_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+    html: text('html').notNull(),
+    id: uuid('id').primaryKey().default(sql`gen_random_uuid()`),
+    last_error: text('last_error'),
+    list_unsubscribe: text('list_unsubscribe'),
+    next_attempt_at: timestamp('next_attempt_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+    recipient: varchar('recipient', { length: 255 }).notNull(),
+    reply_to: varchar('reply_to', { length: 255 }),
+    sender: varchar('sender', { length: 255 }).notNull(),
+    sent_at: timestamp('sent_at', { withTimezone: true }),
+    status: emailOutboxStatus('status').default('pending').notNull(),
+    subject: varchar('subject', { length: 255 }).notNull(),
+    text: text('text').notNull(),
+    updated_at: timestamp('updated_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+  },
+  (table) => [index('email_outbox_due_idx').on(table.status, table.next_attempt_at)],
+)
+
+/**
+ * @name SUBSCRIBERS
+ * @description Newsletter subscribers, anonymous or tied to an account; confirmed by double opt-in
+ */
+export const subscribers = pgTable(
+  'subscribers',
+  {
+    confirmation_sent_at: timestamp('confirmation_sent_at', { withTimezone: true }),
+    confirmed_at: timestamp('confirmed_at', { withTimezone: true }),
+    created_at: timestamp('created_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+    email: varchar('email', { length: 255 }).notNull().unique(),
+    id: uuid('id').primaryKey().default(sql`gen_random_uuid()`),
+    locale: varchar('locale', { length: 16 }).default('en').notNull(),
+    status: subscriberStatus('status').default('pending').notNull(),
+    unsubscribed_at: timestamp('unsubscribed_at', { withTimezone: true }),
+    updated_at: timestamp('updated_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+    user_id: uuid('user_id')
+      .unique()
+      .references(() => users.id, { onDelete: 'set null' }),
+  },
+  (table) => [index('subscribers_status_idx').on(table.status)],
+)
+
+/**
+ * @name ROLES
+ * @description Named bundles of permissions granted to users
+ */
+export const roles = pgTable('roles', {
+  created_at: timestamp('created_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+  description: text('description'),
+  id: uuid('id').primaryKey().default(sql`gen_random_uuid()`),
+  name: varchar('name', { length: 255 }).notNull().unique(),
+})
+
+/**
+ * @name PERMISSIONS
+ * @description The individual actions a role can allow, e.g. `words:write`
+ */
+export const permissions = pgTable('permissions', {
+  created_at: timestamp('created_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+  description: text('description'),
+  id: uuid('id').primaryKey().default(sql`gen_random_uuid()`),
+  name: varchar('name', { length: 255 }).notNull().unique(),
+})
+
+/**
+ * @name ROLE PERMISSIONS
+ * @description Which permissions each role carries
+ */
+export const rolePermissions = pgTable(
+  'role_permissions',
+  {
+    permission_id: uuid('permission_id')
+      .notNull()
+      .references(() => permissions.id, { onDelete: 'cascade' }),
+    role_id: uuid('role_id')
+      .notNull()
+      .references(() => roles.id, { onDelete: 'cascade' }),
+  },
+  (table) => [primaryKey({ columns: [table.role_id, table.permission_id] })],
+)
+
+/**
+ * @name USER ROLES
+ * @description Roles granted to each user
+ */
+export const userRoles = pgTable(
+  'user_roles',
+  {
+    created_at: timestamp('created_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+    granted_by: uuid('granted_by').references(() => users.id, { onDelete: 'set null' }),
+    role_id: uuid('role_id')
+      .notNull()
+      .references(() => roles.id, { onDelete: 'cascade' }),
+    user_id: uuid('user_id')
+      .notNull()
+      .references(() => users.id, { onDelete: 'cascade' }),
+  },
+  (table) => [primaryKey({ columns: [table.user_id, table.role_id] })],
+)
+
+/**
+ * @name PERSONAL ACCESS TOKENS
+ * @description Long-lived, scoped API tokens for scripts; only the hash is kept
+ */
+export const personalAccessTokens = pgTable(
+  'personal_access_tokens',
+  {
+    created_at: timestamp('created_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+    expires_at: timestamp('expires_at', { withTimezone: true }).notNull(),
+    id: uuid('id').primaryKey().default(sql`gen_random_uuid()`),
+    last_used_at: timestamp('last_used_at', { withTimezone: true }),
+    name: varchar('name', { length: 255 }).notNull(),
+    scopes: text('scopes').array().notNull(),
+    status: tokenStatus('status').default('active').notNull(),
+    token_hash: varchar('token_hash', { length: 255 }).notNull().unique(),
+    user_id: uuid('user_id')
+      .notNull()
+      .references(() => users.id, { onDelete: 'cascade' }),
+  },
+  (table) => [index('user_personal_access_tokens_idx').on(table.user_id)],
+)
+
+/**
+ * @name REFRESH TOKENS
+ * @description Hashed refresh tokens for bearer auth; every rotation stays in the same family
+ */
+export const refreshTokens = pgTable(
+  'refresh_tokens',
+  {
+    created_at: timestamp('created_at', { withTimezone: true }).default(sql`CURRENT_TIMESTAMP`).notNull(),
+    expires_at: timestamp('expires_at', { withTimezone: true }).notNull(),
+    family_id: uuid('family_id').notNull(),
+    id: uuid('id').primaryKey().default(sql`gen_random_uuid()`),
+    status: tokenStatus('status').default('active').notNull(),
+    token_hash: varchar('token_hash', { length: 255 }).notNull().unique(),
+    used_at: timestamp('used_at', { withTimezone: true }),
+    user_id: uuid('user_id')
+      .notNull()
+      .references(() => users.id, { onDelete: 'cascade' }),
+  },
+  (table) => [
+    index('user_refresh_tokens_idx').on(table.user_id),
+    index('family_refresh_tokens_idx').on(table.family_id),
   ],
 )
 
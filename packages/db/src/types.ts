@@ -1,5 +1,21 @@
 import { InferInsertModel, InferSelectModel } from 'drizzle-orm'
-import { otpCodes, words, users } from './tables'
+import {
+  emailOutbox,
+  oauthAccounts,
+  otpCodes,
+  passwordHistory,
+  permissions,
+  personalAccessTokens,
+  recoveryCodes,
+  refreshTokens,
+  rolePermissions,
+  roles,
+  signInDevices,
+  subscribers,
+  userRoles,
+  words,
+  users,
+} from './tables'
 
 // ========== USERS ==========
 export type User = InferSelectModel<typeof users>
@@ -9,6 +25,54 @@ export type NewUser = InferInsertModel<typeof users>
 export type OtpCode = InferSelectModel<typeof otpCodes>
 export type NewOtpCode = InferInsertModel<typeof otpCodes>
 
+// ========== RECOVERY CODES ==========
+export type RecoveryCode = InferSelectModel<typeof recoveryCodes>
+export type NewRecoveryCode = InferInsertModel<typeof recoveryCodes>
+
+// ========== OAUTH ACCOUNTS ==========
+export type OauthAccount = InferSelectModel<typeof oauthAccounts>
+export type NewOauthAccount = InferInsertModel<typeof oauthAccounts>
+
+// ========== SIGN IN DEVICES ==========
+export type SignInDevice = InferSelectModel<typeof signInDevices>
+export type NewSignInDevice = InferInsertModel<typeof signInDevices>
+
+// ========== PASSWORD HISTORY ==========
+export type PasswordHistory = InferSelectModel<typeof passwordHistory>
+export type NewPasswordHistory = InferInsertModel<typeof passwordHistory>
+
+// ========== EMAIL OUTBOX ==========
+export type EmailOutbox = InferSelectModel<typeof emailOutbox>
+export type NewEmailOutbox = InferInsertModel<typeof emailOutbox>
+
+// ========== SUBSCRIBERS ==========
+export type Subscriber = InferSelectModel<typeof subscribers>
+export type NewSubscriber = InferInsertModel<typeof subscribers>
+
+// ========== ROLES ==========
+export type Role = InferSelectModel<typeof roles>
+export type NewRole = InferInsertModel<typeof roles>
+
+// ========== PERMISSIONS ==========
+export type Permission = InferSelectModel<typeof permissions>
+export type NewPermission = InferInsertModel<typeof permissions>
+
+// ========== ROLE PERMISSIONS ==========
+export type RolePermission = InferSelectModel<typeof rolePermissions>
+export type NewRolePermission = InferInsertModel<typeof rolePermissions>
+
+// ========== USER ROLES ==========
+export type UserRole = InferSelectModel<typeof userRoles>
+export type NewUserRole = InferInsertModel<typeof userRoles>
+
+// ========== PERSONAL ACCESS TOKENS ==========
+export type PersonalAccessToken = InferSelectModel<typeof personalAccessTokens>
+export type NewPersonalAccessToken = InferInsertModel<typeof personalAccessTokens>
+
+// ========== REFRESH TOKENS ==========
+export type RefreshToken = InferSelectModel<typeof refreshTokens>
+export type NewRefreshToken = InferInsertModel<typeof refreshTokens>
+
 // ========== WORDS ==========
 export type Word = InferSelectModel<typeof words>
 export type NewWord = InferInsertModel<typeof words>